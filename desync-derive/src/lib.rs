@@ -0,0 +1,168 @@
+//!
+//! # desync-derive
+//!
+//! Derive macros for the `desync` crate.
+//!
+//! `#[derive(DesyncWrapper)]` can be used on a struct that contains a `Desync<Inner>` (or an
+//! `Arc<Desync<Inner>>`) to generate `desync()`, `sync()`, `future()` and `after()` methods
+//! that delegate to that field. This removes the boilerplate from the common pattern of an
+//! actor-like struct that wraps its state in a `Desync`:
+//!
+//! ```ignore
+//! #[derive(DesyncWrapper)]
+//! struct Counter(Arc<Desync<i32>>);
+//!
+//! let counter = Counter(Arc::new(Desync::new(0)));
+//! counter.desync(|count| *count += 1);
+//! ```
+//!
+//! If the struct has more than one field with a `Desync` type, the one to delegate to should
+//! be marked with the `#[desync]` attribute. The generated `future()` and `after()` methods
+//! refer to the `futures` crate, so it must be a dependency of the crate using the macro.
+//!
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::{TokenStream as TokenStream2};
+use quote::{quote};
+use syn::{parse_macro_input, DeriveInput, Data, Fields, Field, Type, PathArguments, GenericArgument, Index, Member};
+
+///
+/// Derives `desync()`, `sync()`, `future()` and `after()` methods that delegate to a `Desync` field of a struct
+///
+#[proc_macro_derive(DesyncWrapper, attributes(desync))]
+pub fn derive_desync_wrapper(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match desync_wrapper(&input) {
+        Ok(tokens)  => tokens.into(),
+        Err(err)    => err.to_compile_error().into()
+    }
+}
+
+///
+/// Generates the implementation of the DesyncWrapper methods for a struct
+///
+fn desync_wrapper(input: &DeriveInput) -> Result<TokenStream2, syn::Error> {
+    // Find the field to delegate to
+    let fields = match &input.data {
+        Data::Struct(data)  => &data.fields,
+        _                   => return Err(syn::Error::new_spanned(&input.ident, "DesyncWrapper can only be derived for structs"))
+    };
+    let (member, inner) = desync_field(fields).ok_or_else(|| syn::Error::new_spanned(&input.ident, "DesyncWrapper requires a field of type Desync<T> or Arc<Desync<T>> (mark it with #[desync] if there is more than one)"))?;
+
+    // Generate the implementation
+    let name                                        = &input.ident;
+    let (impl_generics, ty_generics, where_clause)  = input.generics.split_for_impl();
+    let where_predicates                            = where_clause.map(|where_clause| &where_clause.predicates);
+
+    Ok(quote! {
+        impl #impl_generics #name #ty_generics
+        where #inner: 'static+Send+Unpin, #where_predicates {
+            ///
+            /// Performs an operation asynchronously on the wrapped `Desync` object, returning a handle that can cancel it
+            ///
+            pub fn desync<TFn>(&self, job: TFn) -> ::desync::scheduler::JobHandle
+            where TFn: 'static+Send+FnOnce(&mut #inner) {
+                self.#member.desync(job)
+            }
+
+            ///
+            /// Performs an operation synchronously on the wrapped `Desync` object
+            ///
+            pub fn sync<TFn, TResult>(&self, job: TFn) -> TResult
+            where TFn: Send+FnOnce(&mut #inner) -> TResult, TResult: Send {
+                self.#member.sync(job)
+            }
+
+            ///
+            /// Performs an operation asynchronously on the wrapped `Desync` object, returning the result via a future
+            ///
+            pub fn future<TFn, TOutput>(&self, job: TFn) -> impl ::std::future::Future<Output=::std::result::Result<TOutput, ::futures::channel::oneshot::Canceled>>+Send
+            where   TFn:        'static+Send+for<'a> FnOnce(&'a mut #inner) -> ::futures::future::BoxFuture<'a, TOutput>,
+                    TOutput:    'static+Send {
+                self.#member.future(job)
+            }
+
+            ///
+            /// After the pending operations for the wrapped `Desync` object are performed, waits for the supplied future
+            /// to complete and then calls the specified function
+            ///
+            pub fn after<TFn, TResult, TFuture>(&self, after: TFuture, job: TFn) -> impl 'static+::std::future::Future<Output=::std::result::Result<TResult, ::futures::channel::oneshot::Canceled>>+Send
+            where   TFn:        'static+Send+FnOnce(&mut #inner, TFuture::Output) -> TResult,
+                    TResult:    'static+Send,
+                    TFuture:    'static+::std::future::Future+Send {
+                self.#member.after(after, job)
+            }
+        }
+    })
+}
+
+///
+/// Finds the field that contains the `Desync` object, returning how to access it and the type of the data it contains
+///
+fn desync_field(fields: &Fields) -> Option<(Member, Type)> {
+    let candidates = fields.iter().enumerate()
+        .filter_map(|(index, field)| desync_inner_type(&field.ty).map(|inner| (index, field, inner)))
+        .collect::<Vec<_>>();
+
+    // Prefer the field marked with #[desync], otherwise there must be exactly one candidate
+    let marked = candidates.iter().find(|(_, field, _)| field.attrs.iter().any(|attr| attr.path().is_ident("desync")));
+    let chosen = match (marked, candidates.len()) {
+        (Some(marked), _)   => marked,
+        (None, 1)           => &candidates[0],
+        _                   => return None
+    };
+
+    let (index, field, inner) = chosen;
+    Some((field_member(*index, field), inner.clone()))
+}
+
+///
+/// Returns the member used to access a field
+///
+fn field_member(index: usize, field: &Field) -> Member {
+    match &field.ident {
+        Some(ident) => Member::Named(ident.clone()),
+        None        => Member::Unnamed(Index::from(index))
+    }
+}
+
+///
+/// If a type is `Desync<T>` or `Arc<Desync<T>>`, returns `T`
+///
+fn desync_inner_type(ty: &Type) -> Option<Type> {
+    let (name, argument) = single_type_argument(ty)?;
+
+    match name.as_str() {
+        "Desync"    => Some(argument),
+        "Arc"       => {
+            let (name, argument) = single_type_argument(&argument)?;
+            if name == "Desync" { Some(argument) } else { None }
+        },
+        _           => None
+    }
+}
+
+///
+/// If a type is a path with a single generic type argument (like `Foo<T>`), returns the name of the type and the argument
+///
+fn single_type_argument(ty: &Type) -> Option<(String, Type)> {
+    let path    = match ty {
+        Type::Path(path)    => &path.path,
+        _                   => return None
+    };
+    let segment = path.segments.last()?;
+
+    match &segment.arguments {
+        PathArguments::AngleBracketed(arguments) if arguments.args.len() == 1 => {
+            match &arguments.args[0] {
+                GenericArgument::Type(argument) => Some((segment.ident.to_string(), argument.clone())),
+                _                               => None
+            }
+        },
+
+        _ => None
+    }
+}
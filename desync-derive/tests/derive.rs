@@ -0,0 +1,74 @@
+extern crate desync;
+extern crate desync_derive;
+extern crate futures;
+
+use desync::Desync;
+use desync_derive::DesyncWrapper;
+use futures::executor;
+use futures::future::{self, FutureExt};
+
+use std::sync::*;
+
+#[derive(DesyncWrapper)]
+struct Counter(Arc<Desync<i32>>);
+
+#[derive(DesyncWrapper)]
+struct Named {
+    name:   String,
+    state:  Desync<Vec<String>>
+}
+
+#[derive(DesyncWrapper)]
+struct Marked {
+    _other:     Desync<i32>,
+    #[desync]
+    state:      Desync<i32>
+}
+
+#[test]
+fn delegate_to_tuple_field() {
+    let counter = Counter(Arc::new(Desync::new(0)));
+
+    counter.desync(|count| *count += 1);
+    counter.desync(|count| *count += 2);
+
+    assert!(counter.sync(|count| *count) == 3);
+}
+
+#[test]
+fn delegate_to_named_field() {
+    let named = Named { name: "test".to_string(), state: Desync::new(vec![]) };
+    let name  = named.name.clone();
+
+    named.desync(move |state| state.push(name));
+
+    assert!(named.sync(|state| state.clone()) == vec!["test".to_string()]);
+}
+
+#[test]
+fn delegate_future() {
+    let counter = Counter(Arc::new(Desync::new(1)));
+    let result  = counter.future(|count| {
+        *count += 1;
+        future::ready(*count).boxed()
+    });
+
+    assert!(executor::block_on(result) == Ok(2));
+}
+
+#[test]
+fn delegate_after() {
+    let counter = Counter(Arc::new(Desync::new(1)));
+    let result  = counter.after(future::ready(41), |count, val| { *count += val; *count });
+
+    assert!(executor::block_on(result) == Ok(42));
+}
+
+#[test]
+fn delegate_to_marked_field() {
+    let marked = Marked { _other: Desync::new(0), state: Desync::new(0) };
+
+    marked.desync(|val| *val = 42);
+
+    assert!(marked.state.sync(|val| *val) == 42);
+}
@@ -0,0 +1,163 @@
+//!
+//! Actors provide a message-passing interface to a `Desync` object
+//!
+//! The actor model maps directly on to `Desync`: an actor's state is the data stored in the
+//! object and messages are processed by jobs scheduled on its queue. A `DesyncActor` formalises
+//! this by fixing the function used to process messages when the actor is created, so the
+//! only thing other code needs to do to interact with it is to send messages.
+//!
+//! ```
+//! # extern crate futures;
+//! # extern crate desync;
+//! # use ::desync::*;
+//! # use futures::executor;
+//! enum CounterMessage { Add(i32), Subtract(i32) }
+//!
+//! let counter = DesyncActor::new(0, |count: &mut i32, msg| {
+//!     match msg {
+//!         CounterMessage::Add(val)        => *count += val,
+//!         CounterMessage::Subtract(val)   => *count -= val
+//!     }
+//! });
+//!
+//! let addr = counter.addr();
+//! addr.send(CounterMessage::Add(3));
+//! executor::block_on(addr.send_and_wait(CounterMessage::Subtract(1))).unwrap();
+//! ```
+//!
+
+use super::desync::*;
+
+use futures::future;
+use futures::future::{Future, BoxFuture, FutureExt};
+use futures::channel::oneshot;
+
+use std::sync::*;
+
+/// Function used by an actor to process its messages
+type MessageHandler<State, Msg> = Arc<dyn Fn(&mut State, Msg)+Send+Sync>;
+
+///
+/// Trait implemented by the core of an actor, used so that addresses don't need to know the type of the actor's state
+///
+trait MessageTarget<Msg>: Send+Sync {
+    /// Queues a message to be processed by the actor
+    fn send(&self, msg: Msg);
+
+    /// Queues a message and returns a future that completes once it has been processed
+    fn send_and_wait(&self, msg: Msg) -> BoxFuture<'static, Result<(), oneshot::Canceled>>;
+}
+
+///
+/// The state of an actor and the function used to process its messages
+///
+struct ActorCore<Msg, State: Send+Unpin> {
+    /// The state of the actor
+    state: Desync<State>,
+
+    /// The function that processes each message
+    handler: MessageHandler<State, Msg>
+}
+
+///
+/// An actor processes messages, in the order they were sent, using the state stored in a `Desync` object
+///
+pub struct DesyncActor<Msg, State: Send+Unpin> {
+    core: Arc<ActorCore<Msg, State>>
+}
+
+///
+/// The address of an actor: this can be used to send messages to the actor without needing to know the type of its state
+///
+/// Addresses keep the actor they refer to alive.
+///
+pub struct ActorAddr<Msg> {
+    target: Arc<dyn MessageTarget<Msg>>
+}
+
+impl<Msg, State> MessageTarget<Msg> for ActorCore<Msg, State>
+where   Msg:    'static+Send,
+        State:  'static+Send+Unpin {
+    fn send(&self, msg: Msg) {
+        let handler = Arc::clone(&self.handler);
+
+        self.state.desync(move |state| handler(state, msg));
+    }
+
+    fn send_and_wait(&self, msg: Msg) -> BoxFuture<'static, Result<(), oneshot::Canceled>> {
+        let handler = Arc::clone(&self.handler);
+
+        self.state.future(move |state| {
+            handler(state, msg);
+            future::ready(()).boxed()
+        }).boxed()
+    }
+}
+
+impl<Msg, State> DesyncActor<Msg, State>
+where   Msg:    'static+Send,
+        State:  'static+Send+Unpin {
+    ///
+    /// Creates a new actor with an initial state and a function for processing messages sent to it
+    ///
+    pub fn new<HandlerFn>(state: State, handler: HandlerFn) -> DesyncActor<Msg, State>
+    where HandlerFn: 'static+Send+Sync+Fn(&mut State, Msg) {
+        let core = ActorCore {
+            state:      Desync::new(state),
+            handler:    Arc::new(handler)
+        };
+
+        DesyncActor {
+            core: Arc::new(core)
+        }
+    }
+
+    ///
+    /// Sends a message to this actor, which will be processed in the background
+    ///
+    pub fn send(&self, msg: Msg) {
+        self.core.send(msg)
+    }
+
+    ///
+    /// Sends a message to this actor, returning a future that completes once the message has been processed
+    ///
+    pub fn send_and_wait(&self, msg: Msg) -> impl Future<Output=Result<(), oneshot::Canceled>>+Send {
+        self.core.send_and_wait(msg)
+    }
+
+    ///
+    /// Retrieves an address that can be used to send messages to this actor
+    ///
+    pub fn addr(&self) -> ActorAddr<Msg> {
+        let target: Arc<dyn MessageTarget<Msg>> = self.core.clone();
+
+        ActorAddr {
+            target
+        }
+    }
+}
+
+impl<Msg> ActorAddr<Msg> {
+    ///
+    /// Sends a message to the actor, which will be processed in the background
+    ///
+    pub fn send(&self, msg: Msg) {
+        self.target.send(msg)
+    }
+
+    ///
+    /// Sends a message to the actor, returning a future that completes once the message has been processed
+    ///
+    pub fn send_and_wait(&self, msg: Msg) -> impl Future<Output=Result<(), oneshot::Canceled>>+Send {
+        self.target.send_and_wait(msg)
+    }
+}
+
+impl<Msg> Clone for ActorAddr<Msg> {
+    fn clone(&self) -> ActorAddr<Msg> {
+        ActorAddr {
+            target: Arc::clone(&self.target)
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//!
+//! `AsyncIter` turns a `Desync` object containing an iterator into a stream
+//!
+//! Each item is fetched by a job on the queue of the `Desync` object, so calls to the
+//! iterator are serialized with any other jobs scheduled on the object. This is useful for
+//! iterators that block, such as the lines of a file:
+//!
+//! ```
+//! # extern crate futures;
+//! # extern crate desync;
+//! # use ::desync::*;
+//! # use futures::executor;
+//! # use futures::prelude::*;
+//! let numbers = Desync::new(vec![1, 2, 3].into_iter());
+//! let all     = executor::block_on(numbers.async_iter().collect::<Vec<_>>());
+//!
+//! assert!(all == vec![1, 2, 3]);
+//! ```
+//!
+//! `into_stream()` is a more general version of this for objects shared using an `Arc`: it creates a
+//! stream that owns a reference to the object and calls a function to produce each item.
+//!
+
+use super::desync::*;
+
+use futures::future;
+use futures::future::{BoxFuture, FutureExt};
+use futures::stream::{Stream};
+use futures::task::{Context, Poll};
+use futures::channel::oneshot;
+
+use std::pin::*;
+use std::sync::*;
+
+///
+/// Function that produces the next item for a stream created by `into_stream()`
+///
+type NextItemFn<T, TItem> = dyn Fn(&mut T) -> Option<TItem>+Send+Sync;
+
+///
+/// Stream that reads the items from an iterator stored in a `Desync` object
+///
+pub struct AsyncIter<'a, T: 'static+Iterator+Send+Unpin>
+where T::Item: 'static+Send {
+    /// The object containing the iterator
+    desync: &'a Desync<T>,
+
+    /// The job that is fetching the next item, if one is in progress
+    next_item: Option<BoxFuture<'static, Result<Option<T::Item>, oneshot::Canceled>>>,
+
+    /// Set to true once the iterator has been exhausted
+    finished: bool
+}
+
+///
+/// Stream created by `Desync::into_stream()`
+///
+pub struct DesyncIntoStream<T: 'static+Send+Unpin, TItem: 'static+Send> {
+    /// The object that the items are produced from
+    desync: Arc<Desync<T>>,
+
+    /// The function that produces each item
+    next_item_fn: Arc<NextItemFn<T, TItem>>,
+
+    /// The job that is producing the next item, if one is in progress
+    next_item: Option<BoxFuture<'static, Result<Option<TItem>, oneshot::Canceled>>>,
+
+    /// Set to true once the function has returned `None`
+    finished: bool
+}
+
+impl<T: 'static+Send+Unpin> Desync<T> {
+    ///
+    /// Converts a shared object into a stream, by calling a function on it to produce each item
+    ///
+    /// Each item is produced by a job on this object's queue when the stream is polled, so the queue is free to run
+    /// other jobs in between items. The stream ends when the function returns `None`, and keeps the object alive
+    /// until it's dropped.
+    ///
+    pub fn into_stream<TFn, TItem>(self: Arc<Self>, next_item: TFn) -> DesyncIntoStream<T, TItem>
+    where   TFn:    'static+Send+Sync+Fn(&mut T) -> Option<TItem>,
+            TItem:  'static+Send {
+        DesyncIntoStream {
+            desync:         self,
+            next_item_fn:   Arc::new(next_item),
+            next_item:      None,
+            finished:       false
+        }
+    }
+}
+
+impl<T: 'static+Iterator+Send+Unpin> Desync<T>
+where T::Item: 'static+Send {
+    ///
+    /// Returns a stream that reads the items from the iterator stored in this object
+    ///
+    /// Each item is fetched by scheduling a job on this object's queue when the stream is polled.
+    ///
+    pub fn async_iter(&self) -> AsyncIter<'_, T> {
+        AsyncIter {
+            desync:     self,
+            next_item:  None,
+            finished:   false
+        }
+    }
+}
+
+impl<'a, T: 'static+Iterator+Send+Unpin> Stream for AsyncIter<'a, T>
+where T::Item: 'static+Send {
+    type Item = T::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, context: &mut Context) -> Poll<Option<T::Item>> {
+        if self.finished { return Poll::Ready(None); }
+
+        // Schedule a job to fetch the next item if there isn't one already
+        let desync      = self.desync;
+        let next_item   = self.next_item.get_or_insert_with(|| {
+            desync.future(|iter| future::ready(iter.next()).boxed()).boxed()
+        });
+
+        // Wait for the job to complete
+        match next_item.poll_unpin(context) {
+            Poll::Pending       => Poll::Pending,
+            Poll::Ready(item)   => {
+                self.next_item = None;
+
+                // The stream finishes when the iterator is exhausted (or if the job was cancelled)
+                let item = item.ok().flatten();
+                if item.is_none() { self.finished = true; }
+
+                Poll::Ready(item)
+            }
+        }
+    }
+}
+
+impl<T: 'static+Send+Unpin, TItem: 'static+Send> Stream for DesyncIntoStream<T, TItem> {
+    type Item = TItem;
+
+    fn poll_next(mut self: Pin<&mut Self>, context: &mut Context) -> Poll<Option<TItem>> {
+        if self.finished { return Poll::Ready(None); }
+
+        // Schedule a job to produce the next item if there isn't one already
+        if self.next_item.is_none() {
+            let next_item_fn    = Arc::clone(&self.next_item_fn);
+            let next_item       = self.desync.future(move |data| future::ready(next_item_fn(data)).boxed()).boxed();
+
+            self.next_item      = Some(next_item);
+        }
+
+        // Wait for the job to complete
+        match self.next_item.as_mut().expect("Next item").poll_unpin(context) {
+            Poll::Pending       => Poll::Pending,
+            Poll::Ready(item)   => {
+                self.next_item = None;
+
+                // The stream finishes when the function returns None (or if the job was cancelled)
+                let item = item.ok().flatten();
+                if item.is_none() { self.finished = true; }
+
+                Poll::Ready(item)
+            }
+        }
+    }
+}
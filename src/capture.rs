@@ -0,0 +1,149 @@
+//!
+//! `DesyncCapture` records the operations performed on a `Desync` object so they can be replayed later
+//!
+//! This is intended for reproducing bugs: a sequence of operations can be recorded while the
+//! program is running and then replayed deterministically against a fresh copy of the object
+//! in a test.
+//!
+//! ```
+//! # extern crate desync;
+//! # use ::desync::*;
+//! let counter = Desync::new(0);
+//! let capture = counter.capture();
+//!
+//! capture.desync(|count| *count += 1);
+//! capture.desync(|count| *count *= 10);
+//!
+//! let replayed = capture.replay_new();
+//! assert!(replayed.sync(|count| *count) == 10);
+//! ```
+//!
+
+use super::desync::*;
+
+use std::sync::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// An operation recorded by a capture
+type CapturedOperation<T> = Arc<dyn Fn(&mut T)+Send+Sync>;
+
+///
+/// Records the operations scheduled through it on a `Desync` object
+///
+/// Only operations scheduled through the capture object are recorded, and operations must be `Fn` rather than
+/// `FnOnce` so that they can be run again when the capture is replayed. The state of the object at the point
+/// the capture was created is also recorded, so the whole sequence can be replayed from the start.
+///
+pub struct DesyncCapture<'a, T: 'static+Send+Unpin+Clone> {
+    /// The object that operations are being captured for
+    desync: &'a Desync<T>,
+
+    /// The state of the object when the capture started (set by a job on the desync queue)
+    initial_state: Arc<Mutex<Option<T>>>,
+
+    /// The operations that have been recorded so far, in the order they were run
+    operations: Arc<Mutex<Vec<CapturedOperation<T>>>>,
+
+    /// True if new operations should be recorded
+    capturing: AtomicBool
+}
+
+impl<T: 'static+Send+Unpin+Clone> Desync<T> {
+    ///
+    /// Creates a capture object that records the operations scheduled through it so they can be replayed
+    ///
+    pub fn capture(&self) -> DesyncCapture<'_, T> {
+        let capture = DesyncCapture {
+            desync:         self,
+            initial_state:  Arc::new(Mutex::new(None)),
+            operations:     Arc::new(Mutex::new(vec![])),
+            capturing:      AtomicBool::new(true)
+        };
+
+        // Take a copy of the state once the jobs already on the queue have finished
+        let initial_state = Arc::clone(&capture.initial_state);
+        self.desync(move |data| *initial_state.lock().expect("Capture initial state lock") = Some(data.clone()));
+
+        capture
+    }
+}
+
+impl<'a, T: 'static+Send+Unpin+Clone> DesyncCapture<'a, T> {
+    ///
+    /// Sets whether or not operations scheduled through this object should be recorded
+    ///
+    /// Operations are always performed on the underlying object, regardless of whether or not they are recorded.
+    ///
+    pub fn set_capturing(&self, capturing: bool) {
+        self.capturing.store(capturing, Ordering::SeqCst);
+    }
+
+    ///
+    /// True if this object is currently recording operations
+    ///
+    pub fn is_capturing(&self) -> bool {
+        self.capturing.load(Ordering::SeqCst)
+    }
+
+    ///
+    /// Performs an operation asynchronously on the object, recording it if capturing is turned on
+    ///
+    pub fn desync<TFn>(&self, job: TFn)
+    where TFn: 'static+Send+Sync+Fn(&mut T) {
+        if self.is_capturing() {
+            // Operations are recorded from the queue so they're stored in the order they run
+            let operations  = Arc::clone(&self.operations);
+            let job         = Arc::new(job);
+
+            self.desync.desync(move |data| {
+                job(data);
+                operations.lock().expect("Capture operations lock").push(job);
+            });
+        } else {
+            self.desync.desync(job);
+        }
+    }
+
+    ///
+    /// Returns the number of operations that have been recorded, once all of the jobs currently scheduled
+    /// on the object have completed
+    ///
+    pub fn len(&self) -> usize {
+        let operations = Arc::clone(&self.operations);
+        self.desync.sync(move |_data| operations.lock().expect("Capture operations lock").len())
+    }
+
+    ///
+    /// True if no operations have been recorded, once all of the jobs currently scheduled on the object have completed
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    ///
+    /// Schedules all of the operations recorded so far on another `Desync` object
+    ///
+    pub fn replay(&self, target: &Desync<T>) {
+        // Wait for the operations that are currently scheduled to be recorded
+        let operations = Arc::clone(&self.operations);
+        let operations = self.desync.sync(move |_data| operations.lock().expect("Capture operations lock").clone());
+
+        for operation in operations {
+            target.desync(move |data| operation(data));
+        }
+    }
+
+    ///
+    /// Creates a new `Desync` object from the state at the point this capture was created and replays
+    /// all of the recorded operations on it
+    ///
+    pub fn replay_new(&self) -> Desync<T> {
+        let initial_state   = Arc::clone(&self.initial_state);
+        let initial_state   = self.desync.sync(move |_data| initial_state.lock().expect("Capture initial state lock").clone());
+        let target          = Desync::new(initial_state.expect("Capture initial state"));
+
+        self.replay(&target);
+
+        target
+    }
+}
@@ -0,0 +1,248 @@
+//!
+//! Bounded channels where the messages are held in a `Desync` object
+//!
+//! `Scheduler::create_channel()` creates a sender and receiver pair. Unlike other channel types,
+//! the queue of messages is itself a `Desync` object, so sending and receiving are jobs that are
+//! scheduled in the same way as any other. The receiver is a `Stream`, so it can be piped into
+//! another `Desync` object using `pipe_in()` or `pipe()`:
+//!
+//! ```
+//! # extern crate desync;
+//! # extern crate futures;
+//! # use ::desync::*;
+//! # use ::desync::scheduler::*;
+//! # use futures::executor;
+//! # use futures::prelude::*;
+//! let (sender, mut receiver) = scheduler().create_channel(4);
+//!
+//! executor::block_on(async {
+//!     sender.send(1).await.unwrap();
+//!     sender.send(2).await.unwrap();
+//!     drop(sender);
+//!
+//!     assert!(receiver.next().await == Some(1));
+//!     assert!(receiver.next().await == Some(2));
+//!     assert!(receiver.next().await == None);
+//! });
+//! ```
+//!
+
+use super::desync::*;
+use super::scheduler::*;
+
+use futures::future::{Future};
+use futures::stream::{Stream};
+use futures::task::{Poll, Context, Waker};
+
+use std::mem;
+use std::sync::*;
+use std::pin::{Pin};
+use std::collections::VecDeque;
+
+///
+/// Error returned when sending a message on a channel whose receivers have all been dropped (the message is returned)
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ChannelClosed<T>(pub T);
+
+///
+/// The state of a channel
+///
+struct ChannelCore<T> {
+    /// Messages waiting to be received
+    items: VecDeque<T>,
+
+    /// The maximum number of messages that can be waiting
+    capacity: usize,
+
+    /// The number of senders that exist for this channel
+    senders: usize,
+
+    /// The number of receivers that exist for this channel
+    receivers: usize,
+
+    /// Senders waiting for space in the channel
+    sender_wakers: Vec<Waker>,
+
+    /// Receivers waiting for a message
+    receiver_wakers: Vec<Waker>
+}
+
+///
+/// The sending side of a channel created by `Scheduler::create_channel()`
+///
+pub struct DesyncSender<T: 'static+Send+Unpin> {
+    core: Arc<Desync<ChannelCore<T>>>
+}
+
+///
+/// The receiving side of a channel created by `Scheduler::create_channel()`
+///
+/// If the receiver is cloned, each message is received by only one of the clones.
+///
+pub struct DesyncReceiver<T: 'static+Send+Unpin> {
+    core: Arc<Desync<ChannelCore<T>>>
+}
+
+///
+/// Future returned by `DesyncSender::send()`
+///
+pub struct DesyncSend<T: 'static+Send+Unpin> {
+    core: Arc<Desync<ChannelCore<T>>>,
+    item: Option<T>
+}
+
+///
+/// Wakes everything in a list of wakers
+///
+/// This must be called after the job on the channel has finished: waking a task can poll it immediately, which
+/// would deadlock if it tried to access the channel from inside the job.
+///
+fn wake_all(wakers: Vec<Waker>) {
+    wakers.into_iter().for_each(|waker| waker.wake());
+}
+
+impl Scheduler {
+    ///
+    /// Creates a channel that can hold up to `capacity` messages that have not been received yet
+    ///
+    /// Both the sender and the receiver can be cloned. The receiver stops once all of the senders have been
+    /// dropped and all of the messages have been received.
+    ///
+    pub fn create_channel<T: 'static+Send+Unpin>(&self, capacity: usize) -> (DesyncSender<T>, DesyncReceiver<T>) {
+        assert!(capacity > 0, "Channels must have a capacity of at least 1");
+
+        let core = ChannelCore {
+            items:              VecDeque::new(),
+            capacity,
+            senders:            1,
+            receivers:          1,
+            sender_wakers:      vec![],
+            receiver_wakers:    vec![]
+        };
+        let core = Arc::new(Desync::new(core));
+
+        (DesyncSender { core: Arc::clone(&core) }, DesyncReceiver { core })
+    }
+}
+
+impl<T: 'static+Send+Unpin> DesyncSender<T> {
+    ///
+    /// Sends a message, waiting for space in the channel if it's full
+    ///
+    pub fn send(&self, item: T) -> DesyncSend<T> {
+        DesyncSend {
+            core: Arc::clone(&self.core),
+            item: Some(item)
+        }
+    }
+
+    ///
+    /// Sends a message if there's space in the channel, otherwise returns it
+    ///
+    pub fn try_send(&self, item: T) -> Result<(), T> {
+        let (result, wake) = self.core.sync(move |core| {
+            if core.receivers == 0 || core.items.len() >= core.capacity {
+                (Err(item), vec![])
+            } else {
+                core.items.push_back(item);
+                (Ok(()), mem::take(&mut core.receiver_wakers))
+            }
+        });
+
+        wake_all(wake);
+        result
+    }
+}
+
+impl<T: 'static+Send+Unpin> Future for DesyncSend<T> {
+    type Output = Result<(), ChannelClosed<T>>;
+
+    fn poll(mut self: Pin<&mut Self>, context: &mut Context) -> Poll<Self::Output> {
+        let item    = self.item.take().expect("DesyncSend polled after completion");
+        let waker   = context.waker().clone();
+
+        let (result, wake) = self.core.sync(move |core| {
+            if core.receivers == 0 {
+                // Nothing can receive the message
+                (Err(ChannelClosed(item)), vec![])
+            } else if core.items.len() < core.capacity {
+                // Space available
+                core.items.push_back(item);
+                (Ok(None), mem::take(&mut core.receiver_wakers))
+            } else {
+                // Wait for a receiver to make space (returning the item so it can be sent on the next poll)
+                core.sender_wakers.push(waker);
+                (Ok(Some(item)), vec![])
+            }
+        });
+
+        wake_all(wake);
+        match result {
+            Ok(None)        => Poll::Ready(Ok(())),
+            Err(closed)     => Poll::Ready(Err(closed)),
+            Ok(Some(item))  => { self.item = Some(item); Poll::Pending }
+        }
+    }
+}
+
+impl<T: 'static+Send+Unpin> Stream for DesyncReceiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, context: &mut Context) -> Poll<Option<T>> {
+        let waker = context.waker().clone();
+
+        let (result, wake) = self.core.sync(move |core| {
+            match core.items.pop_front() {
+                Some(item)                      => (Poll::Ready(Some(item)), mem::take(&mut core.sender_wakers)),
+                None if core.senders == 0       => (Poll::Ready(None), vec![]),
+                None                            => { core.receiver_wakers.push(waker); (Poll::Pending, vec![]) }
+            }
+        });
+
+        wake_all(wake);
+        result
+    }
+}
+
+impl<T: 'static+Send+Unpin> Clone for DesyncSender<T> {
+    fn clone(&self) -> DesyncSender<T> {
+        self.core.desync(|core| core.senders += 1);
+
+        DesyncSender { core: Arc::clone(&self.core) }
+    }
+}
+
+impl<T: 'static+Send+Unpin> Clone for DesyncReceiver<T> {
+    fn clone(&self) -> DesyncReceiver<T> {
+        self.core.desync(|core| core.receivers += 1);
+
+        DesyncReceiver { core: Arc::clone(&self.core) }
+    }
+}
+
+impl<T: 'static+Send+Unpin> Drop for DesyncSender<T> {
+    fn drop(&mut self) {
+        let wake = self.core.sync(|core| {
+            core.senders -= 1;
+
+            // Receivers stop once there are no senders left
+            if core.senders == 0 { mem::take(&mut core.receiver_wakers) } else { vec![] }
+        });
+
+        wake_all(wake);
+    }
+}
+
+impl<T: 'static+Send+Unpin> Drop for DesyncReceiver<T> {
+    fn drop(&mut self) {
+        let wake = self.core.sync(|core| {
+            core.receivers -= 1;
+
+            // Senders that are waiting for space should fail once there are no receivers left
+            if core.receivers == 0 { mem::take(&mut core.sender_wakers) } else { vec![] }
+        });
+
+        wake_all(wake);
+    }
+}
@@ -0,0 +1,160 @@
+//!
+//! `CircuitBreakerDesync` stops sending jobs to a `Desync` object after repeated failures
+//!
+//! This is useful when a `Desync` object wraps an external resource such as a database connection:
+//! once several jobs in a row have failed (by panicking), further calls fail immediately instead of
+//! waiting on a resource that is probably unavailable. After a timeout, a single trial job is allowed
+//! through to see if the resource has recovered.
+//!
+//! Panics in jobs run through the circuit breaker are caught, so they don't stop the queue for the
+//! object. The job may have left the data in an inconsistent state, so this should only be used with
+//! data that can tolerate a job stopping part-way through.
+//!
+
+use super::desync::*;
+
+use std::sync::*;
+use std::panic;
+use std::time::{Duration, Instant};
+
+///
+/// The reasons a job run through a circuit breaker can fail
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CircuitBreakerError {
+    /// The circuit is open: the job was not run
+    CircuitOpen,
+
+    /// The job panicked
+    Panicked
+}
+
+///
+/// The state of a circuit breaker
+///
+struct CircuitState {
+    /// The number of jobs in a row that have panicked
+    consecutive_failures: usize,
+
+    /// If the circuit is open, when it was opened
+    opened_at: Option<Instant>,
+
+    /// True if a trial job is running while the circuit is half-open
+    trial_in_progress: bool
+}
+
+///
+/// Wrapper for a `Desync` object that stops running jobs once too many have failed in a row
+///
+pub struct CircuitBreakerDesync<'a, T: 'static+Send+Unpin> {
+    /// The object that jobs are run on
+    desync: &'a Desync<T>,
+
+    /// The number of consecutive failures that opens the circuit
+    failure_threshold: usize,
+
+    /// How long the circuit stays open before a trial job is allowed
+    reset_timeout: Duration,
+
+    /// The state of the circuit
+    state: Arc<Mutex<CircuitState>>
+}
+
+impl<T: 'static+Send+Unpin> Desync<T> {
+    ///
+    /// Creates a wrapper for this object that opens a circuit breaker once `failure_threshold` jobs in a row have panicked
+    ///
+    /// While the circuit is open, jobs fail with `CircuitOpen` without being scheduled. Once `reset_timeout` has passed,
+    /// one trial job is allowed: the circuit closes again if it succeeds, or stays open for another `reset_timeout`
+    /// if it fails.
+    ///
+    pub fn with_circuit_breaker(&self, failure_threshold: usize, reset_timeout: Duration) -> CircuitBreakerDesync<'_, T> {
+        CircuitBreakerDesync {
+            desync: self,
+            failure_threshold,
+            reset_timeout,
+            state:  Arc::new(Mutex::new(CircuitState { consecutive_failures: 0, opened_at: None, trial_in_progress: false }))
+        }
+    }
+}
+
+impl CircuitState {
+    ///
+    /// Returns true if a job is allowed to run (and starts a trial job if the circuit is half-open)
+    ///
+    fn allow_job(&mut self, reset_timeout: Duration) -> bool {
+        match self.opened_at {
+            None                                                    => true,
+            Some(opened_at) if opened_at.elapsed() < reset_timeout  => false,
+            Some(_) if self.trial_in_progress                       => false,
+            Some(_)                                                 => { self.trial_in_progress = true; true }
+        }
+    }
+
+    ///
+    /// Updates the state after a job has finished
+    ///
+    fn job_finished(&mut self, succeeded: bool, failure_threshold: usize) {
+        if succeeded {
+            // Any success closes the circuit
+            self.consecutive_failures   = 0;
+            self.opened_at              = None;
+        } else {
+            // Failures open the circuit once the threshold is reached, or immediately if this was a trial job
+            self.consecutive_failures   += 1;
+
+            if self.trial_in_progress || self.consecutive_failures >= failure_threshold {
+                self.opened_at = Some(Instant::now());
+            }
+        }
+
+        self.trial_in_progress = false;
+    }
+}
+
+impl<'a, T: 'static+Send+Unpin> CircuitBreakerDesync<'a, T> {
+    ///
+    /// True if the circuit is currently open (jobs will not be run, unless a trial job is due)
+    ///
+    pub fn is_open(&self) -> bool {
+        self.state.lock().expect("Circuit breaker lock").opened_at.is_some()
+    }
+
+    ///
+    /// Performs a job synchronously if the circuit is closed
+    ///
+    pub fn sync<TFn, Result>(&self, job: TFn) -> std::result::Result<Result, CircuitBreakerError>
+    where   TFn:    Send+FnOnce(&mut T) -> Result,
+            Result: Send {
+        if !self.state.lock().expect("Circuit breaker lock").allow_job(self.reset_timeout) {
+            return Err(CircuitBreakerError::CircuitOpen);
+        }
+
+        let result = self.desync.sync(move |data| panic::catch_unwind(panic::AssertUnwindSafe(move || job(data))));
+        self.state.lock().expect("Circuit breaker lock").job_finished(result.is_ok(), self.failure_threshold);
+
+        result.map_err(|_| CircuitBreakerError::Panicked)
+    }
+
+    ///
+    /// Performs a job asynchronously if the circuit is closed
+    ///
+    /// This returns `CircuitOpen` if the job was not scheduled. The state of the circuit is updated once the job has run.
+    ///
+    pub fn desync<TFn>(&self, job: TFn) -> Result<(), CircuitBreakerError>
+    where TFn: 'static+Send+FnOnce(&mut T) {
+        if !self.state.lock().expect("Circuit breaker lock").allow_job(self.reset_timeout) {
+            return Err(CircuitBreakerError::CircuitOpen);
+        }
+
+        let state               = Arc::clone(&self.state);
+        let failure_threshold   = self.failure_threshold;
+
+        self.desync.desync(move |data| {
+            let succeeded = panic::catch_unwind(panic::AssertUnwindSafe(move || job(data))).is_ok();
+            state.lock().expect("Circuit breaker lock").job_finished(succeeded, failure_threshold);
+        });
+
+        Ok(())
+    }
+}
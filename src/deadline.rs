@@ -0,0 +1,82 @@
+//!
+//! Futures with deadlines and trace IDs
+//!
+//! These are extensions to `Desync::future()` that are useful for servers: `deadline_future()`
+//! gives up waiting for a result at a fixed point in time (such as a deadline supplied with a
+//! request), and `future_with_trace_id()` tags the result with an ID so it can be correlated
+//! with the request that caused it.
+//!
+
+use super::desync::*;
+use super::timer::*;
+
+use futures::future;
+use futures::future::{Future, BoxFuture, FutureExt};
+use futures::channel::oneshot;
+
+use std::time::{Duration, SystemTime};
+
+///
+/// The reasons that a future with a deadline can fail
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DeadlineError {
+    /// The deadline passed before the job completed
+    Timeout,
+
+    /// The job was cancelled before it could complete
+    Canceled
+}
+
+///
+/// A result tagged with the trace ID of the request that produced it
+///
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Traced<T> {
+    /// The trace ID that was passed in when the job was scheduled
+    pub trace_id: u64,
+
+    /// The result of the job
+    pub result: T
+}
+
+impl<T: 'static+Send+Unpin> Desync<T> {
+    ///
+    /// As for `future()`, except the future returns `DeadlineError::Timeout` if the job has not completed by the
+    /// specified time
+    ///
+    /// The job is not scheduled at all if the deadline has already passed. Otherwise, a job that misses the
+    /// deadline still runs, but its result is discarded.
+    ///
+    pub fn deadline_future<TFn, TOutput>(&self, deadline: SystemTime, job: TFn) -> impl 'static+Future<Output=Result<TOutput, DeadlineError>>+Send
+    where   TFn:        'static+Send+for<'a> FnOnce(&'a mut T) -> BoxFuture<'a, TOutput>,
+            TOutput:    'static+Send {
+        let remaining = deadline.duration_since(SystemTime::now()).ok().filter(|remaining| *remaining > Duration::from_millis(0));
+
+        match remaining {
+            None            => future::ready(Err(DeadlineError::Timeout)).left_future(),
+            Some(remaining) => {
+                // The job must run in the background, or polling it could block until it completes, regardless of the deadline
+                let job     = self.future_desync(job);
+                let timeout = delay(remaining).boxed();
+
+                future::select(job, timeout).map(|result| {
+                    match result {
+                        future::Either::Left((Ok(result), _))   => Ok(result),
+                        future::Either::Left((Err(_), _))       => Err(DeadlineError::Canceled),
+                        future::Either::Right(((), _))          => Err(DeadlineError::Timeout)
+                    }
+                }).right_future()
+            }
+        }
+    }
+
+    ///
+    /// As for `future()`, except the result is tagged with a trace ID
+    ///
+    pub fn future_with_trace_id<TFn, TOutput>(&self, trace_id: u64, job: TFn) -> impl 'static+Future<Output=Result<Traced<TOutput>, oneshot::Canceled>>+Send
+    where   TFn:        'static+Send+for<'a> FnOnce(&'a mut T) -> BoxFuture<'a, TOutput>,
+            TOutput:    'static+Send {
+        self.future(job).map(move |result| result.map(|result| Traced { trace_id, result }))
+    }
+}
@@ -0,0 +1,172 @@
+//!
+//! `DebounceHandle` provides debounced updates for a `Desync` object
+//!
+//! A debounced update waits for its triggers to stop for a period of time before it runs. This
+//! is useful for things like saving a document or running a search while the user is typing,
+//! where the update is only worth doing once things have settled down:
+//!
+//! ```
+//! # extern crate desync;
+//! # use ::desync::*;
+//! # use std::thread;
+//! # use std::time::Duration;
+//! let saves   = Desync::new(0);
+//! let save    = saves.debounce(Duration::from_millis(20), |count| *count += 1);
+//!
+//! for _ in 0..10 { save.trigger(); }
+//!
+//! // The update runs once, 20ms after the last trigger
+//! thread::sleep(Duration::from_millis(100));
+//! assert!(saves.sync(|count| *count) == 1);
+//! ```
+//!
+
+use super::desync::*;
+
+use std::sync::*;
+use std::thread;
+use std::time::{Duration, Instant};
+
+///
+/// The state of a debounced update
+///
+struct DebounceState {
+    /// When the most recent trigger happened
+    last_trigger: Option<Instant>,
+
+    /// The job to schedule once the triggers have stopped (trailing edge only)
+    pending: Option<Box<dyn FnOnce()+Send>>,
+
+    /// True if there's a thread waiting to schedule the pending job
+    timer_running: bool
+}
+
+///
+/// Handle for triggering a debounced update to a `Desync` object
+///
+/// When the handle is dropped, any update that is waiting for the triggers to stop is discarded.
+///
+pub struct DebounceHandle<'a, T: 'static+Send+Unpin> {
+    /// The object that is being updated
+    desync: &'a Desync<T>,
+
+    /// How long the triggers must stop for
+    delay: Duration,
+
+    /// True if the update runs on the first trigger rather than after the last one
+    leading: bool,
+
+    /// The function to run when the update happens
+    update: Arc<dyn Fn(&mut T)+Send+Sync>,
+
+    /// The state of the debouncer
+    state: Arc<Desync<DebounceState>>
+}
+
+impl<T: 'static+Send+Unpin> Desync<T> {
+    ///
+    /// Creates a handle that runs an update on this object once `delay` has passed since it was last triggered
+    ///
+    /// Every call to `trigger()` on the handle restarts the delay, so the update only runs once the triggers
+    /// have stopped.
+    ///
+    pub fn debounce<TFn>(&self, delay: Duration, update: TFn) -> DebounceHandle<'_, T>
+    where TFn: 'static+Send+Sync+Fn(&mut T) {
+        self.create_debounce(delay, false, update)
+    }
+
+    ///
+    /// Creates a handle that runs an update on this object when it's first triggered, then ignores further triggers until
+    /// there have been none for `delay`
+    ///
+    pub fn debounce_leading<TFn>(&self, delay: Duration, update: TFn) -> DebounceHandle<'_, T>
+    where TFn: 'static+Send+Sync+Fn(&mut T) {
+        self.create_debounce(delay, true, update)
+    }
+
+    ///
+    /// Creates a debounce handle for either edge
+    ///
+    fn create_debounce<TFn>(&self, delay: Duration, leading: bool, update: TFn) -> DebounceHandle<'_, T>
+    where TFn: 'static+Send+Sync+Fn(&mut T) {
+        DebounceHandle {
+            desync: self,
+            delay,
+            leading,
+            update: Arc::new(update),
+            state:  Arc::new(Desync::new(DebounceState { last_trigger: None, pending: None, timer_running: false }))
+        }
+    }
+}
+
+impl<'a, T: 'static+Send+Unpin> DebounceHandle<'a, T> {
+    ///
+    /// Indicates that the update should run (restarting the delay)
+    ///
+    pub fn trigger(&self) {
+        let update      = Arc::clone(&self.update);
+        let job         = Box::new(self.desync.data_job(move |data| update(data)));
+        let queue       = Arc::clone(self.desync.queue());
+        let scheduler   = self.desync.scheduler();
+        let delay       = self.delay;
+        let leading     = self.leading;
+        let timer_state = Arc::clone(&self.state);
+
+        self.state.desync(move |state| {
+            let now             = Instant::now();
+            let last_trigger    = state.last_trigger.replace(now);
+
+            if leading {
+                // Run straight away unless the previous trigger was within the delay
+                if last_trigger.map(|last_trigger| now.duration_since(last_trigger) >= delay).unwrap_or(true) {
+                    scheduler.desync(&queue, job);
+                }
+
+                return;
+            }
+
+            // The last trigger replaces the pending job
+            state.pending = Some(job);
+
+            if !state.timer_running {
+                state.timer_running = true;
+
+                thread::spawn(move || {
+                    let mut wait = delay;
+
+                    loop {
+                        thread::sleep(wait);
+
+                        // Schedule the job if there have been no more triggers, otherwise wait for the rest of the delay
+                        let next_wait = timer_state.sync(|state| {
+                            let since_last_trigger = state.last_trigger.map(|last_trigger| last_trigger.elapsed()).unwrap_or(delay);
+
+                            if since_last_trigger >= delay {
+                                state.timer_running = false;
+                                if let Some(job) = state.pending.take() {
+                                    scheduler.desync(&queue, job);
+                                }
+
+                                None
+                            } else {
+                                Some(delay - since_last_trigger)
+                            }
+                        });
+
+                        match next_wait {
+                            Some(next_wait) => { wait = next_wait; }
+                            None            => { break; }
+                        }
+                    }
+                });
+            }
+        });
+    }
+}
+
+impl<'a, T: 'static+Send+Unpin> Drop for DebounceHandle<'a, T> {
+    fn drop(&mut self) {
+        // Discard any pending update: it can't be scheduled once this returns, so it can't outlive the object it's updating
+        self.state.sync(|state| state.pending = None);
+    }
+}
@@ -3,15 +3,26 @@
 //! 
 
 use super::scheduler::*;
+use super::panic_observer::*;
+use super::throttle::{ThrottledKeys};
 
 use std::pin::{Pin};
-use std::sync::{Arc};
+use std::sync::{Arc, Mutex, OnceLock, LockResult, PoisonError};
 use std::marker::{Unpin};
 use futures::{FutureExt};
+use futures::executor;
 use futures::channel::oneshot;
+use futures::future;
 use futures::future::{Future, BoxFuture};
+use futures::stream::{Stream, BoxStream, StreamExt};
+use futures::sink::{SinkExt};
 
+use std::any::{Any};
+use arc_swap::{ArcSwap};
+use std::fmt;
 use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::{Duration};
 
 ///
 /// A data storage structure used to govern synchronous and asynchronous access to an underlying object.
@@ -22,7 +33,81 @@ pub struct Desync<T: Send+Unpin> {
 
     /// Data for this object. Boxed so the pointer remains the same through the lifetime of the object.
     /// Will be 'None' only briefly when the data has been taken to be dropped
-    data:   Option<Pin<Box<T>>>
+    data:   Option<Pin<Box<T>>>,
+
+    /// Predicates registered by `wait_for()`, `async_map()` and `observe_changes()` that are checked after every job (only accessed from jobs running on the queue)
+    waiters: Arc<Mutex<Vec<Waiter<T>>>>,
+
+    /// Function called with the data instead of dropping it when this object is dropped
+    drop_handler: Option<Box<dyn FnOnce(T)+Send>>,
+
+    /// The scheduler that dispatches the jobs for this object (changed by `migrate_scheduler()`)
+    scheduler: ArcSwap<Scheduler>,
+
+    /// Handlers registered by `observe_panics()` that are called if a job on this object panics
+    panic_observers: Arc<PanicObservers>,
+
+    /// The keys passed to `throttled_future()` (only allocated once it has been called)
+    throttled_keys: OnceLock<Box<ThrottledKeys>>
+}
+
+///
+/// A predicate registered by `wait_for()`, `async_map()` or `observe_changes()`, and the channel to signal when it becomes true
+///
+struct Waiter<T> {
+    predicate:  Box<dyn Fn(&T) -> bool+Send>,
+    signal:     oneshot::Sender<()>
+}
+
+///
+/// The reasons that `try_into_sync()` can fail
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SyncFailed {
+    /// The queue for the object has panicked, or the job was discarded without running
+    Panicked,
+
+    /// The job did not complete within the time allowed
+    Timeout,
+
+    /// The object's scheduler has shut down, so the job was not run
+    ShutDown
+}
+
+impl From<SyncTimeout> for SyncFailed {
+    fn from(timeout: SyncTimeout) -> SyncFailed {
+        match timeout {
+            SyncTimeout::TimedOut       => SyncFailed::Timeout,
+            SyncTimeout::QueuePanicked  => SyncFailed::Panicked,
+            SyncTimeout::ShutDown       => SyncFailed::ShutDown
+        }
+    }
+}
+
+///
+/// A function that borrows the data of a `Desync` object and returns a future, as accepted by `future_unboxed()`
+///
+/// This is implemented for any function that returns a future that borrows its argument, such as an `async fn` or
+/// an async closure. It's needed as the type of the future depends on the lifetime of the data, which can't be
+/// written as part of a `for<'a> FnOnce` bound.
+///
+pub trait DataFutureFn<'a, T: 'a, TOutput> {
+    /// The future returned by this function
+    type Future: 'a+Send+Future<Output=TOutput>;
+
+    /// Calls this function with the data
+    fn call(self, data: &'a mut T) -> Self::Future;
+}
+
+impl<'a, T, TOutput, TFn, TFuture> DataFutureFn<'a, T, TOutput> for TFn
+where   T:          'a,
+        TFn:        FnOnce(&'a mut T) -> TFuture,
+        TFuture:    'a+Send+Future<Output=TOutput> {
+    type Future = TFuture;
+
+    fn call(self, data: &'a mut T) -> TFuture {
+        self(data)
+    }
 }
 
 // Rust actually derives this anyway at the moment
@@ -35,27 +120,217 @@ unsafe impl<T: Send+Unpin> Sync for Desync<T> {}
 /// Used for passing the data pointer through to the queue
 /// 
 /// 'Safe' because the queue is synchronised during drop, so we can never use the pointer
-/// if the object does not exist.
+/// if the object does not exist. The data is pinned, so the pointer can't be invalidated
+/// by moving the `Desync` either. A job can't see a different object that was later created
+/// at the same address, as the drop can't complete until every job has finished.
 /// 
 struct DataRef<T: Send>(*const T);
 unsafe impl<T: Send> Send for DataRef<T> {}
 
-// TODO: we can change DataRef to Shared (https://doc.rust-lang.org/std/ptr/struct.Shared.html in the future)
-
 // TODO: T does not need to be static as we know that its lifetime is at least the lifetime of Desync<T> and hence the queue
 impl<T: 'static+Send+Unpin> Desync<T> {
     ///
     /// Creates a new Desync object
     ///
     pub fn new(data: T) -> Desync<T> {
-        let queue = queue();
+        Desync::new_with_scheduler(data, shared_scheduler())
+    }
+
+    ///
+    /// Creates a new Desync object whose jobs are run by a particular scheduler
+    ///
+    /// This makes it possible to give a subsystem its own thread pool, so that (for example) slow IO-bound
+    /// jobs can't hold up CPU-bound ones. The scheduler can be changed later with `migrate_scheduler()`.
+    ///
+    pub fn new_with_scheduler(data: T, scheduler: Arc<Scheduler>) -> Desync<T> {
+        Desync::new_with_config(data, scheduler, QueueConfig::default())
+    }
+
+    ///
+    /// Creates a new Desync object whose jobs are run by a particular scheduler, on a queue with the specified settings
+    ///
+    /// This can be used to create an object whose queue is both named and bounded, for example.
+    ///
+    pub fn new_with_config(data: T, scheduler: Arc<Scheduler>, config: QueueConfig) -> Desync<T> {
+        let queue = scheduler.create_job_queue_with_config(config);
+
+        Desync {
+            queue,
+            data:               Some(Pin::new(Box::new(data))),
+            waiters:            Arc::new(Mutex::new(vec![])),
+            drop_handler:       None,
+            scheduler:          ArcSwap::new(scheduler),
+            panic_observers:    Arc::new(Mutex::new(vec![])),
+            throttled_keys:     OnceLock::new()
+        }
+    }
+
+    ///
+    /// Creates a new Desync object with a named queue
+    ///
+    /// The name is shown in the debug output for the queue and the scheduler, and in the panic
+    /// message if a job is scheduled on the object after its queue has panicked.
+    ///
+    pub fn new_named(data: T, name: impl Into<String>) -> Desync<T> {
+        Desync::new_with_config(data, shared_scheduler(), QueueConfig { name: Some(name.into()), ..QueueConfig::default() })
+    }
+
+    ///
+    /// Creates a new Desync object that can have at most `capacity` jobs waiting to run
+    ///
+    /// Once this many jobs are waiting, `desync()` blocks until one of them has started, which stops a fast
+    /// producer from using an unbounded amount of memory. `try_desync()` can be used to avoid blocking.
+    ///
+    pub fn new_bounded(data: T, capacity: usize) -> Desync<T> {
+        Desync::new_bounded_with_policy(data, capacity, QueueOverflowPolicy::Block)
+    }
+
+    ///
+    /// Creates a new Desync object that can have at most `capacity` jobs waiting to run, and which deals with a job
+    /// scheduled by `desync()` while the queue is full according to `policy`
+    ///
+    /// With `QueueOverflowPolicy::DropOldest` or `DropNewest`, `desync()` discards a job instead of waiting for space,
+    /// which keeps a fast producer moving at the cost of some jobs never running.
+    ///
+    pub fn new_bounded_with_policy(data: T, capacity: usize, policy: QueueOverflowPolicy) -> Desync<T> {
+        Desync::new_with_config(data, shared_scheduler(), QueueConfig { capacity: Some(capacity), overflow_policy: policy, ..QueueConfig::default() })
+    }
+
+    ///
+    /// Creates a new Desync object that calls a function with its data when it's dropped
+    ///
+    /// The handler is called on the object's queue once all of the pending jobs have completed,
+    /// and receives ownership of the data instead of it being dropped normally. This is useful
+    /// for cleanup that needs to happen when the object goes away, such as flushing a buffer or
+    /// closing a connection.
+    ///
+    pub fn with_drop_handler<TFn>(data: T, handler: TFn) -> Desync<T>
+    where TFn: 'static+Send+FnOnce(T) {
+        let mut desync = Desync::new(data);
+        desync.drop_handler = Some(Box::new(handler));
+
+        desync
+    }
+
+    ///
+    /// Creates a new Desync object from the contents of a mutex
+    ///
+    /// If the mutex is poisoned, its contents are used as they are.
+    ///
+    pub fn from_mutex(mutex: Mutex<T>) -> Desync<T> {
+        Desync::new(mutex.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner()))
+    }
+
+    ///
+    /// Waits for all of the pending jobs on this object to complete, then moves its contents into a mutex
+    ///
+    /// Any drop handler is not called, as the data is not dropped.
+    ///
+    pub fn into_mutex(mut self) -> Mutex<T> {
+        // Once the queue is empty, nothing else can access the data as this object has been consumed
+        self.sync(|_| { });
+
+        let data = self.data.take().expect("Desync data");
+        self.drop_handler = None;
+
+        Mutex::new(*Pin::into_inner(data))
+    }
+
+    ///
+    /// Waits for all of the pending jobs on this object to complete, then returns its contents
+    ///
+    /// If a job on this object panics (before or while this is waiting), this returns a `PoisonError` instead of
+    /// panicking. The data can still be retrieved from the error, though the job that panicked may have left it in
+    /// an inconsistent state. Any drop handler is not called, as the data is not dropped.
+    ///
+    pub fn into_inner(mut self) -> LockResult<T> {
+        // The data can't be moved until every job that might refer to it has finished (or the queue has panicked, which
+        // stops it running any more jobs)
+        let panicked = self.scheduler().wait_for_jobs_no_panic(&self.queue);
+
+        let data = *Pin::into_inner(self.data.take().expect("Desync data"));
+        self.drop_handler = None;
+
+        if panicked {
+            Err(PoisonError::new(data))
+        } else {
+            Ok(data)
+        }
+    }
+
+    ///
+    /// Waits for all of the pending jobs on this object to complete, then transforms its contents into a new object
+    ///
+    /// The new object keeps this object's queue, scheduler and panic observers, so any handles to those stay valid.
+    /// Waiters, throttled keys and the drop handler are specific to the old type, so they're discarded (as in
+    /// `into_inner()`, the drop handler is not called). This panics if a job on this object has panicked.
+    ///
+    pub fn map<TOutput, TFn>(self, map: TFn) -> Desync<TOutput>
+    where   TOutput:    'static+Send+Unpin,
+            TFn:        FnOnce(T) -> TOutput {
+        let queue           = Arc::clone(&self.queue);
+        let scheduler       = self.scheduler();
+        let panic_observers = Arc::clone(&self.panic_observers);
+        let data            = self.into_inner().expect("Cannot map an object whose queue has panicked");
 
         Desync {
-            queue:  queue,
-            data:   Some(Pin::new(Box::new(data)))
+            queue,
+            data:               Some(Pin::new(Box::new(map(data)))),
+            waiters:            Arc::new(Mutex::new(vec![])),
+            drop_handler:       None,
+            scheduler:          ArcSwap::new(scheduler),
+            panic_observers,
+            throttled_keys:     OnceLock::new()
         }
     }
 
+    ///
+    /// Creates a new object that shares its queue (and scheduler) with this one
+    ///
+    /// Jobs on the two objects run one at a time in the order they were scheduled, as if they were on a single object.
+    /// This makes it possible to split a large structure into several smaller objects without losing the ordering
+    /// between updates to them. As the objects share a queue, a job on one of them must not call `sync()` on the
+    /// other, as it would wait for itself. If a job on either object panics, the queue stops for both of them.
+    ///
+    pub fn share_queue<U: 'static+Send+Unpin>(&self, data: U) -> Desync<U> {
+        Desync {
+            queue:              Arc::clone(&self.queue),
+            data:               Some(Pin::new(Box::new(data))),
+            waiters:            Arc::new(Mutex::new(vec![])),
+            drop_handler:       None,
+            scheduler:          ArcSwap::new(self.scheduler()),
+            panic_observers:    Arc::new(Mutex::new(vec![])),
+            throttled_keys:     OnceLock::new()
+        }
+    }
+
+    ///
+    /// Signals any waiters whose predicates have become true (called after every job)
+    ///
+    /// The predicates are called without the waiters lock held, so they can use this item
+    /// without deadlocking. A predicate that panics is removed, which completes its future.
+    ///
+    fn notify_waiters(waiters: &Mutex<Vec<Waiter<T>>>, data: &T) {
+        let checking = mem::take(&mut *waiters.lock().expect("Desync waiters lock"));
+        if checking.is_empty() {
+            return;
+        }
+
+        let mut still_waiting = vec![];
+        for waiter in checking {
+            match panic::catch_unwind(AssertUnwindSafe(|| (waiter.predicate)(data))) {
+                Ok(true)    => { waiter.signal.send(()).ok(); }
+                Ok(false)   => { still_waiting.push(waiter); }
+                Err(_)      => { }
+            }
+        }
+
+        // Any waiters added while the predicates were running go after the ones that were already waiting
+        let mut waiters = waiters.lock().expect("Desync waiters lock");
+        still_waiting.extend(waiters.drain(..));
+        *waiters = still_waiting;
+    }
+
     ///
     /// Performs an operation asynchronously on this item. This function will return
     /// immediately and the job will happen on a separate thread at some time in the
@@ -68,7 +343,7 @@ impl<T: 'static+Send+Unpin> Desync<T> {
     #[deprecated(since="0.3.0", note="please use `desync` instead")]
     pub fn r#async<TFn>(&self, job: TFn)
     where TFn: 'static+Send+FnOnce(&mut T) -> () {
-        self.desync(job)
+        self.desync(job);
     }
 
     ///
@@ -79,15 +354,149 @@ impl<T: 'static+Send+Unpin> Desync<T> {
     /// Jobs are always performed in the order that they are queued and are always
     /// performed synchronously with respect to this object.
     ///
-    pub fn desync<TFn>(&self, job: TFn)
-    where TFn: 'static+Send+FnOnce(&mut T) -> () {
+    /// The returned handle can be used to cancel the job before it starts. Dropping
+    /// the handle does not cancel the job. If this object was created with `new_bounded()`,
+    /// this blocks while the queue is full. If the job is discarded (because the scheduler has
+    /// shut down, or the queue is full and uses `QueueOverflowPolicy::DropNewest`), the handle's
+    /// `is_rejected()` returns true.
+    ///
+    pub fn desync<TFn>(&self, job: TFn) -> JobHandle
+    where TFn: 'static+Send+FnOnce(&mut T) {
+        self.scheduler().desync(&self.queue, self.data_job(job))
+    }
+
+    ///
+    /// As for `desync()`, except that this returns `TryDesyncError::QueueFull` instead of blocking if this object was
+    /// created with `new_bounded()` and already has as many jobs waiting as it can hold, and `TryDesyncError::ShutDown`
+    /// if the scheduler has shut down
+    ///
+    pub fn try_desync<TFn>(&self, job: TFn) -> Result<JobHandle, TryDesyncError>
+    where TFn: 'static+Send+FnOnce(&mut T) {
+        self.scheduler().try_desync(&self.queue, self.data_job(job))
+    }
+
+    ///
+    /// Schedules several jobs on this object at once, so that no other jobs can be scheduled in between them
+    ///
+    /// The jobs run in order, as if `desync()` had been called for each of them. The queue is only started once all
+    /// of the jobs have been added. Unlike `desync()`, this never blocks, even if this object was created with
+    /// `new_bounded()` and its queue is full.
+    ///
+    pub fn desync_batch<TFn>(&self, jobs: impl IntoIterator<Item=TFn>)
+    where TFn: 'static+Send+FnOnce(&mut T) {
+        let jobs = jobs.into_iter()
+            .map(|job| Box::new(self.data_job(job)) as Box<dyn FnOnce()+Send>)
+            .collect();
+
+        self.scheduler().desync_batch(&self.queue, jobs);
+    }
+
+    ///
+    /// Asynchronously runs a job on this item only if a predicate is true
+    ///
+    /// The predicate and the job run together as a single job on the queue, so no other job can change
+    /// this item in between the check and the update.
+    ///
+    pub fn run_if<TPredicate, TFn>(&self, predicate: TPredicate, job: TFn)
+    where   TPredicate: 'static+Send+FnOnce(&T) -> bool,
+            TFn:        'static+Send+FnOnce(&mut T) {
+        self.desync(move |data| {
+            if predicate(data) {
+                job(data);
+            }
+        });
+    }
+
+    ///
+    /// Asynchronously runs a job on this item only if a predicate is false
+    ///
+    /// As for `run_if()`, the predicate and the job run together as a single job.
+    ///
+    pub fn run_unless<TPredicate, TFn>(&self, predicate: TPredicate, job: TFn)
+    where   TPredicate: 'static+Send+FnOnce(&T) -> bool,
+            TFn:        'static+Send+FnOnce(&mut T) {
+        self.run_if(move |data| !predicate(data), job)
+    }
+
+    ///
+    /// Creates a job that runs a function on the data for this object, for scheduling on its queue
+    ///
+    /// The job must be run on this object's queue before this object is dropped: this is guaranteed as
+    /// long as it's scheduled while there's still a reference to this object.
+    ///
+    pub (crate) fn data_job<TFn>(&self, job: TFn) -> impl 'static+Send+FnOnce()
+    where TFn: 'static+Send+FnOnce(&mut T) {
         // As drop() is the last thing called, we know that this object will still exist at the point where the queue makes the asynchronous callback
-        let data = DataRef::<T>(&**self.data.as_ref().unwrap());
+        let data        = DataRef::<T>(&**self.data.as_ref().unwrap());
+        let waiters     = Arc::clone(&self.waiters);
+        let observers   = Arc::clone(&self.panic_observers);
 
-        desync(&self.queue, move || {
+        move || {
             let data = data.0 as *mut T;
-            job(unsafe { &mut *data });
-        })
+            run_observing_panics(&observers, move || job(unsafe { &mut *data }));
+            Self::notify_waiters(&waiters, unsafe { &*data });
+        }
+    }
+
+    ///
+    /// The queue that the jobs for this object are scheduled on
+    ///
+    pub (crate) fn queue(&self) -> &Arc<JobQueue> {
+        &self.queue
+    }
+
+    ///
+    /// The scheduler that currently dispatches the jobs for this object
+    ///
+    pub (crate) fn scheduler(&self) -> Arc<Scheduler> {
+        self.scheduler.load_full()
+    }
+
+    ///
+    /// The handlers that are called if a job on this object panics
+    ///
+    pub (crate) fn panic_observers(&self) -> &Arc<PanicObservers> {
+        &self.panic_observers
+    }
+
+    ///
+    /// The keys passed to `throttled_future()`, allocating them the first time they're needed
+    ///
+    pub (crate) fn throttled_keys(&self) -> &ThrottledKeys {
+        self.throttled_keys.get_or_init(|| Box::new(ThrottledKeys::new()))
+    }
+
+    ///
+    /// Moves this object to a different scheduler
+    ///
+    /// This suspends the queue once the jobs that are already queued have finished, switches to the new scheduler
+    /// while nothing is running, then resumes the queue and waits for the old scheduler to release it (any jobs
+    /// scheduled in the meantime are run by the old scheduler before the queue becomes idle). Once this returns,
+    /// new jobs will be dispatched by the new scheduler, which is useful for moving a busy object to a dedicated
+    /// thread pool. As with `sync()`, this must not be called from a job running on this object.
+    ///
+    pub fn migrate_scheduler(&self, new_scheduler: Arc<Scheduler>) {
+        // Wait for the old scheduler to finish the jobs that are already queued (this fails if the queue has panicked, in which case there's nothing to wait for)
+        let resumer = executor::block_on(self.scheduler().suspend(&self.queue));
+
+        // Jobs scheduled from now on are dispatched by the new scheduler
+        self.scheduler.store(new_scheduler);
+
+        if let Ok(resumer) = resumer {
+            resumer.resume();
+            executor::block_on(self.queue.on_idle());
+        }
+    }
+
+    ///
+    /// Makes sure that the jobs waiting for this item will start running
+    ///
+    /// Normally a thread is woken when a job is scheduled, but if no thread is available the jobs wait until one
+    /// becomes free. This wakes a thread if one is dormant, and otherwise runs the waiting jobs on the current
+    /// thread (blocking until they have completed).
+    ///
+    pub fn ensure_started(&self) {
+        self.scheduler().ensure_started(&self.queue);
     }
 
     ///
@@ -95,21 +504,352 @@ impl<T: 'static+Send+Unpin> Desync<T> {
     /// jobs that this item may be performing, and this function will not return until the
     /// job is complete and the result is available. 
     ///
+    /// As the job is always finished by the time this returns, it doesn't need to be `'static`: it can borrow
+    /// from the caller's stack frame.
+    ///
     pub fn sync<TFn, Result>(&self, job: TFn) -> Result
     where TFn: Send+FnOnce(&mut T) -> Result, Result: Send {
         let result = {
             // As drop() is the last thing called, we know that this object will still exist at the point where the callback occurs
-            let data = DataRef::<T>(&**self.data.as_ref().unwrap());
+            let data        = DataRef::<T>(&**self.data.as_ref().unwrap());
+            let waiters     = &*self.waiters;
+            let observers   = &*self.panic_observers;
+
+            self.scheduler().sync(&self.queue, move || {
+                let data    = data.0 as *mut T;
+                let result  = run_observing_panics(observers, move || job(unsafe { &mut *data }));
+                Self::notify_waiters(waiters, unsafe { &*data });
 
-            sync(&self.queue, move || {
-                let data = data.0 as *mut T;
-                job(unsafe { &mut *data })
+                result
             })
         };
 
         result
     }
 
+    ///
+    /// As for `sync()`, except that a panic in the job is returned as an error instead of stopping the queue
+    ///
+    /// If the queue has already panicked, it's reset first so that it can run jobs again (any jobs that were waiting
+    /// when it panicked are discarded). The panic observers are still called if the job panics. Note that a job
+    /// that panicked may have left the data in an inconsistent state.
+    ///
+    pub fn catch_unwind<TFn, Result>(&self, job: TFn) -> std::result::Result<Result, Box<dyn Any+Send>>
+    where TFn: Send+FnOnce(&mut T) -> Result, Result: Send {
+        self.scheduler().reset_panicked(&self.queue);
+
+        // As drop() is the last thing called, we know that this object will still exist at the point where the callback occurs
+        let data        = DataRef::<T>(&**self.data.as_ref().unwrap());
+        let waiters     = &*self.waiters;
+        let observers   = &*self.panic_observers;
+
+        self.scheduler().sync(&self.queue, move || {
+            let data    = data.0 as *mut T;
+            let result  = catch_observing_panics(observers, move || job(unsafe { &mut *data }));
+            Self::notify_waiters(waiters, unsafe { &*data });
+
+            result
+        })
+    }
+
+    ///
+    /// As for `sync()`, except that the job is given a function it can call to report its progress
+    ///
+    /// The job can report progress as a value from 0.0 to 1.0 at any point (values outside this range are clamped).
+    /// The `progress` function is called immediately on the thread running the job, so it should not take long:
+    /// this is intended for updating things like progress bars or logs during expensive operations.
+    ///
+    pub fn sync_progress<TFn, TProgress, Result>(&self, job: TFn, progress: TProgress) -> Result
+    where   TFn:        Send+FnOnce(&mut T, &dyn Fn(f32)) -> Result,
+            TProgress:  Send+Sync+Fn(f32),
+            Result:     Send {
+        self.sync(move |data| {
+            let report_progress = |amount: f32| progress(amount.clamp(0.0, 1.0));
+            job(data, &report_progress)
+        })
+    }
+
+    ///
+    /// Performs an operation synchronously on this item, ahead of any jobs that are currently
+    /// waiting to run
+    ///
+    /// This is intended for situations such as an emergency shutdown, where some cleanup code
+    /// needs to run right away. If `cancel_pending` is true, the pending jobs are discarded
+    /// (any futures waiting on them will return `Canceled`), otherwise they are paused and will
+    /// resume once the operation has completed. Jobs that are already in progress, or that
+    /// another thread is blocked waiting on with `sync()`, will still complete first.
+    ///
+    pub fn run_exclusive<TFn, Result>(&self, job: TFn, cancel_pending: bool) -> Result
+    where TFn: Send+FnOnce(&mut T) -> Result, Result: Send {
+        // As drop() is the last thing called, we know that this object will still exist at the point where the callback occurs
+        let data        = DataRef::<T>(&**self.data.as_ref().unwrap());
+        let waiters     = &*self.waiters;
+        let observers   = &*self.panic_observers;
+
+        self.scheduler().run_exclusive(&self.queue, move || {
+            let data    = data.0 as *mut T;
+            let result  = run_observing_panics(observers, move || job(unsafe { &mut *data }));
+            Self::notify_waiters(waiters, unsafe { &*data });
+
+            result
+        }, cancel_pending)
+    }
+
+    ///
+    /// Performs an operation on this item, waiting at most `max_wait` for it to complete
+    ///
+    /// This is a best-effort version of `sync()` for code that can't guarantee that the queue will be
+    /// responsive. Instead of blocking indefinitely or panicking, this returns `SyncFailed::Panicked` if
+    /// the queue has panicked (or panics while running the job) and `SyncFailed::Timeout` if the job
+    /// doesn't finish in time (or `SyncFailed::ShutDown` if the scheduler has shut down). A job that times
+    /// out is not cancelled: it will still run once the jobs ahead of it have finished, but its result is
+    /// discarded.
+    ///
+    pub fn try_into_sync<TFn, TResult>(&self, max_wait: Duration, job: TFn) -> Result<TResult, SyncFailed>
+    where   TFn:        'static+Send+FnOnce(&mut T) -> TResult,
+            TResult:    'static+Send {
+        self.sync_timeout(max_wait, job).map_err(SyncFailed::from)
+    }
+
+    ///
+    /// Performs an operation on this item immediately if no other jobs are running or waiting, otherwise returns
+    /// `TrySyncError::QueueBusy`
+    ///
+    /// This never blocks, which makes it useful for polling: for example, a render loop can use this to
+    /// update a label without waiting for a slow update to finish, showing the old value until it's done.
+    /// The job is not scheduled if it can't run straight away, or if the scheduler has shut down.
+    ///
+    pub fn try_sync<TFn, TResult>(&self, job: TFn) -> Result<TResult, TrySyncError>
+    where TFn: FnOnce(&mut T) -> TResult {
+        // As the queue is idle while this runs, nothing else can access the data
+        let data        = DataRef::<T>(&**self.data.as_ref().unwrap());
+        let waiters     = &*self.waiters;
+        let observers   = &*self.panic_observers;
+
+        self.scheduler().try_sync(&self.queue, move || {
+            let data    = data.0 as *mut T;
+            let result  = run_observing_panics(observers, move || job(unsafe { &mut *data }));
+            Self::notify_waiters(waiters, unsafe { &*data });
+
+            result
+        })
+    }
+
+    ///
+    /// Reads the data for this item if no jobs are running or waiting, otherwise returns `None`
+    ///
+    /// Unlike `try_sync()`, the function only gets read-only access to the data, and no job is started: the function is
+    /// called while the queue is locked, which stops any other jobs from being scheduled until it returns. It must not
+    /// schedule jobs on this object itself.
+    ///
+    pub fn try_get<TFn, TResult>(&self, read: TFn) -> Option<TResult>
+    where TFn: FnOnce(&T) -> TResult {
+        let data = &**self.data.as_ref().unwrap();
+
+        self.scheduler().run_while_idle(&self.queue, move || read(data))
+    }
+
+    ///
+    /// As for `sync()`, except that this returns `SyncTimeout::TimedOut` if the job has not completed within `timeout`
+    ///
+    /// This is useful where a job ahead of this one might be waiting on something slow, such as a network request,
+    /// and the caller can't afford to block indefinitely. `SyncTimeout::QueuePanicked` is returned if the queue has
+    /// panicked, and `SyncTimeout::ShutDown` if the scheduler has shut down. A job that times out is not cancelled:
+    /// it will still run once the jobs ahead of it have finished, but its result is discarded.
+    ///
+    pub fn sync_timeout<TFn, TResult>(&self, timeout: Duration, job: TFn) -> Result<TResult, SyncTimeout>
+    where   TFn:        'static+Send+FnOnce(&mut T) -> TResult,
+            TResult:    'static+Send {
+        // As drop() is the last thing called, we know that this object will still exist at the point where the job runs, even if it times out
+        let data        = DataRef::<T>(&**self.data.as_ref().unwrap());
+        let waiters     = Arc::clone(&self.waiters);
+        let observers   = Arc::clone(&self.panic_observers);
+
+        self.scheduler().sync_timeout(&self.queue, timeout, move || {
+            let data    = data.0 as *mut T;
+            let result  = run_observing_panics(&observers, move || job(unsafe { &mut *data }));
+            Self::notify_waiters(&waiters, unsafe { &*data });
+
+            result
+        })
+    }
+
+    ///
+    /// Compares the contents of this item with another, once all of the jobs currently pending
+    /// on both items have completed
+    /// 
+    /// Both queues are held while the comparison is performed. The queues are always acquired
+    /// in the same order regardless of which object this is called on, so comparing two
+    /// objects from multiple threads at once will not deadlock.
+    ///
+    pub fn eq_sync(&self, other: &Desync<T>) -> bool
+    where T: PartialEq {
+        // An object is always equal to itself
+        if std::ptr::eq(self, other) { return true; }
+
+        // Objects sharing a queue can't be synced one inside the other, but a job on the queue can safely read both
+        if Arc::ptr_eq(&self.queue, &other.queue) {
+            let other_data = DataRef::<T>(&**other.data.as_ref().unwrap());
+
+            return self.sync(move |self_data| {
+                let other_data = other_data.0;
+                *self_data == *unsafe { &*other_data }
+            });
+        }
+
+        // Acquire the queues in address order
+        let self_first = (&*self.queue as *const JobQueue) < (&*other.queue as *const JobQueue);
+        let (first, second) = if self_first { (self, other) } else { (other, self) };
+
+        first.sync(move |first_data| {
+            second.sync(move |second_data| {
+                let (self_data, other_data) = if self_first { (first_data, second_data) } else { (second_data, first_data) };
+                *self_data == *other_data
+            })
+        })
+    }
+
+    ///
+    /// Performs an operation asynchronously on this item, returning a future that completes
+    /// once the operation has finished.
+    /// 
+    /// This is the same as `desync()` except that it's possible to find out when the job has
+    /// completed. As the operation is not itself asynchronous, it does not need to return a
+    /// `BoxFuture` as `future()` does, so this:
+    /// 
+    /// ```
+    /// # extern crate futures;
+    /// # extern crate desync;
+    /// # use desync::Desync;
+    /// # use futures::executor;
+    /// # let number = Desync::new(0);
+    /// let finished = number.transform_in_place(|val| *val = 42);
+    /// # executor::block_on(finished).unwrap();
+    /// # assert!(number.sync(|val| *val) == 42);
+    /// ```
+    /// 
+    /// is equivalent to `number.future(|val| { *val = 42; future::ready(()).boxed() })`.
+    /// 
+    /// The job is scheduled immediately, regardless of whether or not the future is awaited.
+    /// The future will return `Canceled` if the job could not be completed (for example, because
+    /// an earlier job on this item panicked).
+    ///
+    pub fn transform_in_place<TFn>(&self, job: TFn) -> impl Future<Output=Result<(), oneshot::Canceled>>+Send
+    where TFn: 'static+Send+FnOnce(&mut T) {
+        let data        = DataRef::<T>(&**self.data.as_ref().unwrap());
+        let waiters     = Arc::clone(&self.waiters);
+        let observers   = Arc::clone(&self.panic_observers);
+
+        self.scheduler().future(&self.queue, move || {
+            let data = data.0 as *mut T;
+            run_observing_panics(&observers, move || job(unsafe { &mut *data }));
+            Self::notify_waiters(&waiters, unsafe { &*data });
+
+            future::ready(())
+        })
+    }
+
+    ///
+    /// Returns a future that completes once the predicate is true for the contents of this item
+    /// 
+    /// The predicate is checked once all of the jobs that are currently pending have completed,
+    /// and then again after every job that runs on this item until it returns true. The future
+    /// will also complete if this item is dropped before the predicate becomes true.
+    ///
+    pub fn wait_for<TFn>(&self, predicate: TFn) -> impl Future<Output=()>+Send
+    where TFn: 'static+Send+Sync+Fn(&T) -> bool {
+        let (signal, wait)  = oneshot::channel();
+        let waiters         = Arc::clone(&self.waiters);
+
+        self.desync(move |data| {
+            if predicate(data) {
+                // Already true: signal immediately
+                signal.send(()).ok();
+            } else {
+                // Check again after every job
+                waiters.lock().expect("Desync waiters lock").push(Waiter { predicate: Box::new(predicate), signal });
+            }
+        });
+
+        wait.map(|_| ())
+    }
+
+    ///
+    /// Creates a new object whose contents are generated by calling a function on the contents of this one,
+    /// and which is updated whenever this object is updated
+    ///
+    /// The function is called on this object's queue after every job, and its result is sent to the new
+    /// object asynchronously. Updates stop once the new object has been dropped.
+    ///
+    pub fn async_map<U, TFn>(&self, map: TFn) -> Arc<Desync<U>>
+    where   U:      'static+Send+Unpin,
+            TFn:    'static+Send+Sync+Fn(&T) -> U {
+        let data    = DataRef::<T>(&**self.data.as_ref().unwrap());
+        let waiters = Arc::clone(&self.waiters);
+
+        // Not run via sync(), which would call the mapping function a second time straight after this job
+        self.scheduler().sync(&self.queue, move || {
+            // The initial value is generated in the same job that starts observing this object, so no updates can be missed
+            let output          = Arc::new(Desync::new(map(unsafe { &*data.0 })));
+            let weak_output     = Arc::downgrade(&output);
+            let (signal, _)     = oneshot::channel();
+
+            // Waiters are removed once they return true, which happens here once the output has been dropped
+            let observer        = move |data: &T| {
+                match weak_output.upgrade() {
+                    Some(output)    => { let value = map(data); output.desync(move |output| *output = value); false }
+                    None            => true
+                }
+            };
+
+            waiters.lock().expect("Desync waiters lock").push(Waiter { predicate: Box::new(observer), signal });
+
+            output
+        })
+    }
+
+    ///
+    /// Creates a read-only view of part of another object, which is updated whenever that object is updated
+    ///
+    /// `project` extracts the value of the new object from the source object. It's called after every job on the
+    /// source object, and the result is sent to the new object asynchronously. Changes made directly to the new
+    /// object are not sent back to the source, and are overwritten by the next update. Updates stop once the new
+    /// object has been dropped.
+    ///
+    pub fn shadow<U>(other: &Desync<U>, project: fn(&U) -> T) -> Arc<Desync<T>>
+    where U: 'static+Send+Unpin {
+        let shadow      = Arc::new(Desync::new(other.sync(|source| project(source))));
+        let weak_shadow = Arc::downgrade(&shadow);
+
+        // Observers stop once they return true, which happens here once the shadow has been dropped
+        other.observe_changes(move |source| {
+            match weak_shadow.upgrade() {
+                Some(shadow)    => { let value = project(source); shadow.desync(move |shadow| *shadow = value); false }
+                None            => true
+            }
+        });
+
+        shadow
+    }
+
+    ///
+    /// Calls a function with the contents of this item once all of the jobs that are currently pending have completed,
+    /// and then after every job that runs on this item until it returns true
+    ///
+    pub (crate) fn observe_changes<TFn>(&self, observer: TFn)
+    where TFn: 'static+Send+Fn(&T) -> bool {
+        let data    = DataRef::<T>(&**self.data.as_ref().unwrap());
+        let waiters = Arc::clone(&self.waiters);
+
+        // Not scheduled via data_job(), which would call the observer a second time straight after this job
+        self.scheduler().desync(&self.queue, move || {
+            if !observer(unsafe { &*data.0 }) {
+                let (signal, _) = oneshot::channel();
+                waiters.lock().expect("Desync waiters lock").push(Waiter { predicate: Box::new(observer), signal });
+            }
+        });
+    }
+
     ///
     /// Performs an operation asynchronously on the contents of this item, returning the 
     /// result via a future.
@@ -121,18 +861,181 @@ impl<T: 'static+Send+Unpin> Desync<T> {
     pub fn future<TFn, TOutput>(&self, job: TFn) -> impl Future<Output=Result<TOutput, oneshot::Canceled>>+Send
     where   TFn:        'static+Send+for<'a> FnOnce(&'a mut T) -> BoxFuture<'a, TOutput>,
             TOutput:    'static+Send {
-        let data = DataRef::<T>(&**self.data.as_ref().unwrap());
+        let data        = DataRef::<T>(&**self.data.as_ref().unwrap());
+        let waiters     = Arc::clone(&self.waiters);
+        let observers   = Arc::clone(&self.panic_observers);
+
+        self.scheduler().future(&self.queue, move || {
+            let data_ptr    = data.0 as *mut T;
+            let job         = run_observing_panics(&observers, move || job(unsafe { &mut *data_ptr }));
+
+            async move {
+                let result = await_observing_panics(&observers, job).await;
+                Self::notify_waiters(&waiters, unsafe { &*data.0 });
+
+                result
+            }
+        })
+    }
+
+    ///
+    /// As for `future()`, except that the job can return any future rather than a `BoxFuture`
+    ///
+    /// This accepts an `async fn` that takes the data as its argument, or an async closure, without needing to call
+    /// `.boxed()` on the result:
+    ///
+    /// ```
+    /// # extern crate desync;
+    /// # extern crate futures;
+    /// # use ::desync::*;
+    /// # use futures::executor;
+    /// async fn increment(count: &mut i32) -> i32 {
+    ///     *count += 1;
+    ///     *count
+    /// }
+    ///
+    /// let counter = Desync::new(0);
+    /// assert!(executor::block_on(counter.future_unboxed(increment)) == Ok(1));
+    /// ```
+    ///
+    pub fn future_unboxed<TFn, TOutput>(&self, job: TFn) -> impl Future<Output=Result<TOutput, oneshot::Canceled>>+Send
+    where   TFn:        'static+Send+for<'a> DataFutureFn<'a, T, TOutput>,
+            TOutput:    'static+Send {
+        let data        = DataRef::<T>(&**self.data.as_ref().unwrap());
+        let waiters     = Arc::clone(&self.waiters);
+        let observers   = Arc::clone(&self.panic_observers);
+
+        self.scheduler().future(&self.queue, move || {
+            let data_ptr    = data.0 as *mut T;
+            let job         = run_observing_panics(&observers, move || job.call(unsafe { &mut *data_ptr }));
+
+            async move {
+                let result = await_observing_panics(&observers, job).await;
+                Self::notify_waiters(&waiters, unsafe { &*data.0 });
+
+                result
+            }
+        })
+    }
+
+    ///
+    /// Performs an operation asynchronously on this item, returning a future for the result
+    ///
+    /// Unlike `future()`, the job is always run by the scheduler in the background: polling the
+    /// returned future will never run the job (or any jobs queued ahead of it) on the current thread.
+    ///
+    pub fn future_desync<TFn, TOutput>(&self, job: TFn) -> impl 'static+Future<Output=Result<TOutput, oneshot::Canceled>>+Send
+    where   TFn:        'static+Send+for<'a> FnOnce(&'a mut T) -> BoxFuture<'a, TOutput>,
+            TOutput:    'static+Send {
+        let data        = DataRef::<T>(&**self.data.as_ref().unwrap());
+        let waiters     = Arc::clone(&self.waiters);
+        let observers   = Arc::clone(&self.panic_observers);
+
+        self.scheduler().future_desync(&self.queue, move || {
+            let data_ptr    = data.0 as *mut T;
+            let job         = run_observing_panics(&observers, move || job(unsafe { &mut *data_ptr }));
 
-        scheduler().future(&self.queue, move || {
-            let data        = data.0 as *mut T;
-            let job         = job(unsafe { &mut *data });
+            async move {
+                let result = await_observing_panics(&observers, job).await;
+                Self::notify_waiters(&waiters, unsafe { &*data.0 });
 
-            async {
-                job.await
+                result
             }
         })
     }
 
+    ///
+    /// As for `future()`, except that a guard value is held until the future returned by the job has completed
+    ///
+    /// This is useful when an external resource needs to stay locked while this object processes related state.
+    /// The guard must be `Send`, which rules out `std::sync::MutexGuard`: a guard from a lock that can be sent
+    /// between threads, or an `Arc` that owns the resource, can be used instead. If the job never runs (for example,
+    /// because an earlier job panicked), the guard is released when the job is discarded.
+    ///
+    pub fn guarded_future<TGuard, TFn, TOutput>(&self, guard: TGuard, job: TFn) -> impl Future<Output=Result<TOutput, oneshot::Canceled>>+Send
+    where   TGuard:     'static+Send,
+            TFn:        'static+Send+for<'a> FnOnce(&'a mut T) -> BoxFuture<'a, TOutput>,
+            TOutput:    'static+Send {
+        self.future(move |data| {
+            async move {
+                let result = job(data).await;
+
+                // Release the guard only once the job has completely finished
+                mem::drop(guard);
+                result
+            }.boxed()
+        })
+    }
+
+    ///
+    /// As for `future()`, except that this takes a reference-counted object, which is kept alive until the future completes
+    ///
+    /// This is useful when the future needs to outlive the code that scheduled it: there's no need to clone the `Arc`
+    /// separately to keep the object around while the job is waiting to run.
+    ///
+    pub fn arc_future<TFn, TOutput>(self: Arc<Self>, job: TFn) -> impl 'static+Future<Output=Result<TOutput, oneshot::Canceled>>+Send
+    where   TFn:        'static+Send+for<'a> FnOnce(&'a mut T) -> BoxFuture<'a, TOutput>,
+            TOutput:    'static+Send {
+        let future = self.future(job);
+
+        async move {
+            let result = future.await;
+
+            // The object can be released once the job has finished
+            mem::drop(self);
+            result
+        }
+    }
+
+    ///
+    /// Performs an operation on this item that produces a stream, returning a stream that relays its items
+    ///
+    /// The next job on this item's queue does not start until the stream has finished, so the job effectively
+    /// continues until the last item is produced. Items are passed on one at a time as the returned stream is
+    /// read, so the results are never all held in memory at once. If the returned stream is dropped, the job
+    /// stops reading from the stream it created.
+    ///
+    pub fn future_stream<TFn, TItem>(&self, job: TFn) -> impl 'static+Stream<Item=TItem>+Send+Unpin
+    where   TFn:    'static+Send+FnOnce(&mut T) -> BoxStream<'static, TItem>,
+            TItem:  'static+Send {
+        let (mut sender, receiver) = futures::channel::mpsc::channel(0);
+
+        // Run in the background, as the stream is read by the caller rather than by polling the job (we never await the future)
+        let _future = self.future_desync(move |data| {
+            let mut stream = job(data);
+
+            async move {
+                while let Some(item) = stream.next().await {
+                    if sender.send(item).await.is_err() {
+                        // Stop if the returned stream is dropped
+                        break;
+                    }
+                }
+            }.boxed()
+        });
+
+        receiver
+    }
+
+    ///
+    /// As for `future()`, except that if `max_depth` or more jobs are already waiting on this item's queue, the job
+    /// is not scheduled and the result of the fallback function is returned instead
+    ///
+    /// This sheds load from an overloaded item by returning a default or cached result rather than adding to the
+    /// backlog. The fallback is called on the thread that called this function (or, if the job is cancelled,
+    /// the thread that polls the future), as it doesn't need to access this item.
+    ///
+    pub fn future_with_fallback<TFn, TFallback, TOutput>(&self, max_depth: usize, job: TFn, fallback: TFallback) -> impl 'static+Future<Output=TOutput>+Send
+    where   TFn:        'static+Send+for<'a> FnOnce(&'a mut T) -> BoxFuture<'a, TOutput>,
+            TFallback:  'static+Send+FnOnce() -> TOutput,
+            TOutput:    'static+Send {
+        if self.queue.pending_jobs() >= max_depth {
+            future::ready(fallback()).left_future()
+        } else {
+            self.future(job).map(move |result| result.unwrap_or_else(|_| fallback())).right_future()
+        }
+    }
+
     ///
     /// After the pending operations for this item are performed, waits for the
     /// supplied future to complete and then calls the specified function
@@ -146,28 +1049,114 @@ impl<T: 'static+Send+Unpin> Desync<T> {
             }.boxed()
         })
     }
+
+    ///
+    /// As for `after()`, except waits for all of a set of futures to complete before calling the function
+    ///
+    /// The function receives the results of the futures in the same order as they were supplied.
+    ///
+    pub fn after_all<TFn, Res: 'static+Send, Futs>(&self, after: Futs, job: TFn) -> impl 'static+Future<Output=Result<Res, oneshot::Canceled>>+Send
+    where   Futs:           IntoIterator,
+            Futs::Item:     'static+Future+Send,
+            <Futs::Item as Future>::Output: Send,
+            TFn:            'static+Send+FnOnce(&mut T, Vec<<Futs::Item as Future>::Output>) -> Res {
+        self.after(future::join_all(after), job)
+    }
+
+    ///
+    /// Processes a collection of items with a single job, returning a future for all of the results
+    ///
+    /// Scheduling a job has some overhead, so this is more efficient than scheduling a future for each
+    /// item when there are a lot of items that each need only a small amount of processing.
+    ///
+    pub fn batch_future<TItem, TFn, TResult>(&self, items: Vec<TItem>, job: TFn) -> impl 'static+Future<Output=Result<Vec<TResult>, oneshot::Canceled>>+Send
+    where   TItem:      'static+Send,
+            TResult:    'static+Send,
+            TFn:        'static+Send+FnOnce(&mut T, Vec<TItem>) -> Vec<TResult> {
+        self.future(move |data| future::ready(job(data, items)).boxed())
+    }
+
+    ///
+    /// Schedules a set of future jobs on this item, returning a future that completes once they have all finished
+    ///
+    /// The jobs run one after the other, in order. The results are returned in the same order as the jobs.
+    ///
+    pub fn future_batch<TFn, TOutput>(&self, jobs: Vec<TFn>) -> impl 'static+Future<Output=Result<Vec<TOutput>, oneshot::Canceled>>+Send
+    where   TFn:        'static+Send+for<'a> FnOnce(&'a mut T) -> BoxFuture<'a, TOutput>,
+            TOutput:    'static+Send {
+        let futures = jobs.into_iter()
+            .map(|job| self.future(job))
+            .collect::<Vec<_>>();
+
+        future::join_all(futures).map(|results| results.into_iter().collect())
+    }
+
+    ///
+    /// Performs an operation asynchronously for each item in a collection
+    ///
+    /// All of the items are processed by a single job, so no other jobs can run in between them, and
+    /// there's only the overhead of scheduling one job. This is useful for things like bulk inserts.
+    ///
+    pub fn iter_desync<TItems, TFn>(&self, items: TItems, job: TFn)
+    where   TItems:     'static+Send+IntoIterator,
+            TFn:        'static+Send+Sync+Fn(&mut T, TItems::Item) {
+        self.desync(move |data| {
+            for item in items {
+                job(data, item);
+            }
+        });
+    }
+}
+
+impl<T: 'static+Send+Unpin+fmt::Debug> fmt::Debug for Desync<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        // The data can only be read without blocking if the queue is idle
+        let alternate   = fmt.alternate();
+        let data        = self.try_get(|data| if alternate { format!("{:#?}", data) } else { format!("{:?}", data) });
+
+        match data {
+            Some(data)  => fmt.debug_struct("Desync")
+                .field("state", &QueueState::Idle)
+                .field("data", &format_args!("{}", data))
+                .finish(),
+
+            None        => fmt.debug_struct("Desync")
+                .field("state", &self.queue.state())
+                .field("pending_jobs", &self.queue.pending_jobs())
+                .finish()
+        }
+    }
 }
 
 impl<T: Send+Unpin> Drop for Desync<T> {
     fn drop(&mut self) {
         use std::thread;
 
+        // Once the data has been taken by `into_mutex()` or `into_inner()`, the queue has finished (or panicked) and nothing can refer to it
+        if self.data.is_none() {
+            return;
+        }
+
         // Take the data we're about to drop from the object
-        let data = self.data.take();
+        let data            = self.data.take();
+        let drop_handler    = self.drop_handler.take();
+        let data            = move || {
+            match (data, drop_handler) {
+                (Some(data), Some(drop_handler))    => drop_handler(*Pin::into_inner(data)),
+                (data, _)                           => mem::drop(data)
+            }
+        };
+        let scheduler       = self.scheduler.load_full();
 
         // Ensure that everything on the queue has committed by queueing a last synchronous event
         // (Not synchronising the queue would make this unsafe as we would hold on to a pointer to
         // the internal data structure)
         if thread::panicking() {
             // If the thread is already panicking when we're dropped, do not panic again
-            scheduler().sync_no_panic(&self.queue, move || {
-                mem::drop(data);
-            });
+            scheduler.sync_no_panic(&self.queue, data);
         } else {
             // Thread is not panicking
-            sync(&self.queue, move || {
-                mem::drop(data);
-            });
+            scheduler.sync(&self.queue, data);
         }
     }
 }
@@ -0,0 +1,112 @@
+//!
+//! Applying the same operation to a collection of `Desync` objects
+//!
+//! A `DesyncGroup` schedules a job on each of its members at once, so the jobs run in parallel
+//! (each member still runs its own jobs in order). This is the 'scatter' half of scatter-gather
+//! parallelism: `future_all()` and `sync_all()` gather the results back together:
+//!
+//! ```
+//! # extern crate desync;
+//! # use ::desync::*;
+//! # use std::sync::*;
+//! let shards  = DesyncGroup::new(vec![Arc::new(Desync::new(1)), Arc::new(Desync::new(2)), Arc::new(Desync::new(3))]);
+//!
+//! shards.desync_all(Arc::new(|shard| *shard *= 10));
+//! let totals  = shards.sync_all(Arc::new(|shard| *shard));
+//!
+//! assert!(totals == vec![10, 20, 30]);
+//! ```
+//!
+
+use super::desync::*;
+
+use futures::future;
+use futures::future::{Future, BoxFuture, FutureExt};
+use futures::channel::oneshot;
+
+use std::sync::*;
+use std::sync::mpsc;
+
+///
+/// A job that produces a future, which can be run on any member of a group
+///
+type GroupFutureFn<T, TOutput> = dyn for<'a> Fn(&'a mut T) -> BoxFuture<'a, TOutput>+Send+Sync;
+
+///
+/// A collection of `Desync` objects that jobs can be scheduled on all at once
+///
+pub struct DesyncGroup<T: 'static+Send+Unpin> {
+    /// The objects in this group
+    members: Vec<Arc<Desync<T>>>
+}
+
+impl<T: 'static+Send+Unpin> DesyncGroup<T> {
+    ///
+    /// Creates a new group containing the specified objects
+    ///
+    pub fn new(members: Vec<Arc<Desync<T>>>) -> DesyncGroup<T> {
+        DesyncGroup { members }
+    }
+
+    ///
+    /// The objects in this group
+    ///
+    pub fn members(&self) -> &Vec<Arc<Desync<T>>> {
+        &self.members
+    }
+
+    ///
+    /// Schedules a job on every member of this group, returning immediately
+    ///
+    pub fn desync_all(&self, job: Arc<dyn Fn(&mut T)+Send+Sync>) {
+        for member in self.members.iter() {
+            let job = Arc::clone(&job);
+            member.desync(move |data| job(data));
+        }
+    }
+
+    ///
+    /// Runs a job on every member of this group in parallel, waiting for them all to finish
+    ///
+    /// The results are returned in the same order as the members.
+    ///
+    pub fn sync_all<TResult>(&self, job: Arc<dyn Fn(&mut T) -> TResult+Send+Sync>) -> Vec<TResult>
+    where TResult: 'static+Send {
+        // Schedule all of the jobs before waiting for any of them
+        let (send_result, receive_result) = mpsc::channel();
+
+        for (index, member) in self.members.iter().enumerate() {
+            let job         = Arc::clone(&job);
+            let send_result = send_result.clone();
+
+            member.desync(move |data| { send_result.send((index, job(data))).ok(); });
+        }
+        drop(send_result);
+
+        // Gather the results back into member order
+        let mut results = (0..self.members.len()).map(|_| None).collect::<Vec<_>>();
+        for (index, result) in receive_result.iter() {
+            results[index] = Some(result);
+        }
+
+        results.into_iter().map(|result| result.expect("A job in the group panicked")).collect()
+    }
+
+    ///
+    /// Schedules a job on every member of this group, returning a future for all of the results
+    ///
+    /// The results are returned in the same order as the members. The future returns `Canceled` if any of the jobs
+    /// could not be completed.
+    ///
+    pub fn future_all<TOutput>(&self, job: Arc<GroupFutureFn<T, TOutput>>) -> impl 'static+Future<Output=Result<Vec<TOutput>, oneshot::Canceled>>+Send
+    where TOutput: 'static+Send {
+        let futures = self.members.iter()
+            .map(|member| {
+                let job = Arc::clone(&job);
+                member.future_desync(move |data| job(data))
+            })
+            .collect::<Vec<_>>();
+
+        future::join_all(futures).map(|results| results.into_iter().collect())
+    }
+}
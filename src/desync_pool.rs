@@ -0,0 +1,128 @@
+//!
+//! Spreading jobs across a pool of identical `Desync` objects
+//!
+//! A `DesyncPool` is useful when there are a lot of independent jobs that each need some state to
+//! work with, but it doesn't matter which copy of the state they use. Each job is dispatched to
+//! one member of the pool, so up to one job per member can run at once:
+//!
+//! ```
+//! # extern crate desync;
+//! # use ::desync::*;
+//! let workers = DesyncPool::new(4, || 0);
+//!
+//! for _ in 0..100 {
+//!     workers.desync(|count| *count += 1);
+//! }
+//!
+//! let total: i32 = workers.iter().map(|worker| worker.sync(|count| *count)).sum();
+//! assert!(total == 100);
+//! ```
+//!
+
+use super::desync::*;
+use super::scheduler::*;
+
+use futures::future::{Future, BoxFuture};
+use futures::channel::oneshot;
+
+use std::ops::{Deref};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+///
+/// A set of `Desync` objects with the same type of data, which each job is dispatched to one of
+///
+/// Jobs go to the members of the pool in turn, or to the member with the fewest jobs waiting if the pool was created
+/// with `new_least_loaded()`. There's no ordering between jobs that are sent to different members. The members can
+/// also be accessed directly, as the pool dereferences to a slice of them.
+///
+pub struct DesyncPool<T: 'static+Send+Unpin> {
+    /// The objects in this pool
+    members: Vec<Desync<T>>,
+
+    /// The member that the next job is dispatched to (or that the search for the least loaded member starts at)
+    next: AtomicUsize,
+
+    /// True if jobs go to the member with the fewest jobs instead of to each member in turn
+    least_loaded: bool
+}
+
+impl<T: 'static+Send+Unpin> DesyncPool<T> {
+    ///
+    /// Creates a pool of `size` objects, calling `factory` to create the data for each one
+    ///
+    /// Jobs are dispatched to each member of the pool in turn.
+    ///
+    pub fn new<TFactory: Fn() -> T>(size: usize, factory: TFactory) -> DesyncPool<T> {
+        assert!(size > 0, "A DesyncPool must have at least one member");
+
+        DesyncPool {
+            members:        (0..size).map(|_| Desync::new(factory())).collect(),
+            next:           AtomicUsize::new(0),
+            least_loaded:   false
+        }
+    }
+
+    ///
+    /// Creates a pool of `size` objects that dispatches each job to the member with the fewest jobs waiting to run
+    ///
+    pub fn new_least_loaded<TFactory: Fn() -> T>(size: usize, factory: TFactory) -> DesyncPool<T> {
+        DesyncPool {
+            least_loaded: true,
+            ..Self::new(size, factory)
+        }
+    }
+
+    ///
+    /// Chooses the member of this pool that the next job should run on
+    ///
+    fn choose_member(&self) -> &Desync<T> {
+        let count = self.members.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % count;
+
+        if !self.least_loaded {
+            return &self.members[start];
+        }
+
+        // Members with equal loads are chosen in turn, so an idle pool still spreads its jobs out
+        (0..count)
+            .map(|offset| &self.members[(start + offset) % count])
+            .min_by_key(|member| {
+                let queue = member.queue();
+                queue.pending_jobs() + if queue.state() == QueueState::Running { 1 } else { 0 }
+            })
+            .expect("DesyncPool member")
+    }
+
+    ///
+    /// Schedules a job on one of the members of this pool, returning immediately
+    ///
+    pub fn desync<TFn>(&self, job: TFn) -> JobHandle
+    where TFn: 'static+Send+FnOnce(&mut T) {
+        self.choose_member().desync(job)
+    }
+
+    ///
+    /// Runs a job on one of the members of this pool, waiting for it to finish
+    ///
+    pub fn sync<TFn, Result>(&self, job: TFn) -> Result
+    where TFn: Send+FnOnce(&mut T) -> Result, Result: Send {
+        self.choose_member().sync(job)
+    }
+
+    ///
+    /// Runs a job on one of the members of this pool, returning its result via a future
+    ///
+    pub fn future<TFn, TOutput>(&self, job: TFn) -> impl Future<Output=Result<TOutput, oneshot::Canceled>>+Send
+    where   TFn:        'static+Send+for<'a> FnOnce(&'a mut T) -> BoxFuture<'a, TOutput>,
+            TOutput:    'static+Send {
+        self.choose_member().future(job)
+    }
+}
+
+impl<T: 'static+Send+Unpin> Deref for DesyncPool<T> {
+    type Target = [Desync<T>];
+
+    fn deref(&self) -> &[Desync<T>] {
+        &self.members
+    }
+}
@@ -0,0 +1,98 @@
+//!
+//! Broadcasting events from a `Desync` object to sinks
+//!
+//! Types that implement `Emit` can produce an event after every job that runs on a `Desync`
+//! object containing them. Functions added with `add_sink()` are called with these events, which
+//! makes it possible to use a `Desync` object as the authoritative state in a publish/subscribe
+//! system, with the sinks forwarding events to channels, sockets or other subscribers:
+//!
+//! ```
+//! # extern crate desync;
+//! # use ::desync::*;
+//! # use std::sync::*;
+//! # use std::sync::mpsc;
+//! struct Counter(u32);
+//!
+//! impl Emit for Counter {
+//!     type Output = u32;
+//!
+//!     fn emit(&self) -> Option<u32> { Some(self.0) }
+//! }
+//!
+//! let counter             = Desync::new(Counter(0));
+//! let (sender, events)    = mpsc::channel();
+//! let sender              = Mutex::new(sender);
+//!
+//! counter.add_sink(Arc::new(move |count: u32| { sender.lock().unwrap().send(count).ok(); }));
+//! counter.desync(|counter| counter.0 += 1);
+//!
+//! assert!(events.recv().unwrap() == 1);
+//! ```
+//!
+
+use super::desync::*;
+
+use std::cell::{Cell};
+use std::sync::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+///
+/// Trait implemented by types that produce events when they're updated by a job on a `Desync` object
+///
+pub trait Emit {
+    /// The type of event produced by this type
+    type Output;
+
+    ///
+    /// Returns the event to send to the sinks after a job has run, or `None` if there's nothing to send
+    ///
+    fn emit(&self) -> Option<Self::Output>;
+}
+
+///
+/// Identifies a sink added by `add_sink()`, so it can be removed later
+///
+#[derive(Debug)]
+pub struct SinkId {
+    /// Set to true once the sink has been removed
+    removed: Arc<AtomicBool>
+}
+
+impl<T: 'static+Send+Unpin+Emit> Desync<T> {
+    ///
+    /// Adds a function that's called with the event emitted by this object after every job that runs on it
+    ///
+    /// The sink is called on this object's queue, so it should return quickly (sending to a channel, for example).
+    ///
+    pub fn add_sink<M>(&self, sink: Arc<dyn Fn(M)+Send+Sync>) -> SinkId
+    where M: 'static+From<T::Output> {
+        let removed         = Arc::new(AtomicBool::new(false));
+        let sink_removed    = Arc::clone(&removed);
+        let is_first_call   = Cell::new(true);
+
+        self.observe_changes(move |data: &T| {
+            if sink_removed.load(Ordering::Acquire) {
+                // Stop observing once the sink has been removed
+                return true;
+            }
+
+            // The first call is made when the sink is added rather than after a job
+            if !is_first_call.replace(false) {
+                if let Some(output) = data.emit() {
+                    sink(M::from(output));
+                }
+            }
+
+            false
+        });
+
+        SinkId { removed }
+    }
+
+    ///
+    /// Stops sending events to a sink (the sink may still be called if a job is already running)
+    ///
+    pub fn remove_sink(&self, sink: SinkId) {
+        sink.removed.store(true, Ordering::Release);
+    }
+}
@@ -0,0 +1,205 @@
+//!
+//! Health checks for `Desync` objects
+//!
+//! `health_check()` reports on the state of the queue for a `Desync` object, and probes it to
+//! see whether or not it is still processing jobs. This is intended to be used to implement
+//! monitoring endpoints, such as liveness probes. `timeout_on_idle()` can be used to detect
+//! objects that have stopped receiving jobs.
+//!
+//! `len()` and `is_empty()` report how many jobs are waiting for an object, which is useful for
+//! applying backpressure to producers:
+//!
+//! ```
+//! # extern crate desync;
+//! # extern crate futures;
+//! # use ::desync::*;
+//! # use futures::executor;
+//! let counter = Desync::new(0);
+//!
+//! for _ in 0..100 {
+//!     // Wait for the object to catch up if too many jobs are waiting
+//!     if counter.len() > 10 {
+//!         executor::block_on(counter.wait_empty());
+//!     }
+//!
+//!     counter.desync(|count| *count += 1);
+//! }
+//!
+//! assert!(counter.sync(|count| *count) == 100);
+//! ```
+//!
+
+use super::desync::*;
+use super::scheduler::*;
+use super::timer::*;
+
+use futures::future;
+use futures::future::{Future};
+
+use std::sync::*;
+use std::time::{Duration, Instant};
+
+/// How long a health check waits for a job to run before deciding that a queue is unresponsive
+const RESPONSIVE_TIMEOUT: Duration = Duration::from_millis(100);
+
+///
+/// The health of a `Desync` object
+///
+#[derive(Clone, Debug)]
+pub struct DesyncHealth {
+    /// The state of the queue when the health check was made
+    pub state: QueueState,
+
+    /// The number of jobs waiting to run when the health check was made
+    pub pending_jobs: usize,
+
+    /// The time that the most recent job finished running, if any job has finished
+    pub last_job_completed_at: Option<Instant>,
+
+    /// True if a job scheduled on the object by the health check ran within 100ms
+    pub is_responsive: bool
+}
+
+impl<T: 'static+Send+Unpin> Desync<T> {
+    ///
+    /// Checks the health of this object
+    ///
+    /// This schedules an empty job, and will block for up to 100ms waiting for it to run in order to
+    /// determine if the object is responsive.
+    ///
+    pub fn health_check(&self) -> DesyncHealth {
+        // Read the state of the queue before the probe job is scheduled
+        let queue                   = self.queue();
+        let state                   = queue.state();
+        let pending_jobs            = queue.pending_jobs();
+        let last_job_completed_at   = queue.last_job_completed_at();
+
+        // The object is responsive if a new job runs in time
+        let is_responsive           = self.try_into_sync(RESPONSIVE_TIMEOUT, |_| { }).is_ok();
+
+        DesyncHealth {
+            state,
+            pending_jobs,
+            last_job_completed_at,
+            is_responsive
+        }
+    }
+
+    ///
+    /// Returns a future that completes once no new jobs have been scheduled on this object for the specified duration
+    ///
+    /// The timer starts when this is called, and restarts whenever a new job is scheduled. This can be used to
+    /// raise an alert or restart things when an object that should be receiving regular work stops doing so.
+    ///
+    pub fn timeout_on_idle(&self, duration: Duration) -> impl 'static+Future<Output=()>+Send {
+        let queue   = Arc::clone(self.queue());
+        let started = Instant::now();
+
+        async move {
+            loop {
+                // Wait until the idle period would end if no more jobs have been scheduled since the last check
+                let last_activity   = queue.last_job_scheduled_at().map(|scheduled_at| scheduled_at.max(started)).unwrap_or(started);
+                let idle_for        = last_activity.elapsed();
+
+                if idle_for >= duration {
+                    return;
+                }
+
+                delay(duration - idle_for).await;
+            }
+        }
+    }
+
+    ///
+    /// Returns the number of jobs that are waiting to run on this object
+    ///
+    /// This does not include a job that is currently running. The count can change as soon as this returns, as
+    /// other threads can schedule jobs and the queue continues to run in the background, so it should only be
+    /// used as a hint (for example, to decide whether or not a producer should slow down).
+    ///
+    pub fn len(&self) -> usize {
+        self.queue().pending_jobs()
+    }
+
+    ///
+    /// True if this object has no jobs running or waiting to run
+    ///
+    /// As for `len()`, this can change as soon as it returns.
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.queue().is_empty()
+    }
+
+    ///
+    /// Returns the state of the queue for this object
+    ///
+    /// This is a snapshot: the state can change as soon as this returns. It's mainly useful for instrumentation and
+    /// for tests that need to check that an object has finished its work.
+    ///
+    pub fn queue_state(&self) -> QueueState {
+        self.queue().state()
+    }
+
+    ///
+    /// True if this object has no jobs running or waiting to run (an alias for `is_empty()`)
+    ///
+    pub fn is_idle(&self) -> bool {
+        self.is_empty()
+    }
+
+    ///
+    /// Returns the number of jobs that are waiting to run on this object (an alias for `len()`)
+    ///
+    pub fn pending_job_count(&self) -> usize {
+        self.len()
+    }
+
+    ///
+    /// Returns a future that completes once the jobs that are currently scheduled on this object have finished
+    ///
+    /// Unlike `wait_empty()`, this does not wait for jobs that are scheduled after it's called. The future also
+    /// completes if the queue panics.
+    ///
+    pub fn drain(&self) -> impl 'static+Future<Output=()>+Send {
+        self.scheduler().drain_queue(self.queue())
+    }
+
+    ///
+    /// Returns a future that completes once this object has no jobs waiting to run
+    ///
+    /// This completes immediately if the object is already empty. Otherwise, it waits for the jobs that are
+    /// currently scheduled to finish, then checks again in case more jobs were added while it was waiting. The
+    /// future also completes if the queue panics, as its remaining jobs will never run.
+    ///
+    pub fn wait_empty(&self) -> impl 'static+Future<Output=()>+Send {
+        let queue       = Arc::clone(self.queue());
+        let scheduler   = self.scheduler();
+
+        async move {
+            if queue.is_empty() {
+                return;
+            }
+
+            loop {
+                if queue.is_panicked() {
+                    return;
+                }
+
+                // Wait for everything that's currently scheduled, then stop if nothing else has been added
+                if scheduler.future_desync(&queue, || future::ready(())).await.is_err() || queue.pending_jobs() == 0 {
+                    return;
+                }
+            }
+        }
+    }
+
+    ///
+    /// Returns a future that completes the next time this object's queue becomes idle
+    ///
+    /// Unlike `wait_empty()`, this doesn't schedule any jobs: the queue signals the future directly when it runs out
+    /// of jobs. This completes immediately if the object is already idle, and also completes if the queue panics.
+    ///
+    pub fn on_idle(&self) -> impl 'static+Future<Output=()>+Send {
+        self.queue().on_idle()
+    }
+}
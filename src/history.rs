@@ -0,0 +1,130 @@
+//!
+//! `DesyncHistory` keeps a record of the most recent states of a `Desync` object
+//!
+//! This is a debugging and auditing tool: any job performed through the history object
+//! will record a snapshot of the data once it has completed, so it's possible to see how
+//! the state of the object evolved over time (for example, when trying to work out what
+//! happened before a crash).
+//!
+
+use super::desync::*;
+
+use futures::future::{Future, FutureExt};
+
+use std::sync::*;
+use std::collections::VecDeque;
+
+///
+/// Records the last `N` snapshots of the state of a `Desync` object
+///
+/// Snapshots are taken after every job that is scheduled through this object (jobs that are
+/// scheduled directly on the `Desync` object are not recorded). The state of the object at
+/// the point the history was created is recorded as the first snapshot.
+///
+pub struct DesyncHistory<'a, T: 'static+Send+Unpin+Clone, const N: usize> {
+    /// The object that this is recording the history for
+    desync: &'a Desync<T>,
+
+    /// The snapshots recorded so far (oldest first). Only updated by jobs running on the desync queue
+    snapshots: Arc<Mutex<VecDeque<T>>>
+}
+
+impl<T: 'static+Send+Unpin+Clone> Desync<T> {
+    ///
+    /// Creates a history object that records the state of this object after each job scheduled through it
+    ///
+    /// The history stores the `N` most recent snapshots: older snapshots are discarded as newer ones
+    /// are recorded. Only jobs scheduled through the returned `DesyncHistory` are recorded: changes made
+    /// by calling `desync()`, `sync()` or any other method on this object directly do not appear in the
+    /// history.
+    ///
+    pub fn history<const N: usize>(&self) -> DesyncHistory<'_, T, N> {
+        let history = DesyncHistory {
+            desync:     self,
+            snapshots:  Arc::new(Mutex::new(VecDeque::with_capacity(N)))
+        };
+
+        // The initial state is the first snapshot
+        let snapshots = Arc::clone(&history.snapshots);
+        self.desync(move |data| DesyncHistory::<T, N>::record(&snapshots, data));
+
+        history
+    }
+}
+
+impl<'a, T: 'static+Send+Unpin+Clone, const N: usize> DesyncHistory<'a, T, N> {
+    ///
+    /// Adds a snapshot of the specified data to a list of snapshots, discarding the oldest snapshot if
+    /// there are more than `N` available
+    ///
+    fn record(snapshots: &Mutex<VecDeque<T>>, data: &T) {
+        let mut snapshots = snapshots.lock().expect("History snapshots lock");
+
+        snapshots.push_back(data.clone());
+        while snapshots.len() > N {
+            snapshots.pop_front();
+        }
+    }
+
+    ///
+    /// Performs an operation asynchronously on the object, recording a snapshot of the state once it
+    /// has completed
+    ///
+    pub fn desync<TFn>(&self, job: TFn)
+    where TFn: 'static+Send+FnOnce(&mut T) {
+        let snapshots = Arc::clone(&self.snapshots);
+
+        self.desync.desync(move |data| {
+            job(data);
+            Self::record(&snapshots, data);
+        });
+    }
+
+    ///
+    /// Performs an operation synchronously on the object, recording a snapshot of the state once it
+    /// has completed
+    ///
+    pub fn sync<TFn, Result>(&self, job: TFn) -> Result
+    where TFn: Send+FnOnce(&mut T) -> Result, Result: Send {
+        let snapshots = Arc::clone(&self.snapshots);
+
+        self.desync.sync(move |data| {
+            let result = job(data);
+            Self::record(&snapshots, data);
+
+            result
+        })
+    }
+
+    ///
+    /// Retrieves the n-th most recent snapshot (0 is the most recent), once all of the jobs currently
+    /// scheduled on the object have completed
+    ///
+    /// Returns `None` if there are not enough snapshots stored to retrieve the requested one.
+    ///
+    pub fn nth_snapshot(&self, n: usize) -> impl Future<Output=Option<T>>+Send {
+        let snapshots = Arc::clone(&self.snapshots);
+
+        self.desync.future(move |_data| {
+            async move {
+                let snapshots = snapshots.lock().expect("History snapshots lock");
+                snapshots.iter().rev().nth(n).cloned()
+            }.boxed()
+        }).map(|snapshot| snapshot.ok().flatten())
+    }
+
+    ///
+    /// Retrieves all of the snapshots stored by this object, in the order they were recorded (ie, oldest
+    /// first), once all of the jobs currently scheduled on the object have completed
+    ///
+    pub fn all_snapshots(&self) -> impl Future<Output=Vec<T>>+Send {
+        let snapshots = Arc::clone(&self.snapshots);
+
+        self.desync.future(move |_data| {
+            async move {
+                let snapshots = snapshots.lock().expect("History snapshots lock");
+                snapshots.iter().cloned().collect::<Vec<_>>()
+            }.boxed()
+        }).map(|snapshots| snapshots.unwrap_or_default())
+    }
+}
@@ -0,0 +1,96 @@
+//!
+//! `LazyHandle` provides deferred jobs for `Desync` objects
+//!
+//! A lazy job is created without being scheduled: it only runs once it has been committed.
+//! This makes it possible to accumulate a set of operations and then commit them together
+//! as a group, or to discard them if it turns out they aren't needed.
+//!
+//! ```
+//! # extern crate desync;
+//! # use ::desync::*;
+//! let list    = Desync::new(vec![]);
+//! let first   = list.lazy_desync(|list| list.push(1));
+//! let second  = list.lazy_desync(|list| list.push(2));
+//! let unused  = list.lazy_desync(|list| list.push(3));
+//!
+//! unused.cancel();
+//! LazyHandle::commit_all(vec![first, second]);
+//!
+//! assert!(list.sync(|list| list.clone()) == vec![1, 2]);
+//! ```
+//!
+
+use super::desync::*;
+
+use std::sync::*;
+use std::marker::PhantomData;
+
+///
+/// A job for a `Desync` object that will not run until it is committed
+///
+/// Handles are deliberately not `Send`: they're intended to be committed by the code that
+/// creates them. Dropping a handle without committing it discards the job.
+///
+pub struct LazyHandle<'a, T: 'static+Send+Unpin> {
+    /// The object that this job is for
+    desync: &'a Desync<T>,
+
+    /// The job to schedule when this handle is committed
+    job: Box<dyn FnOnce()+Send>,
+
+    /// Prevents the handle from being sent to another thread
+    not_send: PhantomData<*const ()>
+}
+
+impl<T: 'static+Send+Unpin> Desync<T> {
+    ///
+    /// Creates a job for this object that is not scheduled until the handle that is returned is committed
+    ///
+    pub fn lazy_desync<TFn>(&self, job: TFn) -> LazyHandle<'_, T>
+    where TFn: 'static+Send+FnOnce(&mut T) {
+        LazyHandle {
+            desync:     self,
+            job:        Box::new(self.data_job(job)),
+            not_send:   PhantomData
+        }
+    }
+}
+
+impl<'a, T: 'static+Send+Unpin> LazyHandle<'a, T> {
+    ///
+    /// Schedules the job for this handle on its `Desync` object
+    ///
+    pub fn commit(self) {
+        self.desync.scheduler().desync(self.desync.queue(), self.job);
+    }
+
+    ///
+    /// Discards the job for this handle without running it
+    ///
+    pub fn cancel(self) { }
+
+    ///
+    /// Commits a set of handles, in order
+    ///
+    /// The jobs are grouped by the `Desync` object they're for, and all of the jobs for a particular object are
+    /// added to its queue at once, so no other jobs can be scheduled in between them. Jobs for the same object
+    /// keep the order that their handles were passed in.
+    ///
+    pub fn commit_all(handles: Vec<LazyHandle<'a, T>>) {
+        // Group the jobs by the queue they're for, in the order that each queue first appears
+        let mut batches: Vec<(&'a Desync<T>, Vec<_>)> = vec![];
+
+        for handle in handles {
+            let queue = handle.desync.queue();
+
+            match batches.iter_mut().find(|(desync, _)| Arc::ptr_eq(desync.queue(), queue)) {
+                Some((_, jobs)) => jobs.push(handle.job),
+                None            => batches.push((handle.desync, vec![handle.job]))
+            }
+        }
+
+        for (desync, jobs) in batches {
+            desync.scheduler().desync_batch(desync.queue(), jobs);
+        }
+    }
+}
@@ -116,6 +116,8 @@
 #[macro_use]
 extern crate lazy_static;
 extern crate futures;
+extern crate crossbeam_queue;
+extern crate arc_swap;
 
 #[cfg(not(target_arch = "wasm32"))]
 extern crate num_cpus;
@@ -123,6 +125,54 @@ extern crate num_cpus;
 pub mod scheduler;
 pub mod desync;
 pub mod pipe;
+pub mod history;
+pub mod actor;
+pub mod async_iter;
+pub mod capture;
+pub mod lazy;
+pub mod throttle;
+pub mod debounce;
+pub mod select;
+pub mod deadline;
+pub mod health;
+pub mod rate_limit;
+pub mod channel;
+pub mod zip_latest;
+pub mod circuit_breaker;
+pub mod emit;
+pub mod panic_observer;
+pub mod read_write;
+pub mod observer;
+pub mod desync_group;
+pub mod desync_pool;
+pub mod weak_desync;
+pub mod sink;
+mod timer;
 
 pub use self::desync::*;
+pub use self::scheduler::{QueueState, QueueOverflowPolicy, QueueConfig};
 pub use self::pipe::*;
+pub use self::history::*;
+pub use self::actor::*;
+pub use self::async_iter::*;
+pub use self::capture::*;
+pub use self::lazy::*;
+pub use self::throttle::*;
+pub use self::debounce::*;
+pub use self::select::*;
+pub use self::deadline::*;
+pub use self::health::*;
+pub use self::rate_limit::*;
+pub use self::channel::*;
+pub use self::circuit_breaker::*;
+pub use self::emit::*;
+pub use self::panic_observer::*;
+pub use self::read_write::*;
+pub use self::observer::*;
+pub use self::desync_group::*;
+pub use self::desync_pool::*;
+pub use self::weak_desync::*;
+pub use self::sink::*;
+
+#[cfg(feature = "derive")]
+pub use desync_derive::DesyncWrapper;
@@ -0,0 +1,99 @@
+//!
+//! Observers that keep their own state
+//!
+//! `observe_with_context()` registers a function that is called with the contents of a `Desync`
+//! object after every job, along with a context value that belongs to the observer. This makes it
+//! easy to write observers that track statistics or compare each update with the previous one:
+//!
+//! ```
+//! # extern crate desync;
+//! # use ::desync::*;
+//! # use std::sync::*;
+//! let value       = Desync::new(0);
+//! let changes     = Arc::new(Mutex::new(vec![]));
+//!
+//! // The context holds the previous value, so the observer can report how much it changed by
+//! let observed    = Arc::clone(&changes);
+//! let _observer   = value.observe_with_context(None, move |value: &i32, previous: &mut Option<i32>| {
+//!     if let Some(previous) = previous { observed.lock().unwrap().push(*value - *previous); }
+//!     *previous = Some(*value);
+//! });
+//!
+//! value.desync(|value| *value = 3);
+//! value.desync(|value| *value = 10);
+//! value.sync(|_| { });
+//!
+//! assert!(*changes.lock().unwrap() == vec![3, 7, 0]);
+//! ```
+//!
+
+use super::desync::*;
+
+use std::cell::{RefCell};
+use std::sync::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+///
+/// Handle returned by `observe()` and `observe_with_context()`: the observer stops when this is dropped
+///
+pub struct ObserverHandle {
+    /// Set to true once the observer should stop
+    stopped: Arc<AtomicBool>
+}
+
+impl<T: 'static+Send+Unpin> Desync<T> {
+    ///
+    /// Registers an observer that is called with the contents of this object and a context value that it can update
+    ///
+    /// The observer is called once all of the jobs that are currently pending have completed, and then after every
+    /// job that runs on this object. It runs on this object's queue, so it should not take long. The observer and
+    /// its context are dropped after the next job once the returned handle has been dropped.
+    ///
+    pub fn observe_with_context<TContext, TFn>(&self, context: TContext, observer: TFn) -> ObserverHandle
+    where   TContext:   'static+Send,
+            TFn:        'static+Send+Fn(&T, &mut TContext) {
+        let stopped         = Arc::new(AtomicBool::new(false));
+        let observer_stop   = Arc::clone(&stopped);
+
+        // Observers are only ever called from jobs on the queue, so the context is never borrowed more than once at a time
+        let context         = RefCell::new(context);
+
+        self.observe_changes(move |data: &T| {
+            if observer_stop.load(Ordering::Acquire) {
+                return true;
+            }
+
+            observer(data, &mut *context.borrow_mut());
+            false
+        });
+
+        ObserverHandle { stopped }
+    }
+
+    ///
+    /// Registers a callback that is called whenever a job might have changed the contents of this object
+    ///
+    /// As for `observe_with_context()`, the callback is called once the jobs that are currently pending have completed,
+    /// and then after every job. It's called asynchronously on a queue of its own, so it can take as long as it needs
+    /// to without holding up this object (and can schedule jobs on this object to read the new value).
+    ///
+    pub fn observe<TFn>(&self, callback: TFn) -> ObserverHandle
+    where TFn: 'static+Send+Fn() {
+        let callback        = Arc::new(Mutex::new(callback));
+        let scheduler       = self.scheduler();
+        let callback_queue  = scheduler.create_job_queue();
+
+        self.observe_with_context((), move |_: &T, _: &mut ()| {
+            let callback = Arc::clone(&callback);
+
+            // A callback that panics stops the callback queue, but mustn't stop this object
+            scheduler.desync_no_panic(&callback_queue, move || (*callback.lock().expect("Observer callback lock"))());
+        })
+    }
+}
+
+impl Drop for ObserverHandle {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Release);
+    }
+}
@@ -0,0 +1,136 @@
+//!
+//! Observing panics in the jobs run on a `Desync` object
+//!
+//! A job that panics stops the queue for its object: any jobs after it will never run. `observe_panics()`
+//! registers a handler that is called with the panic payload when this happens, which is useful for
+//! logging the error or for starting recovery (for example, by creating a replacement object):
+//!
+//! ```
+//! # extern crate desync;
+//! # use ::desync::*;
+//! # use std::sync::mpsc;
+//! let object          = Desync::new(0);
+//! let (send, recv)    = mpsc::channel();
+//!
+//! let _observer = object.observe_panics(move |payload| {
+//!     send.send(payload.downcast_ref::<&str>().map(|msg| msg.to_string())).ok();
+//! });
+//!
+//! object.desync(|_| panic!("Out of cheese"));
+//! assert!(recv.recv().unwrap() == Some("Out of cheese".to_string()));
+//! # std::mem::forget(object);
+//! ```
+//!
+
+use super::desync::*;
+
+use futures::future::{Future, FutureExt};
+
+use std::any::{Any};
+use std::panic;
+use std::sync::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+///
+/// Function called with the payload of a panic
+///
+type PanicHandler = dyn Fn(&(dyn Any+Send))+Send+Sync;
+
+///
+/// The panic observers registered for an object, and the ID of each one
+///
+pub (crate) type PanicObservers = Mutex<Vec<(usize, Arc<PanicHandler>)>>;
+
+///
+/// The ID to assign to the next panic observer
+///
+static NEXT_OBSERVER_ID: AtomicUsize = AtomicUsize::new(0);
+
+///
+/// Handle returned by `observe_panics()`: the observer is removed when this is dropped
+///
+pub struct PanicObserverHandle {
+    /// The observers for the object (which may have been dropped)
+    observers: Weak<PanicObservers>,
+
+    /// The ID of the observer
+    id: usize
+}
+
+impl<T: 'static+Send+Unpin> Desync<T> {
+    ///
+    /// Registers a handler that is called with the panic payload if a job on this object panics
+    ///
+    /// The handler is called on the thread that was running the job, after which the panic continues and the
+    /// queue for this object is marked as panicked as usual. The handler stays registered until the returned
+    /// handle is dropped.
+    ///
+    pub fn observe_panics<TFn>(&self, handler: TFn) -> PanicObserverHandle
+    where TFn: 'static+Send+Sync+Fn(&(dyn Any+Send)) {
+        let id = NEXT_OBSERVER_ID.fetch_add(1, Ordering::Relaxed);
+        self.panic_observers().lock().expect("Panic observers lock").push((id, Arc::new(handler)));
+
+        PanicObserverHandle {
+            observers:  Arc::downgrade(self.panic_observers()),
+            id
+        }
+    }
+}
+
+///
+/// Calls the panic observers with a panic payload
+///
+fn notify_panic_observers(observers: &PanicObservers, payload: &(dyn Any+Send)) {
+    // Handlers are called outside of the lock, so they can register or remove observers
+    let handlers = observers.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+        .iter()
+        .map(|(_, handler)| Arc::clone(handler))
+        .collect::<Vec<_>>();
+
+    handlers.into_iter().for_each(|handler| handler(payload));
+}
+
+///
+/// Runs a job, calling the panic observers if it panics (the panic then continues as normal)
+///
+pub (crate) fn run_observing_panics<TFn, TResult>(observers: &PanicObservers, job: TFn) -> TResult
+where TFn: FnOnce() -> TResult {
+    match catch_observing_panics(observers, job) {
+        Ok(result)      => result,
+        Err(payload)    => panic::resume_unwind(payload)
+    }
+}
+
+///
+/// Runs a job, calling the panic observers and returning the payload if it panics
+///
+pub (crate) fn catch_observing_panics<TFn, TResult>(observers: &PanicObservers, job: TFn) -> Result<TResult, Box<dyn Any+Send>>
+where TFn: FnOnce() -> TResult {
+    panic::catch_unwind(panic::AssertUnwindSafe(job))
+        .map_err(|payload| {
+            notify_panic_observers(observers, &*payload);
+            payload
+        })
+}
+
+///
+/// Awaits a future, calling the panic observers if it panics (the panic then continues as normal)
+///
+pub (crate) async fn await_observing_panics<TFuture>(observers: &PanicObservers, future: TFuture) -> TFuture::Output
+where TFuture: Future {
+    match panic::AssertUnwindSafe(future).catch_unwind().await {
+        Ok(result)      => result,
+        Err(payload)    => {
+            notify_panic_observers(observers, &*payload);
+            panic::resume_unwind(payload)
+        }
+    }
+}
+
+impl Drop for PanicObserverHandle {
+    fn drop(&mut self) {
+        if let Some(observers) = self.observers.upgrade() {
+            observers.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).retain(|(id, _)| *id != self.id);
+        }
+    }
+}
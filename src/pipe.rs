@@ -44,18 +44,21 @@
 //! 
 
 use super::desync::*;
+use super::timer;
 
 use futures::*;
 use futures::future::{BoxFuture};
 use futures::stream::{Stream};
 use futures::task;
 use futures::task::{Poll, Context};
+use futures::channel::oneshot;
 
 use std::mem;
 use std::sync::*;
 use std::pin::{Pin};
 use std::ops::Deref;
 use std::collections::VecDeque;
+use std::time::{Duration, Instant};
 
 lazy_static! {
     /// The shared queue where we monitor for updates to the active pipe streams
@@ -65,9 +68,12 @@ lazy_static! {
     static ref REFERENCE_CHUTE: Desync<()> = Desync::new(());
 }
 
-/// The maximum number of items to queue on a pipe stream before we stop accepting new input
+/// The default maximum number of items to queue on a pipe stream before we stop accepting new input
 const PIPE_BACKPRESSURE_COUNT: usize = 5;
 
+/// How long `ReconnectPolicy::Immediate` waits before reconnecting to a stream that ended without producing any items
+const EMPTY_RECONNECT_DELAY: Duration = Duration::from_millis(10);
+
 /// Wraps an Arc<> that is dropped on a separate queue
 struct LazyDrop<Core: 'static+Send+Unpin> {
     reference: Option<Arc<Desync<Core>>>
@@ -105,8 +111,8 @@ impl<Core: 'static+Send+Unpin> Drop for LazyDrop<Core> {
 /// the only thing referencing this object.
 /// 
 /// Piping a stream to a `Desync` like this will cause it to start executing: ie, this is
-/// similar to calling `executor::spawn(stream)`, except that the stream will immediately
-/// start draining into the `Desync` object.
+/// similar to spawning a task on an executor to read the stream, except that the stream will
+/// immediately start draining into the `Desync` object.
 /// 
 pub fn pipe_in<Core, S, ProcessFn>(desync: Arc<Desync<Core>>, stream: S, process: ProcessFn)
 where   Core:       'static+Send+Unpin,
@@ -173,6 +179,522 @@ where   Core:       'static+Send+Unpin,
     });
 }
 
+///
+/// A processing function for `pipe_in_async`, which returns a future that can borrow the core
+///
+/// This is implemented for any `FnMut` that returns a suitable future, including async closures.
+///
+pub trait PipeFutureFn<'a, Core: 'a, Item> {
+    /// The future returned by this function
+    type Future: 'a+Send+Future<Output=()>;
+
+    /// Calls this function to process an item
+    fn call(&mut self, core: &'a mut Core, item: Item) -> Self::Future;
+}
+
+impl<'a, Core, Item, TFn, TFuture> PipeFutureFn<'a, Core, Item> for TFn
+where   Core:       'a,
+        TFn:        FnMut(&'a mut Core, Item) -> TFuture,
+        TFuture:    'a+Send+Future<Output=()> {
+    type Future = TFuture;
+
+    fn call(&mut self, core: &'a mut Core, item: Item) -> TFuture {
+        self(core, item)
+    }
+}
+
+///
+/// As for `pipe_in`, except that the processing function can return any future instead of a `BoxFuture`
+///
+/// Each item is processed by a job on the `Desync` object, which finishes when the future does, so the next
+/// item is not processed until the future for the previous one has completed. The future can await other
+/// futures without blocking the thread running the queue.
+///
+pub fn pipe_in_async<Core, S, ProcessFn>(desync: Arc<Desync<Core>>, stream: S, process: ProcessFn)
+where   Core:       'static+Send+Unpin,
+        S:          'static+Send+Unpin+Stream,
+        S::Item:    Send,
+        ProcessFn:  'static+Send+for<'a> PipeFutureFn<'a, Core, S::Item> {
+    let mut process = process;
+
+    pipe_in(desync, stream, move |core, item| process.call(core, item).boxed())
+}
+
+///
+/// How long `pipe_in_reconnecting` waits before reconnecting to a stream that has ended
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ReconnectPolicy {
+    /// Reconnect as soon as the stream ends (if the stream ended without producing any items, there's a short delay
+    /// so that a source that keeps ending immediately doesn't keep the pipe busy)
+    Immediate,
+
+    /// Wait for an exponentially increasing amount of time between reconnections
+    Backoff(ExponentialBackoff)
+}
+
+///
+/// Settings for an exponential backoff: the delay starts at `initial_delay` and is multiplied by `multiplier`
+/// every time the stream ends without producing any items, up to a maximum of `max_delay`
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ExponentialBackoff {
+    /// The delay before the first reconnection attempt (and after any stream that produced at least one item)
+    pub initial_delay: Duration,
+
+    /// The longest delay between reconnection attempts
+    pub max_delay: Duration,
+
+    /// The amount the delay is multiplied by after each reconnection attempt that doesn't produce any items
+    pub multiplier: f64
+}
+
+impl Default for ExponentialBackoff {
+    fn default() -> ExponentialBackoff {
+        ExponentialBackoff {
+            initial_delay:  Duration::from_millis(100),
+            max_delay:      Duration::from_secs(30),
+            multiplier:     2.0
+        }
+    }
+}
+
+///
+/// A stream that reconnects to its source whenever it ends
+///
+struct ReconnectingStream<S, ConnectFn> {
+    /// Function that creates a new stream
+    connect: ConnectFn,
+
+    /// How long to wait before reconnecting
+    policy: ReconnectPolicy,
+
+    /// The stream that's currently connected
+    stream: Option<S>,
+
+    /// The delay before the next reconnection, if waiting to reconnect
+    waiting: Option<BoxFuture<'static, ()>>,
+
+    /// The number of times the stream has ended without producing any items
+    failures: i32,
+
+    /// True if the stream that's currently connected has produced an item
+    produced_items: bool
+}
+
+impl<S, ConnectFn> ReconnectingStream<S, ConnectFn> {
+    ///
+    /// Returns the delay before the next reconnection attempt
+    ///
+    fn reconnect_delay(&self) -> Option<Duration> {
+        match self.policy {
+            ReconnectPolicy::Immediate          => None,
+            ReconnectPolicy::Backoff(backoff)   => {
+                let delay = backoff.initial_delay.as_secs_f64() * backoff.multiplier.powi(self.failures);
+                Some(Duration::from_secs_f64(delay.min(backoff.max_delay.as_secs_f64())))
+            }
+        }
+    }
+}
+
+impl<S, ConnectFn> Stream for ReconnectingStream<S, ConnectFn>
+where   S:          Unpin+Stream,
+        ConnectFn:  Unpin+Fn() -> S {
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, context: &mut Context) -> Poll<Option<S::Item>> {
+        loop {
+            // Wait for the delay before reconnecting
+            if let Some(waiting) = self.waiting.as_mut() {
+                match waiting.poll_unpin(context) {
+                    Poll::Pending   => { return Poll::Pending; }
+                    Poll::Ready(()) => { self.waiting = None; }
+                }
+            }
+
+            // Connect if there's no stream
+            if self.stream.is_none() {
+                let stream          = (self.connect)();
+                self.stream         = Some(stream);
+                self.produced_items = false;
+            }
+
+            // Read from the current stream
+            match self.stream.as_mut().unwrap().poll_next_unpin(context) {
+                Poll::Pending           => { return Poll::Pending; }
+                Poll::Ready(Some(item)) => { self.failures = 0; self.produced_items = true; return Poll::Ready(Some(item)); }
+                Poll::Ready(None)       => {
+                    // Stream has ended: wait and reconnect (always waiting for a stream that produced nothing, so this returns to the pipe, which checks if its target still exists)
+                    let delay       = if self.produced_items { self.reconnect_delay() } else { self.reconnect_delay().or(Some(EMPTY_RECONNECT_DELAY)) };
+                    self.stream     = None;
+                    self.waiting    = delay.map(|delay| timer::delay(delay).boxed());
+                    self.failures   = self.failures.saturating_add(1);
+                }
+            }
+        }
+    }
+}
+
+///
+/// As for `pipe_in`, except that when the stream ends, the `connect` function is called to create
+/// a new stream and the pipe continues processing with that
+///
+/// The `connect` function is called immediately to create the initial stream. The policy determines
+/// how long to wait between creating new streams. As with `pipe_in`, the pipe stops once nothing else
+/// is referencing the `Desync` object.
+///
+pub fn pipe_in_reconnecting<Core, S, ConnectFn, ProcessFn>(desync: Arc<Desync<Core>>, connect: ConnectFn, policy: ReconnectPolicy, process: ProcessFn)
+where   Core:       'static+Send+Unpin,
+        S:          'static+Send+Unpin+Stream,
+        S::Item:    Send,
+        ConnectFn:  'static+Send+Sync+Unpin+Fn() -> S,
+        ProcessFn:  'static+Send+for<'a> FnMut(&'a mut Core, S::Item) -> BoxFuture<'a, ()> {
+    let stream = ReconnectingStream {
+        connect,
+        policy,
+        stream:         None,
+        waiting:        None,
+        failures:       0,
+        produced_items: false
+    };
+
+    pipe_in(desync, stream, process)
+}
+
+///
+/// The maximum rate that items can pass through a pipe created by `pipe_throttle`
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RateLimit {
+    /// The number of items allowed in each window (this many items can also arrive in a burst after a quiet period)
+    pub max_items: usize,
+
+    /// The length of the window
+    pub window: Duration
+}
+
+impl RateLimit {
+    ///
+    /// Allows at most `max_items` items through a pipe every `window`
+    ///
+    pub fn new(max_items: usize, window: Duration) -> RateLimit {
+        assert!(max_items > 0, "A rate limit must allow at least one item");
+        assert!(window > Duration::from_secs(0), "A rate limit must have a non-zero window");
+
+        RateLimit { max_items, window }
+    }
+
+    ///
+    /// Allows at most `max_items` items through a pipe every second
+    ///
+    pub fn per_second(max_items: usize) -> RateLimit {
+        RateLimit::new(max_items, Duration::from_secs(1))
+    }
+}
+
+///
+/// Tracks how many items have been let through a rate limit
+///
+/// Each item adds one unit to the bucket, which leaks at a steady rate of `max_items` units per window. An item is
+/// only let through if there's room for it in the bucket, so the rate is measured from the actual times that items
+/// arrive rather than from when the bucket happens to be checked.
+///
+#[derive(Clone, Debug)]
+pub struct LeakyBucket {
+    /// The number of units the bucket can hold
+    capacity: f64,
+
+    /// The number of units that leak out of the bucket every second
+    leak_per_second: f64,
+
+    /// The number of units in the bucket as of `last_leak`
+    level: f64,
+
+    /// The time the level was last updated
+    last_leak: Instant
+}
+
+impl LeakyBucket {
+    ///
+    /// Creates an empty bucket for a rate limit
+    ///
+    pub fn new(rate: RateLimit) -> LeakyBucket {
+        LeakyBucket {
+            capacity:           rate.max_items as f64,
+            leak_per_second:    rate.max_items as f64 / rate.window.as_secs_f64(),
+            level:              0.0,
+            last_leak:          Instant::now()
+        }
+    }
+
+    ///
+    /// Adds an item to the bucket if there's room for it, or returns how long it will be until there is
+    ///
+    pub fn try_add(&mut self) -> Result<(), Duration> {
+        // Leak the units that have drained since the last update
+        let now         = Instant::now();
+        let elapsed     = now.duration_since(self.last_leak).as_secs_f64();
+        self.level      = (self.level - elapsed * self.leak_per_second).max(0.0);
+        self.last_leak  = now;
+
+        let overflow    = self.level + 1.0 - self.capacity;
+
+        if overflow <= 0.0 {
+            self.level += 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(overflow / self.leak_per_second))
+        }
+    }
+}
+
+///
+/// A stream that waits before returning an item if returning it would exceed a rate limit
+///
+struct ThrottledStream<S> {
+    /// The stream that's being throttled
+    stream: S,
+
+    /// Tracks the rate that items are being returned at
+    bucket: LeakyBucket,
+
+    /// True if the next item from the stream has already been allowed through the rate limit
+    allowed: bool,
+
+    /// The delay until the rate limit allows another item, if waiting for one
+    waiting: Option<BoxFuture<'static, ()>>
+}
+
+impl<S> Stream for ThrottledStream<S>
+where S: Unpin+Stream {
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, context: &mut Context) -> Poll<Option<S::Item>> {
+        while !self.allowed {
+            // Wait until the bucket has room for the next item
+            if let Some(waiting) = self.waiting.as_mut() {
+                match waiting.poll_unpin(context) {
+                    Poll::Pending   => { return Poll::Pending; }
+                    Poll::Ready(()) => { self.waiting = None; }
+                }
+            }
+
+            match self.bucket.try_add() {
+                Ok(())      => { self.allowed = true; }
+                Err(wait)   => { self.waiting = Some(timer::delay(wait).boxed()); }
+            }
+        }
+
+        // The allowance is kept until an item actually arrives
+        let next = self.stream.poll_next_unpin(context);
+
+        if let Poll::Ready(Some(_)) = &next {
+            self.allowed = false;
+        }
+
+        next
+    }
+}
+
+///
+/// As for `pipe_in`, except that items are passed on to the `Desync` object no faster than the specified rate
+///
+/// Items that arrive too quickly are left on the stream until the rate limit allows them through, so a bounded
+/// stream (such as an mpsc channel) will push back on its producer.
+///
+pub fn pipe_throttle<Core, S, ProcessFn>(desync: Arc<Desync<Core>>, stream: S, rate: RateLimit, process: ProcessFn)
+where   Core:       'static+Send+Unpin,
+        S:          'static+Send+Unpin+Stream,
+        S::Item:    Send,
+        ProcessFn:  'static+Send+for<'a> FnMut(&'a mut Core, S::Item) -> BoxFuture<'a, ()> {
+    let stream = ThrottledStream {
+        stream,
+        bucket:     LeakyBucket::new(rate),
+        allowed:    false,
+        waiting:    None
+    };
+
+    pipe_in(desync, stream, process)
+}
+
+///
+/// A `Desync` object that items from a stream are being distributed to
+///
+struct PipeTarget<Core: 'static+Send+Unpin> {
+    /// The target object (the pipe stops sending to it once it has been dropped)
+    desync: Weak<Desync<Core>>,
+
+    /// Completes as each of the items that have been sent to this target are processed, in the order they were sent
+    /// (cancelled if the target panics, as the jobs on a panicked queue are dropped)
+    processing: VecDeque<oneshot::Receiver<()>>
+}
+
+impl<Core: 'static+Send+Unpin> PipeTarget<Core> {
+    ///
+    /// Creates a new pipe target
+    ///
+    fn new(desync: &Arc<Desync<Core>>) -> PipeTarget<Core> {
+        PipeTarget {
+            desync:     Arc::downgrade(desync),
+            processing: VecDeque::new()
+        }
+    }
+
+    ///
+    /// Polls until this target has fewer than `max_processing` items waiting to be processed, returning false if it can
+    /// no longer accept items
+    ///
+    fn poll_ready(&mut self, context: &mut Context, max_processing: usize) -> Poll<bool> {
+        // Items are processed in order, so stop at the first one that's still waiting
+        while let Some(processing) = self.processing.front_mut() {
+            if processing.poll_unpin(context).is_pending() {
+                break;
+            }
+
+            self.processing.pop_front();
+        }
+
+        match self.desync.upgrade() {
+            None            => Poll::Ready(false),
+            Some(desync)    => {
+                let desync = LazyDrop::new(desync);
+
+                if desync.queue().is_panicked() {
+                    eprintln!("desync: a pipe target has panicked: no more items will be sent to it");
+                    Poll::Ready(false)
+                } else if self.processing.len() < max_processing {
+                    Poll::Ready(true)
+                } else {
+                    Poll::Pending
+                }
+            }
+        }
+    }
+
+    ///
+    /// Sends an item to be processed by this target, returning false if it can no longer accept items
+    ///
+    fn send<Item, ProcessFn>(&mut self, item: Item, process: &Arc<Mutex<ProcessFn>>) -> bool
+    where   Item:       'static+Send,
+            ProcessFn:  'static+Send+FnMut(&mut Core, Item) {
+        let desync = match self.desync.upgrade() {
+            Some(desync)    => LazyDrop::new(desync),
+            None            => { return false; }
+        };
+
+        let process             = Arc::clone(process);
+        let (done, processing)  = oneshot::channel();
+        let job                 = desync.data_job(move |core| {
+            // A panic while processing an item for one target shouldn't stop the other targets from using the function
+            let mut process = process.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            (*process)(core, item);
+            done.send(()).ok();
+        });
+
+        if desync.scheduler().desync_no_panic(desync.queue(), job) {
+            eprintln!("desync: a pipe target has panicked: no more items will be sent to it");
+            false
+        } else {
+            self.processing.push_back(processing);
+            true
+        }
+    }
+}
+
+///
+/// Pipes a stream into several `Desync` objects, sending a copy of every item to each of them
+///
+/// Each target processes its copy of an item independently. Items are read from the stream as long as every target
+/// has fewer than `PIPE_BACKPRESSURE_COUNT` items waiting, so a slow target only holds up the others once it falls that
+/// far behind. The processing function is called for one item at a time, whichever target it's for. A target that
+/// panics is skipped (with a warning) and the remaining targets continue to receive items. As with `pipe_in`, this
+/// only holds weak references to the targets: the pipe stops once all of them have been dropped or have panicked.
+///
+pub fn pipe_fan_out<Core, S, ProcessFn>(targets: Vec<Arc<Desync<Core>>>, stream: S, process: ProcessFn)
+where   Core:       'static+Send+Unpin,
+        S:          'static+Send+Unpin+Stream,
+        S::Item:    'static+Send+Clone,
+        ProcessFn:  'static+Send+FnMut(&mut Core, S::Item) {
+    let mut stream  = Box::new(stream);
+    let mut targets = targets.iter().map(PipeTarget::new).collect::<Vec<_>>();
+    let process     = Arc::new(Mutex::new(process));
+
+    PIPE_MONITOR.monitor(move |context| {
+        loop {
+            // Wait for every target to have space for another item, removing any that can no longer accept items
+            let mut index = 0;
+            let mut ready = true;
+            while index < targets.len() {
+                match targets[index].poll_ready(context, PIPE_BACKPRESSURE_COUNT) {
+                    Poll::Pending       => { ready = false; index += 1; }
+                    Poll::Ready(true)   => { index += 1; }
+                    Poll::Ready(false)  => { targets.remove(index); }
+                }
+            }
+
+            if targets.is_empty() {
+                return Poll::Ready(());
+            } else if !ready {
+                return Poll::Pending;
+            }
+
+            match stream.poll_next_unpin(context) {
+                Poll::Pending           => { return Poll::Pending; }
+                Poll::Ready(None)       => { return Poll::Ready(()); }
+                Poll::Ready(Some(next)) => { targets.retain_mut(|target| target.send(next.clone(), &process)); }
+            }
+        }
+    });
+}
+
+///
+/// Pipes a stream into several `Desync` objects, sending each item to the next target in turn
+///
+/// Items are not copied, so each one is only processed by a single target. The pipe waits for a target to finish
+/// with its previous item before sending it another, so a slow target will hold up the pipe when its turn comes
+/// around again. The processing function is called for one item at a time, whichever target it's for. Targets that
+/// panic are skipped as for `pipe_fan_out()` (an item sent just as its target panics is lost), and the pipe stops
+/// once all of the targets have been dropped or have panicked.
+///
+pub fn pipe_round_robin<Core, S, ProcessFn>(targets: Vec<Arc<Desync<Core>>>, stream: S, process: ProcessFn)
+where   Core:       'static+Send+Unpin,
+        S:          'static+Send+Unpin+Stream,
+        S::Item:    'static+Send,
+        ProcessFn:  'static+Send+FnMut(&mut Core, S::Item) {
+    let mut stream      = Box::new(stream);
+    let mut targets     = targets.iter().map(PipeTarget::new).collect::<Vec<_>>();
+    let mut next_target = 0;
+    let process         = Arc::new(Mutex::new(process));
+
+    PIPE_MONITOR.monitor(move |context| {
+        loop {
+            if targets.is_empty() {
+                return Poll::Ready(());
+            }
+
+            // Wait for the next target to be ready for another item
+            next_target %= targets.len();
+            match targets[next_target].poll_ready(context, 1) {
+                Poll::Pending       => { return Poll::Pending; }
+                Poll::Ready(true)   => { }
+                Poll::Ready(false)  => { targets.remove(next_target); continue; }
+            }
+
+            match stream.poll_next_unpin(context) {
+                Poll::Pending           => { return Poll::Pending; }
+                Poll::Ready(None)       => { return Poll::Ready(()); }
+                Poll::Ready(Some(next)) => {
+                    if targets[next_target].send(next, &process) {
+                        next_target += 1;
+                    } else {
+                        targets.remove(next_target);
+                    }
+                }
+            }
+        }
+    });
+}
+
 ///
 /// Pipes a stream into this object. Whenever an item becomes available on the stream, the
 /// processing function is called asynchronously with the item that was received. The
@@ -218,6 +740,53 @@ where   Core:       'static+Send+Unpin,
 /// ```
 /// 
 pub fn pipe<Core, S, Output, ProcessFn>(desync: Arc<Desync<Core>>, stream: S, process: ProcessFn) -> PipeStream<Output>
+where   Core:       'static+Send+Unpin,
+        S:          'static+Send+Unpin+Stream,
+        S::Item:    Send,
+        Output:     'static+Send,
+        ProcessFn:  'static+Send+for <'a> FnMut(&'a mut Core, S::Item) -> BoxFuture<'a, Output> {
+    pipe_with_capacity(desync, stream, PIPE_BACKPRESSURE_COUNT, process)
+}
+
+///
+/// As for `pipe`, except with a specific capacity for the output stream
+/// 
+/// Once `capacity` items are waiting to be read from the output stream, the pipe stops reading
+/// from the input stream until some of them have been consumed. This means that a slow consumer
+/// will slow down the producer rather than causing the pipe to buffer an unbounded amount of data.
+/// This panics if `capacity` is 0, as the pipe would never be able to produce any output.
+/// 
+pub fn pipe_with_capacity<Core, S, Output, ProcessFn>(desync: Arc<Desync<Core>>, stream: S, capacity: usize, process: ProcessFn) -> PipeStream<Output>
+where   Core:       'static+Send+Unpin,
+        S:          'static+Send+Unpin+Stream,
+        S::Item:    Send,
+        Output:     'static+Send,
+        ProcessFn:  'static+Send+for <'a> FnMut(&'a mut Core, S::Item) -> BoxFuture<'a, Output> {
+    pipe_with_limits(desync, stream, PipeStream::new(capacity, None), process)
+}
+
+///
+/// As for `pipe_with_capacity`, except that once the output stream is full the pipe waits for the
+/// consumer to read it down to half of its capacity before reading from the input stream again
+/// 
+/// This avoids waking the pipe for every item the consumer reads when the consumer is the slower
+/// side, so the input stream is read in batches instead. The point at which the pipe resumes can
+/// be changed with `PipeStream::set_low_water_mark()`. As for `pipe_with_capacity`, this panics if
+/// `capacity` is 0.
+/// 
+pub fn pipe_bounded<Core, S, Output, ProcessFn>(desync: Arc<Desync<Core>>, stream: S, capacity: usize, process: ProcessFn) -> PipeStream<Output>
+where   Core:       'static+Send+Unpin,
+        S:          'static+Send+Unpin+Stream,
+        S::Item:    Send,
+        Output:     'static+Send,
+        ProcessFn:  'static+Send+for <'a> FnMut(&'a mut Core, S::Item) -> BoxFuture<'a, Output> {
+    pipe_with_limits(desync, stream, PipeStream::new(capacity, Some(capacity/2)), process)
+}
+
+///
+/// Pipes a stream through a desync object into the specified output stream, which determines how much data is buffered
+/// 
+fn pipe_with_limits<Core, S, Output, ProcessFn>(desync: Arc<Desync<Core>>, stream: S, output_stream: PipeStream<Output>, process: ProcessFn) -> PipeStream<Output>
 where   Core:       'static+Send+Unpin,
         S:          'static+Send+Unpin+Stream,
         S::Item:    Send,
@@ -228,8 +797,7 @@ where   Core:       'static+Send+Unpin,
     let mut input_stream    = Box::new(stream);
     let process             = Arc::new(Mutex::new(process));
 
-    // Create the output stream
-    let output_stream   = PipeStream::new();
+    // Share the output stream with the monitor
     let stream_core     = Arc::clone(&output_stream.core);
     let stream_core     = Arc::downgrade(&stream_core);
 
@@ -244,10 +812,14 @@ where   Core:       'static+Send+Unpin,
                     // Fetch the core
                     let mut stream_core = stream_core.lock().unwrap();
 
-                    // If the pending queue is full, then stop processing events
-                    if stream_core.pending.len() >= stream_core.max_pipe_depth {
+                    // If the pending queue is full, then stop processing events until the consumer has read enough of it
+                    if stream_core.paused || stream_core.pending.len() >= stream_core.capacity {
                         // Wake when the stream accepts some input
-                        stream_core.backpressure_release_notify = Some(context.waker().clone());
+                        // (the monitor can be polled many times while paused, so only one waker is stored for each task)
+                        stream_core.paused = true;
+                        if !stream_core.producer_wakers.iter().any(|waker| waker.will_wake(context.waker())) {
+                            stream_core.producer_wakers.push(context.waker().clone());
+                        }
 
                         // Go back to sleep without reading from the stream
                         return Poll::Pending;
@@ -339,7 +911,13 @@ where   Core:       'static+Send+Unpin,
 /// 
 struct PipeStreamCore<Item>  {
     /// The maximum number of items we allow to be queued in this stream before producing backpressure
-    max_pipe_depth: usize,
+    capacity: usize,
+
+    /// Once the stream is full, the number of items that must be left before the producers are woken again (or `None` to wake them as soon as there's space)
+    low_water_mark: Option<usize>,
+
+    /// True if the stream has become full and the producers are waiting for it to drain to the low-water mark
+    paused: bool,
 
     /// The pending data for this stream
     pending: VecDeque<Item>,
@@ -350,8 +928,8 @@ struct PipeStreamCore<Item>  {
     /// The task to notify when the stream changes
     notify: Option<task::Waker>,
 
-    /// The task to notify when we reduce the amount of pending data
-    backpressure_release_notify: Option<task::Waker>
+    /// The tasks to notify when we reduce the amount of pending data (the producers that are waiting for space in the stream)
+    producer_wakers: Vec<task::Waker>
 }
 
 ///
@@ -363,16 +941,20 @@ pub struct PipeStream<Item> {
 
 impl<Item> PipeStream<Item> {
     ///
-    /// Creates a new, empty, pipestream
+    /// Creates a new, empty, pipestream that will buffer up to `capacity` items
     /// 
-    fn new() -> PipeStream<Item> {
+    fn new(capacity: usize, low_water_mark: Option<usize>) -> PipeStream<Item> {
+        assert!(capacity > 0, "A pipe must be able to buffer at least one item");
+
         PipeStream {
             core: Arc::new(Mutex::new(PipeStreamCore {
-                max_pipe_depth:                 PIPE_BACKPRESSURE_COUNT,
-                pending:                        VecDeque::new(),
-                closed:                         false,
-                notify:                         None,
-                backpressure_release_notify:    None
+                capacity,
+                low_water_mark,
+                paused:             false,
+                pending:            VecDeque::new(),
+                closed:             false,
+                notify:             None,
+                producer_wakers:    vec![]
             }))
         }
     }
@@ -380,10 +962,59 @@ impl<Item> PipeStream<Item> {
     ///
     /// Sets the number of items that this pipe stream will buffer before producing backpressure
     /// 
-    /// If this call is not made, this will be set to 5.
+    /// If this call is not made, this will be set to the capacity the pipe was created with (5 for pipes created
+    /// by the `pipe` function). This panics if `max_depth` is 0, as the pipe would never be able to produce any output.
     /// 
     pub fn set_backpressure_depth(&mut self, max_depth: usize) {
-        self.core.lock().unwrap().max_pipe_depth = max_depth;
+        assert!(max_depth > 0, "A pipe must be able to buffer at least one item");
+
+        let producer_wakers = {
+            let mut core    = self.core.lock().unwrap();
+            core.capacity   = max_depth;
+            core.paused     = false;
+
+            mem::take(&mut core.producer_wakers)
+        };
+
+        // The producers might be able to continue with the new capacity
+        producer_wakers.into_iter().for_each(|waker| waker.wake());
+    }
+
+    ///
+    /// Sets the number of items that must be left in this stream after it has become full before the pipe starts
+    /// reading from its input stream again
+    /// 
+    /// Pipes created by `pipe_bounded` start with a low-water mark of half their capacity, and other pipes resume as
+    /// soon as there is space in the stream.
+    /// 
+    pub fn set_low_water_mark(&mut self, low_water_mark: usize) {
+        let producer_wakers = {
+            let mut core        = self.core.lock().unwrap();
+            core.low_water_mark = Some(low_water_mark);
+
+            if core.paused && core.pending.len() <= core.resume_depth() {
+                core.paused = false;
+                mem::take(&mut core.producer_wakers)
+            } else {
+                vec![]
+            }
+        };
+
+        producer_wakers.into_iter().for_each(|waker| waker.wake());
+    }
+}
+
+impl<Item> PipeStreamCore<Item> {
+    ///
+    /// The number of pending items at or below which a paused producer can continue
+    /// 
+    fn resume_depth(&self) -> usize {
+        let below_capacity = self.capacity.saturating_sub(1);
+
+        match self.low_water_mark {
+            Some(low_water_mark)    => low_water_mark.min(below_capacity),
+            None                    => below_capacity
+        }
     }
 }
 
@@ -408,16 +1039,22 @@ impl<Item> Stream for PipeStream<Item> {
             let mut core = self.core.lock().unwrap();
 
             if let Some(item) = core.pending.pop_front() {
-                // Value waiting at the start of the stream
-                let notify_backpressure = core.backpressure_release_notify.take();
+                // Value waiting at the start of the stream (the producers can continue once the stream has drained far enough)
+                let notify_backpressure = if core.pending.len() <= core.resume_depth() {
+                    core.paused = false;
+                    mem::take(&mut core.producer_wakers)
+                } else {
+                    vec![]
+                };
 
                 (Poll::Ready(Some(item)), notify_backpressure)
             } else if core.closed {
                 // No more data will be returned from this stream
-                (Poll::Ready(None), None)
+                (Poll::Ready(None), vec![])
             } else {
                 // Stream not ready
-                let notify_backpressure = core.backpressure_release_notify.take();
+                let notify_backpressure = mem::take(&mut core.producer_wakers);
+                core.paused = false;
                 core.notify = Some(context.waker().clone());
 
                 (Poll::Pending, notify_backpressure)
@@ -425,7 +1062,7 @@ impl<Item> Stream for PipeStream<Item> {
         };
 
         // If anything needs notifying, do so outside of the lock
-        notify.map(|notify| notify.wake());
+        notify.into_iter().for_each(|notify| notify.wake());
         result
     }
 }
@@ -478,13 +1115,18 @@ impl PipeMonitor {
 
 impl PipeNotify {
     fn poll(&self, context: &mut Context) {
+        // The context itself can't be sent to the queue, so we pass the waker and create a new context there
+        let waker = context.waker().clone();
+
         // Poll for the next result
-        self.future.sync(|maybe_future| {
+        self.future.sync(move |maybe_future| {
+            let mut context = Context::from_waker(&waker);
+
             // Take ownership of the future
             let mut future = maybe_future.take();
 
             // Poll for the next result
-            match future.as_mut().map(|future| future.poll_unpin(context)) {
+            match future.as_mut().map(|future| future.poll_unpin(&mut context)) {
                 // Stop if the future completes (keep the polling function so it's deallocated)
                 None | Some(Poll::Ready(())) => { 
                     // Drop the future down the reference chute to avoid a potential deadlock
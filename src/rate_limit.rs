@@ -0,0 +1,172 @@
+//!
+//! `RateLimitedDesync` limits how often jobs can be run on a `Desync` object
+//!
+//! This is useful for protecting an object from callers that flood it with requests: calls
+//! that arrive faster than the limit are delayed rather than queued up all at once.
+//!
+//! ```
+//! # extern crate desync;
+//! # use ::desync::*;
+//! let counter = Desync::new(0);
+//! let limited = counter.rate_limit_sync(100.0);
+//!
+//! // These take around 20ms in total, as there are 10ms between each call
+//! for _ in 0..3 { limited.sync(|count| *count += 1); }
+//!
+//! assert!(counter.sync(|count| *count) == 3);
+//! ```
+//!
+
+use super::desync::*;
+
+use std::sync::*;
+use std::thread;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+///
+/// The state of a rate limiter
+///
+struct RateLimitState {
+    /// The time the most recent call was allowed to run (which may be in the future if calls are waiting)
+    last_call: Option<Instant>,
+
+    /// Jobs waiting to be scheduled by `desync()`, and when they're allowed to be scheduled
+    pending: VecDeque<(Instant, Box<dyn FnOnce()+Send>)>,
+
+    /// True if there's a thread scheduling the pending jobs
+    timer_running: bool
+}
+
+///
+/// Wrapper for a `Desync` object that limits how often jobs can be run on it
+///
+/// When this is dropped, it waits for any jobs that are waiting to be scheduled by `desync()`.
+///
+pub struct RateLimitedDesync<'a, T: 'static+Send+Unpin> {
+    /// The object that jobs are run on
+    desync: &'a Desync<T>,
+
+    /// The minimum time between calls
+    interval: Duration,
+
+    /// The state of the rate limiter, and a condition that's signalled when the timer thread finishes
+    state: Arc<(Mutex<RateLimitState>, Condvar)>
+}
+
+impl<T: 'static+Send+Unpin> Desync<T> {
+    ///
+    /// Creates a wrapper for this object that allows at most `max_calls_per_sec` calls per second
+    ///
+    /// `sync()` calls on the wrapper block the calling thread until they're allowed to run, and `desync()`
+    /// calls return immediately but delay adding the job to the queue.
+    ///
+    pub fn rate_limit_sync(&self, max_calls_per_sec: f64) -> RateLimitedDesync<'_, T> {
+        assert!(max_calls_per_sec > 0.0, "max_calls_per_sec must be positive");
+
+        RateLimitedDesync {
+            desync:     self,
+            interval:   Duration::from_secs_f64(1.0 / max_calls_per_sec),
+            state:      Arc::new((Mutex::new(RateLimitState { last_call: None, pending: VecDeque::new(), timer_running: false }), Condvar::new()))
+        }
+    }
+}
+
+impl RateLimitState {
+    ///
+    /// Reserves the next time a call is allowed to run
+    ///
+    fn reserve_slot(&mut self, interval: Duration) -> Instant {
+        let now     = Instant::now();
+        let slot    = match self.last_call {
+            Some(last_call) => (last_call + interval).max(now),
+            None            => now
+        };
+
+        self.last_call = Some(slot);
+        slot
+    }
+}
+
+impl<'a, T: 'static+Send+Unpin> RateLimitedDesync<'a, T> {
+    ///
+    /// Performs a job synchronously, blocking first if it's too soon after the previous call
+    ///
+    pub fn sync<TFn, Result>(&self, job: TFn) -> Result
+    where   TFn:    Send+FnOnce(&mut T) -> Result,
+            Result: Send {
+        let slot = { self.state.0.lock().expect("Rate limit lock").reserve_slot(self.interval) };
+
+        let now = Instant::now();
+        if slot > now {
+            thread::sleep(slot - now);
+        }
+
+        self.desync.sync(job)
+    }
+
+    ///
+    /// Performs a job asynchronously, delaying adding it to the queue if it's too soon after the previous call
+    ///
+    pub fn desync<TFn>(&self, job: TFn)
+    where TFn: 'static+Send+FnOnce(&mut T) {
+        let job         = Box::new(self.desync.data_job(job));
+        let queue       = self.desync.queue();
+        let scheduler   = self.desync.scheduler();
+        let mut state   = self.state.0.lock().expect("Rate limit lock");
+        let slot        = state.reserve_slot(self.interval);
+
+        if slot <= Instant::now() && state.pending.is_empty() {
+            // Schedule straight away
+            scheduler.desync(queue, job);
+            return;
+        }
+
+        // Leave the job for the timer thread, starting it if needed
+        state.pending.push_back((slot, job));
+
+        if !state.timer_running {
+            state.timer_running = true;
+
+            let timer_state = Arc::clone(&self.state);
+            let queue       = Arc::clone(queue);
+
+            thread::spawn(move || {
+                loop {
+                    // Fetch the next job, stopping once there are none left
+                    let (slot, job) = {
+                        let mut state = timer_state.0.lock().expect("Rate limit lock");
+
+                        match state.pending.pop_front() {
+                            Some(next)  => next,
+                            None        => {
+                                state.timer_running = false;
+                                timer_state.1.notify_all();
+                                return;
+                            }
+                        }
+                    };
+
+                    // Wait for the job's slot, then schedule it
+                    let now = Instant::now();
+                    if slot > now {
+                        thread::sleep(slot - now);
+                    }
+
+                    scheduler.desync(&queue, job);
+                }
+            });
+        }
+    }
+}
+
+impl<'a, T: 'static+Send+Unpin> Drop for RateLimitedDesync<'a, T> {
+    fn drop(&mut self) {
+        // Pending jobs must be scheduled before this returns, so they can't outlive the object they run on
+        let mut state = self.state.0.lock().expect("Rate limit lock");
+
+        while state.timer_running {
+            state = self.state.1.wait(state).expect("Rate limit lock");
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//!
+//! Splitting a `Desync` object into handles for reading and writing
+//!
+//! `read_write_split()` is useful for objects that are read much more often than they're written.
+//! Reads from the `ReadHandle` return a snapshot of the data without waiting for the queue, so they
+//! can happen concurrently with each other and with writes. Writes made through the `WriteHandle`
+//! run on the queue as usual and replace the snapshot once they're complete, so readers never see
+//! a partially updated value:
+//!
+//! ```
+//! # extern crate desync;
+//! # use ::desync::*;
+//! let settings            = Desync::new(vec![1, 2, 3]);
+//! let (reader, writer)    = settings.read_write_split();
+//!
+//! writer.write(|settings| settings.push(4));
+//! writer.sync();
+//!
+//! assert!(*reader.read() == vec![1, 2, 3, 4]);
+//! ```
+//!
+
+use super::desync::*;
+
+use std::sync::*;
+
+///
+/// Handle for reading the latest snapshot of an object split by `read_write_split()`
+///
+/// Read handles can be cloned and sent to other threads.
+///
+#[derive(Clone)]
+pub struct ReadHandle<T> {
+    /// The snapshot made after the most recent write
+    snapshot: Arc<RwLock<Arc<T>>>
+}
+
+///
+/// Handle for updating an object split by `read_write_split()`
+///
+pub struct WriteHandle<'a, T: 'static+Send+Unpin> {
+    /// The object being written to
+    desync: &'a Desync<T>,
+
+    /// The snapshot shared with the read handles
+    snapshot: Arc<RwLock<Arc<T>>>
+}
+
+impl<T: 'static+Send+Sync+Unpin+Clone> Desync<T> {
+    ///
+    /// Splits this object into a handle that can read snapshots of the data and a handle that updates them
+    ///
+    /// The snapshot starts as a copy of the data once the jobs that are currently queued have finished. Only writes
+    /// made through the `WriteHandle` update the snapshot: jobs scheduled on this object directly are not seen by
+    /// readers until the next write.
+    ///
+    pub fn read_write_split(&self) -> (ReadHandle<T>, WriteHandle<'_, T>) {
+        let snapshot = Arc::new(RwLock::new(Arc::new(self.sync(|data| data.clone()))));
+
+        (ReadHandle { snapshot: Arc::clone(&snapshot) }, WriteHandle { desync: self, snapshot })
+    }
+}
+
+impl<T> ReadHandle<T> {
+    ///
+    /// Retrieves the snapshot made after the most recent write
+    ///
+    /// This never waits for the queue. The snapshot is not copied, and stays the same even if there are further
+    /// writes while it's being used.
+    ///
+    pub fn read(&self) -> Arc<T> {
+        Arc::clone(&*self.snapshot.read().expect("Snapshot lock"))
+    }
+}
+
+impl<'a, T: 'static+Send+Sync+Unpin+Clone> WriteHandle<'a, T> {
+    ///
+    /// Updates the object in the background, replacing the snapshot seen by readers once the update is complete
+    ///
+    pub fn write<TFn>(&self, update: TFn)
+    where TFn: 'static+Send+FnOnce(&mut T) {
+        let snapshot = Arc::clone(&self.snapshot);
+
+        self.desync.desync(move |data| {
+            update(data);
+
+            let new_snapshot = Arc::new(data.clone());
+            *snapshot.write().expect("Snapshot lock") = new_snapshot;
+        });
+    }
+
+    ///
+    /// Waits for any writes that are in progress to finish, so the readers will see their results
+    ///
+    pub fn sync(&self) {
+        self.desync.sync(|_| { });
+    }
+}
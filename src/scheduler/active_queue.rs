@@ -1,6 +1,7 @@
 use super::job_queue::*;
 use super::queue_state::*;
 
+use std::mem;
 use std::thread;
 
 ///
@@ -13,9 +14,15 @@ pub (super) struct ActiveQueue<'a> {
 impl<'a> Drop for ActiveQueue<'a> {
     fn drop(&mut self) {
         if thread::panicking() {
-            self.queue.core.lock()
-                .map(|mut core| core.state = QueueState::Panicked)
-                .ok();
+            // The queue will never become idle and its jobs will never run, so anything waiting for those is cancelled (outside of the lock)
+            let (idle_notifiers, jobs) = self.queue.core.lock()
+                .map(|mut core| { core.state = QueueState::Panicked; (mem::take(&mut core.idle_notifiers), mem::take(&mut core.queue)) })
+                .unwrap_or_default();
+            mem::drop(idle_notifiers);
+            mem::drop(jobs);
+
+            // Threads waiting for space on the queue need to find out that it has panicked
+            self.queue.space_available.notify_all();
         }
     }
 }
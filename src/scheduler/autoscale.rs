@@ -0,0 +1,184 @@
+use super::core::*;
+use super::desync_scheduler::*;
+
+use std::sync::*;
+use std::thread;
+use std::time::{Duration, Instant};
+
+///
+/// How often the autoscaler checks the length of the schedule
+///
+const AUTOSCALE_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+///
+/// How long the schedule must be empty before `enable_autoscale()` removes a thread
+///
+const DEFAULT_AUTOSCALE_IDLE_TIME: Duration = Duration::from_secs(1);
+
+///
+/// The settings for a scheduler that changes its thread limit depending on how many queues are waiting
+///
+pub (super) struct Autoscale {
+    /// The thread limit never goes below this
+    min_threads: usize,
+
+    /// The thread limit never goes above this
+    max_threads: usize,
+
+    /// The limit is raised when more than this many queues are waiting for a thread
+    scale_up_threshold: usize,
+
+    /// The limit is lowered when there have been no queues waiting for a thread for this long
+    idle_time: Duration,
+
+    /// The time that the schedule was first seen to be empty, or `None` if there were queues waiting at the last check
+    idle_since: Mutex<Option<Instant>>
+}
+
+impl Scheduler {
+    ///
+    /// Starts adjusting the number of threads for this scheduler depending on how many queues are waiting to run
+    ///
+    /// The thread limit starts at `min_threads`. While more than `scale_up_threshold` queues are waiting for a thread,
+    /// it's raised by one (up to `max_threads`) every 50ms. Once no queues have been waiting for a second, it's lowered
+    /// by one (down to `min_threads`) and a thread is removed, then again after every further second of idle time.
+    /// The autoscaler runs on its own thread, so it can add threads even when all of the scheduler's threads are busy. It
+    /// replaces any previous autoscaler for this scheduler, and stops when the scheduler is dropped.
+    ///
+    pub fn enable_autoscale(&self, min_threads: usize, max_threads: usize, scale_up_threshold: usize) {
+        self.enable_autoscale_with_idle_time(min_threads, max_threads, scale_up_threshold, DEFAULT_AUTOSCALE_IDLE_TIME);
+    }
+
+    ///
+    /// As for `enable_autoscale()`, except with the length of time the scheduler must be idle before it removes a thread
+    ///
+    pub fn enable_autoscale_with_idle_time(&self, min_threads: usize, max_threads: usize, scale_up_threshold: usize, idle_time: Duration) {
+        assert!(min_threads <= max_threads, "The minimum number of threads must not be more than the maximum");
+
+        let autoscale = Arc::new(Autoscale {
+            min_threads,
+            max_threads,
+            scale_up_threshold,
+            idle_time,
+            idle_since: Mutex::new(None)
+        });
+
+        // Replace any existing autoscaler (which will stop the next time it checks the schedule)
+        *self.core.autoscale.lock().expect("Autoscale lock") = Some(Arc::clone(&autoscale));
+        self.set_max_threads(min_threads);
+        Self::despawn_excess_threads(&self.core);
+
+        // The monitor only holds a weak reference to the scheduler, so it stops if the scheduler is dropped
+        let core = Arc::downgrade(&self.core);
+
+        thread::Builder::new()
+            .name("desync-autoscale".to_string())
+            .spawn(move || {
+                loop {
+                    thread::sleep(AUTOSCALE_CHECK_INTERVAL);
+
+                    let core = match core.upgrade() {
+                        Some(core)  => core,
+                        None        => break
+                    };
+
+                    if !autoscale.check(&core) {
+                        break;
+                    }
+                }
+            })
+            .expect("Autoscale thread");
+    }
+
+    ///
+    /// Stops adjusting the number of threads for this scheduler (the thread limit is left where it is)
+    ///
+    pub fn disable_autoscale(&self) {
+        *self.core.autoscale.lock().expect("Autoscale lock") = None;
+    }
+
+    ///
+    /// Removes threads from a scheduler core until it's running no more than the maximum number
+    ///
+    /// Unlike `despawn_threads_if_overloaded()`, this doesn't wait for the threads to stop: any thread that is running a
+    /// queue finishes it before stopping.
+    ///
+    fn despawn_excess_threads(core: &SchedulerCore) {
+        let max_threads = { *core.max_threads.lock().expect("Max threads lock") };
+        let to_despawn  = {
+            let mut threads     = core.threads.lock().expect("Scheduler threads lock");
+            let mut to_despawn  = vec![];
+
+            while threads.len() > max_threads {
+                to_despawn.push(threads.pop().expect("Missing threads").1);
+            }
+
+            to_despawn
+        };
+
+        // Dropping the join handles leaves the threads to finish on their own
+        to_despawn.into_iter().for_each(|thread| { thread.despawn(); });
+    }
+}
+
+impl Autoscale {
+    ///
+    /// Adjusts the thread limit for a scheduler. Returns false if this autoscaler has been replaced or disabled
+    ///
+    fn check(self: &Arc<Self>, core: &Arc<SchedulerCore>) -> bool {
+        // Stop if this is no longer the scheduler's autoscaler
+        let is_current = match &*core.autoscale.lock().expect("Autoscale lock") {
+            Some(current)   => Arc::ptr_eq(current, self),
+            None            => false
+        };
+
+        if !is_current {
+            return false;
+        }
+
+        let waiting_queues  = core.schedule.len();
+        let thread_count    = core.threads.lock().expect("Scheduler threads lock").len();
+        let mut idle_since  = self.idle_since.lock().expect("Autoscale idle lock");
+
+        if waiting_queues > self.scale_up_threshold {
+            *idle_since = None;
+
+            if thread_count < self.max_threads {
+                // Raise the limit and start a thread for one of the waiting queues
+                {
+                    let mut max_threads = core.max_threads.lock().expect("Max threads lock");
+                    *max_threads        = (thread_count + 1).max(*max_threads).min(self.max_threads);
+                }
+
+                core.schedule_thread(Arc::clone(core));
+            }
+        } else if waiting_queues == 0 {
+            let now         = Instant::now();
+            let idle_start  = *idle_since.get_or_insert(now);
+
+            if now.duration_since(idle_start) >= self.idle_time {
+                // Start timing the idle period again, so threads are removed one at a time
+                *idle_since = Some(now);
+
+                let lowered = {
+                    let mut max_threads = core.max_threads.lock().expect("Max threads lock");
+
+                    if *max_threads > self.min_threads {
+                        *max_threads = (*max_threads - 1).max(self.min_threads);
+                        true
+                    } else {
+                        false
+                    }
+                };
+
+                if lowered {
+                    Scheduler::despawn_excess_threads(core);
+                }
+            }
+        } else {
+            *idle_since = None;
+        }
+
+        true
+    }
+}
@@ -1,29 +1,99 @@
 use super::scheduler_thread::*;
 use super::job_queue::*;
 use super::queue_state::*;
+use super::queue_registry::*;
 use super::wake_queue::*;
+use super::job_limit::*;
+use super::scheduler_config::*;
+use super::autoscale::*;
 
+use std::mem;
+use std::panic;
+use std::process;
 use std::sync::*;
-use std::collections::vec_deque::*;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use futures::task;
 use futures::task::{Context};
+use crossbeam_queue::{SegQueue};
+
+#[cfg(not(target_arch = "wasm32"))]
+use num_cpus;
+
+#[cfg(not(target_arch = "wasm32"))]
+const MIN_THREADS: usize = 8;
+
+///
+/// The default maximum number of threads in a scheduler 
+///
+#[cfg(not(target_arch = "wasm32"))]
+fn initial_max_threads() -> usize {
+    MIN_THREADS.max(num_cpus::get()*2)
+}
+
+///
+/// The default maximum number of threads in a scheduler 
+///
+#[cfg(target_arch = "wasm32")]
+fn initial_max_threads() -> usize {
+    0
+}
 
 ///
 /// The scheduler core contains the internal data used by the scheduler
 ///
 pub (super) struct SchedulerCore {
-    /// The queues that are active in the scheduler
-    pub (super) schedule: Arc<Mutex<VecDeque<Arc<JobQueue>>>>,
+    /// The queues that are active in the scheduler (lock-free, as every thread reads from this and every new job can write to it)
+    pub (super) schedule: Arc<SegQueue<Arc<JobQueue>>>,
 
     /// Active threads and whether or not they're busy
     pub (super) threads: Mutex<Vec<(Arc<Mutex<bool>>, SchedulerThread)>>,
 
     /// The maximum number of threads permitted in this scheduler
-    pub (super) max_threads: Mutex<usize>
+    pub (super) max_threads: Mutex<usize>,
+
+    /// If set, the spawner used to run jobs instead of creating dedicated threads
+    pub (super) spawner: Option<Arc<dyn ThreadSpawner>>,
+
+    /// If set, limits the number of jobs that can run on the scheduler threads at once
+    pub (super) job_limit: Option<Arc<JobLimit>>,
+
+    /// The configuration for this scheduler
+    pub (super) config: SchedulerConfig,
+
+    /// If true, the process is aborted if a scheduler thread panics while running a queue, instead of recovering
+    pub (super) panic_on_thread_death: AtomicBool,
+
+    /// False once the scheduler has started shutting down (new background jobs are discarded)
+    pub (super) accepting_jobs: AtomicBool,
+
+    /// The queues that have been scheduled on this scheduler (used to find the ones that are in flight)
+    pub (super) queues: QueueRegistry,
+
+    /// If set, adjusts the maximum number of threads depending on how many queues are waiting to run
+    pub (super) autoscale: Mutex<Option<Arc<Autoscale>>>
 }
 
 impl SchedulerCore {
+    ///
+    /// Creates a new scheduler core with the default maximum number of threads, which spawns its own threads and
+    /// doesn't limit the number of jobs in flight
+    ///
+    pub (super) fn new(config: SchedulerConfig) -> SchedulerCore {
+        SchedulerCore {
+            schedule:               Arc::new(SegQueue::new()),
+            threads:                Mutex::new(vec![]),
+            max_threads:            Mutex::new(initial_max_threads()),
+            spawner:                None,
+            job_limit:              None,
+            config,
+            panic_on_thread_death:  AtomicBool::new(false),
+            accepting_jobs:         AtomicBool::new(true),
+            queues:                 QueueRegistry::new(),
+            autoscale:              Mutex::new(None)
+        }
+    }
+
     ///
     /// Wakes a thread to run a dormant queue. Returns true if a thread was woken up
     ///
@@ -33,12 +103,18 @@ impl SchedulerCore {
 
         // Schedule work on this dormant thread
         let work_core   = Arc::clone(&core);
+        let job_limit   = self.job_limit.clone();
         let do_work     = move |work: Arc<JobQueue>| {
             let waker       = Arc::new(WakeQueue(Arc::clone(&work), Arc::clone(&work_core)));
             let waker       = task::waker_ref(&waker);
             let mut context = Context::from_waker(&waker);
 
-            work.drain(&mut context)
+            // A panic would otherwise end the thread, leaving it marked as busy forever
+            let drained     = panic::catch_unwind(panic::AssertUnwindSafe(|| work.drain(&mut context, job_limit.as_deref())));
+
+            if drained.is_err() {
+                work_core.recover_from_panic(&work, Arc::clone(&work_core));
+            }
         };
 
         if !self.schedule_dormant(move || Self::next_to_run(&schedule), do_work) {
@@ -60,7 +136,7 @@ impl SchedulerCore {
     /// If a queue is idle and has pending jobs, places it in the schedule
     ///
     pub (super) fn reschedule_queue(&self, queue: &Arc<JobQueue>, core: Arc<SchedulerCore>) {
-        let reschedule = {
+        let (reschedule, idle_notifiers) = {
             let mut core = queue.core.lock().expect("JobQueue core lock");
 
             if core.state == QueueState::Idle {
@@ -68,20 +144,41 @@ impl SchedulerCore {
                 if core.queue.len() > 0 {
                     // Need to schedule the queue after this event
                     core.state = QueueState::Pending;
-                    true
+                    (true, vec![])
                 } else {
                     // Queue is empty and can go back to idle
                     core.state = QueueState::Idle;
-                    false
+                    (false, core.take_idle_notifiers())
                 }
             } else {
-                false
+                (false, vec![])
             }
         };
 
+        signal_idle(idle_notifiers);
+
         if reschedule {
-            self.schedule.lock().expect("Schedule lock").push_back(queue.clone());
-            self.schedule_thread(core);
+            self.schedule_queue(Arc::clone(queue), core);
+        }
+    }
+
+    ///
+    /// Makes a pending queue available to run, either on its dedicated thread or on one of the shared threads
+    ///
+    pub (super) fn schedule_queue(&self, queue: Arc<JobQueue>, core: Arc<SchedulerCore>) {
+        let dedicated_thread = {
+            let mut queue_core = queue.core.lock().expect("JobQueue core lock");
+
+            self.queues.register(&queue, &mut queue_core.registered_with);
+            queue_core.dedicated_thread.clone()
+        };
+
+        match dedicated_thread {
+            Some(dedicated_thread)  => dedicated_thread.wake(),
+            None                    => {
+                self.schedule.push(queue);
+                self.schedule_thread(core);
+            }
         }
     }
 
@@ -89,12 +186,9 @@ impl SchedulerCore {
     /// Finds the next queue that should be run. If this returns successfully, the queue will 
     /// be marked as running.
     /// 
-    pub (super) fn next_to_run(schedule: &Arc<Mutex<VecDeque<Arc<JobQueue>>>>) -> Option<Arc<JobQueue>> {
-        // Search the queues...
-        let mut schedule = schedule.lock().expect("Schedule lock");
-
+    pub (super) fn next_to_run(schedule: &SegQueue<Arc<JobQueue>>) -> Option<Arc<JobQueue>> {
         // Find a queue where the state is pending
-        while let Some(q) = schedule.pop_front() {
+        while let Some(q) = schedule.pop() {
             let mut core = q.core.lock().expect("JobQueue core lock");
 
             if core.state == QueueState::Pending {
@@ -159,6 +253,106 @@ impl SchedulerCore {
         false
     }
 
+    ///
+    /// Called when a scheduler thread has panicked while draining a queue
+    ///
+    /// Panics in jobs leave the queue in the `Panicked` state, and are reported to whatever is waiting for the queue, so
+    /// the thread just carries on. If the panic happened anywhere else, the scheduler itself has failed: this aborts the
+    /// process if `panic_on_thread_death` is set, or otherwise puts the queue back in the schedule for another thread
+    /// to pick up, as it may still be marked as running.
+    ///
+    pub (super) fn recover_from_panic(&self, queue: &Arc<JobQueue>, core: Arc<SchedulerCore>) {
+        // `ActiveQueue` marks the queue as panicked when a job panics
+        if queue.core.lock().map(|core| core.state == QueueState::Panicked).unwrap_or(false) {
+            return;
+        }
+
+        if self.panic_on_thread_death.load(Ordering::Relaxed) {
+            process::abort();
+        }
+
+        let reschedule = {
+            let mut core = queue.core.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+            if core.state == QueueState::Running {
+                core.state = QueueState::Pending;
+                true
+            } else {
+                false
+            }
+        };
+
+        if reschedule {
+            self.schedule_queue(Arc::clone(queue), core);
+        }
+    }
+
+    ///
+    /// Returns the queues that are waiting for a thread or are running on one
+    ///
+    /// Queues that are waiting for a future to wake them, or that are running on a thread that called `sync()` or
+    /// polled a future, are not included.
+    ///
+    pub (super) fn active_queues(&self) -> Vec<Arc<JobQueue>> {
+        self.queues.queues()
+            .into_iter()
+            .filter(|queue| {
+                match queue.core.lock().expect("JobQueue core lock").state {
+                    QueueState::Pending             |
+                    QueueState::Running             |
+                    QueueState::AwokenWhileRunning  |
+                    QueueState::WaitingForUnpark    => true,
+                    _other                          => false
+                }
+            })
+            .collect()
+    }
+
+    ///
+    /// Stops accepting new jobs and removes the jobs that have not started yet from the queues that are scheduled
+    /// or running, returning the number of jobs that were removed
+    ///
+    /// Jobs that cannot be cancelled (synchronous jobs that another thread is waiting for and futures that have
+    /// already started) are left to run.
+    ///
+    pub (super) fn terminate(&self) -> usize {
+        self.accepting_jobs.store(false, Ordering::SeqCst);
+
+        let mut removed = 0;
+
+        for queue in self.active_queues() {
+            let (cancelled, idle_notifiers) = {
+                let mut queue_core  = queue.core.lock().expect("JobQueue core lock");
+                let cancelled       = queue_core.take_cancellable_jobs();
+
+                // Queues that are empty now won't find anything to run, so they can go back to idle (threads skip idle
+                // queues when they find them in the schedule)
+                if queue_core.state == QueueState::Pending && queue_core.queue.is_empty() {
+                    queue_core.state = QueueState::Idle;
+                }
+
+                (cancelled, queue_core.take_idle_notifiers())
+            };
+
+            // Jobs are dropped outside of the lock as they might try to wake futures
+            removed += cancelled.len();
+            mem::drop(cancelled);
+            signal_idle(idle_notifiers);
+        }
+
+        removed
+    }
+
+    ///
+    /// Creates a new thread for this scheduler (using the spawner if there is one)
+    ///
+    pub (super) fn create_thread(&self, index: usize) -> SchedulerThread {
+        match &self.spawner {
+            Some(spawner)   => SchedulerThread::with_spawner(Arc::clone(spawner)),
+            None            => SchedulerThread::new(self.config.thread_name(index))
+        }
+    }
+
     ///
     /// If we're running fewer than the maximum number of threads, try to spawn a new one
     ///
@@ -169,7 +363,7 @@ impl SchedulerCore {
         if threads.len() < max_threads {
             // Create a new thread
             let is_busy     = Arc::new(Mutex::new(false));
-            let new_thread  = SchedulerThread::new();
+            let new_thread  = self.create_thread(threads.len());
             threads.push((is_busy, new_thread));
             
             true
@@ -0,0 +1,196 @@
+use super::core::*;
+use super::job_queue::*;
+use super::queue_state::*;
+use super::wake_queue::*;
+use super::desync_scheduler::*;
+
+use std::panic;
+use std::sync::*;
+use std::thread;
+
+use futures::task;
+use futures::task::{Context};
+
+///
+/// Used to wake the dedicated thread for a queue when the queue has jobs to run
+///
+pub (super) struct DedicatedThreadSignal {
+    /// True if the queue has been scheduled since the thread last checked it
+    woken: Mutex<bool>,
+
+    /// Notified when `woken` is set
+    wake: Condvar
+}
+
+///
+/// Handle for a thread started by `Scheduler::spawn_dedicated_thread()`
+///
+/// The thread stops once this is dropped or `despawn()` is called, and the queue goes back to being run by
+/// the scheduler's shared threads.
+///
+pub struct DedicatedThreadHandle {
+    /// The queue that the thread runs
+    queue: Arc<JobQueue>,
+
+    /// The signal used to wake the thread
+    signal: Arc<DedicatedThreadSignal>,
+
+    /// The scheduler that runs the queue once the thread has stopped
+    core: Arc<SchedulerCore>,
+
+    /// The thread itself (taken by `despawn()`)
+    thread: Option<thread::JoinHandle<()>>
+}
+
+impl DedicatedThreadSignal {
+    ///
+    /// Wakes the dedicated thread so that it checks its queue
+    ///
+    pub (super) fn wake(&self) {
+        *self.woken.lock().expect("Dedicated thread lock") = true;
+        self.wake.notify_one();
+    }
+
+    ///
+    /// Waits until the dedicated thread has been woken
+    ///
+    fn wait(&self) {
+        let mut woken = self.woken.lock().expect("Dedicated thread lock");
+
+        while !*woken {
+            woken = self.wake.wait(woken).expect("Dedicated thread lock");
+        }
+
+        *woken = false;
+    }
+}
+
+impl Scheduler {
+    ///
+    /// Starts a thread that runs the jobs for a single queue, and never runs jobs from any other queue
+    ///
+    /// This is useful for latency-sensitive queues, which won't have to wait for one of the shared threads to
+    /// become available. The thread doesn't count towards the scheduler's maximum number of threads. Jobs run by
+    /// `sync()` on an idle queue still run on the calling thread, as for any other queue.
+    ///
+    /// A queue can only have one dedicated thread at a time: starting a new one stops the old one.
+    ///
+    pub fn spawn_dedicated_thread(&self, queue: &Arc<JobQueue>) -> DedicatedThreadHandle {
+        let signal = Arc::new(DedicatedThreadSignal { woken: Mutex::new(false), wake: Condvar::new() });
+
+        // Replace any existing dedicated thread (which will stop once it's next woken)
+        let previous = queue.core.lock().expect("JobQueue core lock").dedicated_thread.replace(Arc::clone(&signal));
+        if let Some(previous) = previous { previous.wake(); }
+
+        let thread_queue    = Arc::clone(queue);
+        let thread_signal   = Arc::clone(&signal);
+        let core            = Arc::clone(&self.core);
+        let name            = match queue.name() {
+            Some(name)  => format!("desync-dedicated-{}", name),
+            None        => "desync-dedicated".to_string()
+        };
+
+        let thread = thread::Builder::new()
+            .name(name)
+            .spawn(move || run_dedicated_thread(thread_queue, thread_signal, core))
+            .expect("Dedicated thread");
+
+        // The queue might already be waiting in the schedule, or might have jobs that haven't been scheduled yet
+        signal.wake();
+
+        DedicatedThreadHandle {
+            queue:  Arc::clone(queue),
+            signal,
+            core:   Arc::clone(&self.core),
+            thread: Some(thread)
+        }
+    }
+}
+
+///
+/// Runs the jobs for a queue whenever the dedicated thread is woken, until the queue is given a different thread
+///
+fn run_dedicated_thread(queue: Arc<JobQueue>, signal: Arc<DedicatedThreadSignal>, core: Arc<SchedulerCore>) {
+    let waker       = Arc::new(WakeQueue(Arc::clone(&queue), Arc::clone(&core)));
+    let waker       = task::waker_ref(&waker);
+    let mut context = Context::from_waker(&waker);
+
+    loop {
+        signal.wait();
+
+        // Claim the queue if it's waiting to run (the queue lock also protects the check that this is still its thread)
+        let claimed = {
+            let mut queue_core = queue.core.lock().expect("JobQueue core lock");
+
+            match &queue_core.dedicated_thread {
+                Some(current) if Arc::ptr_eq(current, &signal)  => { }
+                _                                               => { return; }
+            }
+
+            if queue_core.state == QueueState::Pending && !queue_core.queue.is_empty() {
+                queue_core.state = QueueState::Running;
+                true
+            } else {
+                false
+            }
+        };
+
+        if claimed {
+            // A panicking job leaves the queue in the panicked state, and this thread waits to be despawned
+            let drained = panic::catch_unwind(panic::AssertUnwindSafe(|| queue.drain(&mut context, None)));
+
+            if drained.is_err() {
+                core.recover_from_panic(&queue, Arc::clone(&core));
+            }
+        }
+    }
+}
+
+impl DedicatedThreadHandle {
+    ///
+    /// Stops the dedicated thread, returning a handle that can be used to wait for it to finish
+    ///
+    /// The thread finishes the job it's running (if any) first, and the remaining jobs are run by the scheduler's
+    /// shared threads.
+    ///
+    pub fn despawn(mut self) -> thread::JoinHandle<()> {
+        self.stop();
+        self.thread.take().expect("Dedicated thread")
+    }
+
+    ///
+    /// Detaches the queue from the dedicated thread and wakes the thread so that it stops
+    ///
+    fn stop(&self) {
+        let reschedule = {
+            let mut queue_core = self.queue.core.lock().expect("JobQueue core lock");
+
+            let is_current = match &queue_core.dedicated_thread {
+                Some(current)   => Arc::ptr_eq(current, &self.signal),
+                None            => false
+            };
+
+            if is_current {
+                queue_core.dedicated_thread = None;
+            }
+
+            // A queue that was waiting for the dedicated thread needs to go to the shared threads instead
+            is_current && queue_core.state == QueueState::Pending
+        };
+
+        self.signal.wake();
+
+        if reschedule {
+            self.core.schedule_queue(Arc::clone(&self.queue), Arc::clone(&self.core));
+        }
+    }
+}
+
+impl Drop for DedicatedThreadHandle {
+    fn drop(&mut self) {
+        // The thread has already been stopped if it was despawned
+        if self.thread.is_some() {
+            self.stop();
+        }
+    }
+}
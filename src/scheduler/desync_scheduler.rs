@@ -2,53 +2,109 @@
 
 use super::core::*;
 use super::job::*;
+use super::job_handle::*;
 use super::future_job::*;
 use super::unsafe_job::*;
 use super::scheduler_thread::*;
 use super::job_queue::*;
 use super::queue_state::*;
+use super::queue_overflow_policy::*;
+use super::queue_config::*;
 use super::active_queue::*;
 use super::scheduler_future::*;
 use super::queue_resumer::*;
+use super::suspension_guard::*;
+use super::join_queues::*;
+use super::queue_group::*;
+use super::job_limit::*;
+use super::scheduler_config::*;
+use super::scheduler_metrics::*;
 
 use std::fmt;
+use std::mem;
+use std::panic;
+use std::time::{Duration, Instant};
 use std::sync::*;
-use std::collections::vec_deque::*;
+use std::sync::atomic::{Ordering};
+use std::collections::HashMap;
 
+use futures::executor;
 use futures::channel::oneshot;
-use futures::future::{Future};
+use futures::future;
+use futures::future::{Future, FutureExt};
 
-#[cfg(not(target_arch = "wasm32"))]
-use num_cpus;
+/// How often `desync()` re-checks a full bounded queue (it's normally woken as soon as a job is removed)
+const SPACE_CHECK_INTERVAL: Duration = Duration::from_millis(10);
 
-#[cfg(not(target_arch = "wasm32"))]
-const MIN_THREADS: usize = 8;
+///
+/// The reasons that `sync_timeout()` can fail
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SyncTimeout {
+    /// The job did not complete within the time allowed
+    TimedOut,
 
-lazy_static! {
-    static ref SCHEDULER: Arc<Scheduler> = Arc::new(Scheduler::new());
+    /// The queue has panicked, so the job will never run
+    QueuePanicked,
+
+    /// The scheduler has shut down, so the job was not scheduled
+    ShutDown
+}
+
+///
+/// The reasons that `try_desync()` can fail
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TryDesyncError {
+    /// A bounded queue has no space for another job
+    QueueFull,
+
+    /// The scheduler has shut down, so the job was not scheduled
+    ShutDown
 }
 
 ///
-/// The default maximum number of threads in a scheduler 
+/// The reasons that `try_sync()` can fail
 ///
-#[cfg(not(target_arch = "wasm32"))]
-fn initial_max_threads() -> usize {
-    MIN_THREADS.max(num_cpus::get()*2)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TrySyncError {
+    /// There are jobs running or waiting on the queue (or it has panicked), so the job can't run straight away
+    QueueBusy,
+
+    /// The scheduler has shut down, so the job was not run
+    ShutDown
 }
 
 ///
-/// The default maximum number of threads in a scheduler 
+/// What to do when scheduling a job on a bounded queue that is full
 ///
-#[cfg(target_arch = "wasm32")]
-fn initial_max_threads() -> usize {
-    0
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub (super) enum WhenFull {
+    /// Add the job anyway
+    Schedule,
+
+    /// Wait for there to be space on the queue
+    Block,
+
+    /// Discard the job and return `TryDesyncError::QueueFull`
+    Fail
+}
+
+lazy_static! {
+    static ref SCHEDULER: RwLock<Arc<Scheduler>> = RwLock::new(Arc::new(Scheduler::new()));
+
+    /// Global schedulers that have been replaced (these are never freed, so references returned by `scheduler()` remain valid)
+    static ref RETIRED_SCHEDULERS: Mutex<Vec<Arc<Scheduler>>> = Mutex::new(vec![]);
 }
 
 ///
 /// The scheduler is used to schedule tasks onto a pool of threads
 ///
 pub struct Scheduler {
-    pub (super) core: Arc<SchedulerCore>
+    pub (super) core: Arc<SchedulerCore>,
+
+    /// The queue groups that have been registered with this scheduler
+    groups: Mutex<HashMap<GroupId, Arc<QueueGroup>>>
 }
 
 impl Scheduler {
@@ -58,14 +114,92 @@ impl Scheduler {
     /// (There's usually only one scheduler)
     /// 
     pub fn new() -> Scheduler {
-        let core = SchedulerCore { 
-            schedule:       Arc::new(Mutex::new(VecDeque::new())),
-            threads:        Mutex::new(vec![]),
-            max_threads:    Mutex::new(initial_max_threads())
+        Scheduler::from_core(Arc::new(SchedulerCore::new(SchedulerConfig::default())))
+    }
+
+    ///
+    /// Creates a new scheduler that runs its jobs using a spawner instead of creating its own threads
+    /// 
+    /// This makes it possible to use an existing thread pool to run the jobs for the scheduler. The
+    /// maximum number of threads still limits how many jobs can be sent to the spawner at once.
+    ///
+    pub fn new_with_spawner(spawner: Arc<dyn ThreadSpawner>) -> Scheduler {
+        let mut core    = SchedulerCore::new(SchedulerConfig::default());
+        core.spawner    = Some(spawner);
+
+        Scheduler::from_core(Arc::new(core))
+    }
+
+    ///
+    /// Creates a new scheduler that runs at most `max_jobs_in_flight` jobs at once across all of its queues
+    /// 
+    /// This is separate from the maximum number of threads: a queue can be assigned a thread but will then
+    /// wait for one of the running jobs to finish before it starts. This is useful for limiting the use
+    /// of resources such as file handles. Jobs run by `sync()` on the calling thread are not limited.
+    ///
+    pub fn new_work_limited(max_jobs_in_flight: usize) -> Scheduler {
+        assert!(max_jobs_in_flight > 0, "A work-limited scheduler must allow at least one job in flight");
+
+        let mut core    = SchedulerCore::new(SchedulerConfig::default());
+        core.job_limit  = Some(Arc::new(JobLimit::new(max_jobs_in_flight)));
+
+        Scheduler::from_core(Arc::new(core))
+    }
+
+    ///
+    /// Creates a new scheduler with the specified configuration
+    ///
+    pub fn new_with_config(config: SchedulerConfig) -> Scheduler {
+        Scheduler::from_core(Arc::new(SchedulerCore::new(config)))
+    }
+
+    ///
+    /// Sets whether or not a panic in a scheduler thread aborts the process
+    ///
+    /// A job that panics only stops its own queue, and the thread that was running it carries on. If a scheduler thread
+    /// panics anywhere else while running a queue, by default the thread recovers so that the scheduler doesn't lose
+    /// capacity, and the queue is rescheduled if it was left marked as running. Setting this to true aborts the process
+    /// in that case instead.
+    ///
+    pub fn with_panic_on_thread_death(self, enabled: bool) -> Scheduler {
+        self.core.panic_on_thread_death.store(enabled, Ordering::Relaxed);
+        self
+    }
+
+    ///
+    /// Replaces the global scheduler returned by `scheduler()`
+    ///
+    /// Objects created after this call (such as `Desync` objects and queues created by `queue()`) use the new
+    /// scheduler. Objects created earlier keep using the old one. Once the new scheduler has been installed, this
+    /// waits for the jobs that are in flight on the old scheduler to finish, then stops its threads and waits for
+    /// them to exit (objects that still use the old scheduler start new threads as they need them). This must not
+    /// be called from a job running on the global scheduler.
+    ///
+    /// This is mostly useful for tests, for example to install a scheduler with a single thread. As references
+    /// returned by `scheduler()` might still refer to it, the old scheduler itself is never freed.
+    ///
+    pub fn global_replace(new_scheduler: Scheduler) {
+        let old_scheduler = {
+            let mut global = SCHEDULER.write().expect("Global scheduler lock");
+            mem::replace(&mut *global, Arc::new(new_scheduler))
         };
 
+        RETIRED_SCHEDULERS.lock().expect("Retired schedulers lock").push(Arc::clone(&old_scheduler));
+
+        // Wait for the jobs in flight on the old scheduler, then stop its threads
+        executor::block_on(old_scheduler.drain());
+
+        let threads = mem::take(&mut *old_scheduler.core.threads.lock().expect("Scheduler threads lock"));
+        threads.into_iter().for_each(|(_, thread)| { thread.despawn_and_wait(None); });
+    }
+
+    ///
+    /// Creates a scheduler using an existing core (with no registered queue groups)
+    ///
+    pub (super) fn from_core(core: Arc<SchedulerCore>) -> Scheduler {
         Scheduler {
-            core: Arc::new(core)
+            core,
+            groups: Mutex::new(HashMap::new())
         }
     }
 
@@ -91,6 +225,26 @@ impl Scheduler {
         // Webassembly does not support threads so we run synchronously
     }
 
+    ///
+    /// Returns a snapshot of how busy this scheduler is
+    ///
+    pub fn metrics(&self) -> SchedulerMetrics {
+        let max_threads         = { *self.core.max_threads.lock().expect("Max threads lock") };
+        let (active_thread_count, thread_count) = {
+            let threads = self.core.threads.lock().expect("Scheduler threads lock");
+            let active  = threads.iter().filter(|(busy, _)| *busy.lock().expect("Thread busy lock")).count();
+
+            (active, threads.len())
+        };
+
+        SchedulerMetrics {
+            active_thread_count,
+            idle_thread_count:      thread_count.saturating_sub(active_thread_count),
+            pending_queue_count:    self.core.schedule.len(),
+            max_threads
+        }
+    }
+
     ///
     /// Despawns threads if we're running more than the maximum number
     /// 
@@ -105,7 +259,9 @@ impl Scheduler {
             let mut threads     = self.core.threads.lock().expect("Scheduler threads lock");
 
             while threads.len() > max_threads {
-                to_despawn.push(threads.pop().expect("Missing threads").1.despawn());
+                if let Some(join_handle) = threads.pop().expect("Missing threads").1.despawn() {
+                    to_despawn.push(join_handle);
+                }
             }
 
             to_despawn
@@ -133,9 +289,10 @@ impl Scheduler {
     /// Spawns a thread in this scheduler
     ///
     pub fn spawn_thread(&self) {
+        let mut threads = self.core.threads.lock().expect("Scheduler threads lock");
         let is_busy     = Arc::new(Mutex::new(false));
-        let new_thread  = SchedulerThread::new();
-        self.core.threads.lock().expect("Scheduler threads lock").push((is_busy, new_thread));
+        let new_thread  = self.core.create_thread(threads.len());
+        threads.push((is_busy, new_thread));
     }
 
     ///
@@ -146,6 +303,55 @@ impl Scheduler {
         new_queue
     }
 
+    ///
+    /// Creates a new job queue for this scheduler with the specified settings
+    ///
+    pub fn create_job_queue_with_config(&self, config: QueueConfig) -> Arc<JobQueue> {
+        JobQueue::new_with_config(config)
+    }
+
+    ///
+    /// Creates a new, empty, group of queues for this scheduler
+    ///
+    pub fn create_queue_group(&self) -> QueueGroup {
+        QueueGroup::new(Arc::clone(&self.core))
+    }
+
+    ///
+    /// Registers a queue group with this scheduler so that it's included in operations like `drain_queue_groups()`
+    ///
+    pub fn register_queue_group(&self, group: QueueGroup) -> GroupId {
+        let id = GroupId::new();
+        self.groups.lock().expect("Scheduler groups lock").insert(id, Arc::new(group));
+
+        id
+    }
+
+    ///
+    /// Retrieves a queue group that was previously registered with this scheduler
+    ///
+    pub fn queue_group(&self, id: GroupId) -> Option<Arc<QueueGroup>> {
+        self.groups.lock().expect("Scheduler groups lock").get(&id).cloned()
+    }
+
+    ///
+    /// Removes a queue group from this scheduler, returning it if it was registered
+    ///
+    pub fn unregister_queue_group(&self, id: GroupId) -> Option<Arc<QueueGroup>> {
+        self.groups.lock().expect("Scheduler groups lock").remove(&id)
+    }
+
+    ///
+    /// Returns a future that completes once all of the jobs currently scheduled on the queues in all the registered
+    /// queue groups have completed
+    ///
+    pub fn drain_queue_groups(&self) -> impl Future<Output=()>+Send {
+        let groups  = self.groups.lock().expect("Scheduler groups lock").values().cloned().collect::<Vec<_>>();
+        let drained = groups.iter().map(|group| group.drain_all()).collect::<Vec<_>>();
+
+        future::join_all(drained).map(|_| ())
+    }
+
     ///
     /// Schedules a job on this scheduler, which will run after any jobs that are already 
     /// in the specified queue and as soon as a thread is available to run it.
@@ -153,33 +359,238 @@ impl Scheduler {
     #[inline]
     #[deprecated(since="0.3.0", note="please use `desync` instead")]
     pub fn r#async<TFn: 'static+Send+FnOnce() -> ()>(&self, queue: &Arc<JobQueue>, job: TFn) {
-        self.desync(queue, job)
+        self.desync(queue, job);
     }
 
     ///
     /// Schedules a job on this scheduler, which will run after any jobs that are already 
     /// in the specified queue and as soon as a thread is available to run it.
     ///
-    pub fn desync<TFn: 'static+Send+FnOnce() -> ()>(&self, queue: &Arc<JobQueue>, job: TFn) {
-        self.schedule_job_desync(queue, Box::new(Job::new(job)));
+    /// The returned handle can be used to cancel the job if it has not started yet. If the queue was created
+    /// with `JobQueue::new_bounded()` and is full, this blocks until a job has been removed from it, so this
+    /// should not be called from a job running on the same queue. Queues created with `new_bounded_with_policy()`
+    /// can discard a job instead of blocking. If the new job is discarded (because the queue uses
+    /// `QueueOverflowPolicy::DropNewest`) or the scheduler has shut down, the handle's `is_rejected()` returns true.
+    ///
+    pub fn desync<TFn: 'static+Send+FnOnce()>(&self, queue: &Arc<JobQueue>, job: TFn) -> JobHandle {
+        let (job, handle) = Job::cancellable(job);
+
+        match self.schedule_jobs_when_full(queue, vec![Box::new(job)], WhenFull::Block) {
+            Ok(true)    => panic!("Cannot schedule jobs on a panicked {}", queue.description()),
+            Ok(false)   => { }
+            Err(_)      => handle.reject()
+        }
+
+        handle
+    }
+
+    ///
+    /// As for `desync()`, except that this returns `TryDesyncError::QueueFull` instead of blocking if a bounded queue
+    /// is full, and `TryDesyncError::ShutDown` if the scheduler has shut down
+    ///
+    pub fn try_desync<TFn: 'static+Send+FnOnce()>(&self, queue: &Arc<JobQueue>, job: TFn) -> Result<JobHandle, TryDesyncError> {
+        let (job, handle) = Job::cancellable(job);
+
+        if self.schedule_jobs_when_full(queue, vec![Box::new(job)], WhenFull::Fail)? {
+            panic!("Cannot schedule jobs on a panicked {}", queue.description());
+        }
+
+        Ok(handle)
     }
 
     ///
-    /// Schedules a job on this scheduler, which will run after any jobs that are already 
+    /// Returns a future that completes once the jobs that are currently scheduled on a queue have finished
+    ///
+    /// This schedules an empty job on the queue and waits for it to run, so jobs scheduled after this is called
+    /// are not waited for. The future also completes if the queue panics, as the jobs will then never run.
+    ///
+    pub fn drain_queue(&self, queue: &Arc<JobQueue>) -> impl 'static+Future<Output=()>+Send {
+        let (finished, wait_finished)   = oneshot::channel::<()>();
+        let panicked                    = self.desync_no_panic(queue, move || { finished.send(()).ok(); });
+
+        async move {
+            // The job is dropped without running if the queue panics, which cancels the channel
+            if !panicked {
+                wait_finished.await.ok();
+            }
+        }
+    }
+
+    ///
+    /// Returns a future that completes once the jobs that are currently scheduled on this scheduler have finished
+    ///
+    /// This waits for the queues that are waiting for a thread or running on one when this is called, as for
+    /// `drain_queue()`. A queue waiting for a future to wake it up is not included.
+    ///
+    pub fn drain(&self) -> impl 'static+Future<Output=()>+Send {
+        let queues = self.core.active_queues();
+        let drains = queues.iter().map(|queue| self.drain_queue(queue)).collect::<Vec<_>>();
+
+        future::join_all(drains).map(|_| ())
+    }
+
+    ///
+    /// Schedules a job to run once all of the queues that are currently waiting for a thread have started
+    ///
+    /// This creates a sentinel queue containing the job and adds it to the end of the schedule, so the
+    /// job runs after the work that is currently waiting, without blocking the caller. Multiple jobs
+    /// registered this way are started in the order they were registered. Queues that are already running
+    /// on another thread may not have finished by the time the job runs.
+    ///
+    pub fn run_sync_on_idle<TFn: 'static+Send+FnOnce()>(&self, job: TFn) {
+        let sentinel = self.create_job_queue();
+        self.desync(&sentinel, job);
+    }
+
+    ///
+    /// Makes sure that a queue with jobs waiting for a thread will start running
+    ///
+    /// If the queue is waiting in the schedule, this wakes or spawns a thread to run it. If no thread is available
+    /// (for example, because the maximum number of threads is 0, or all of the threads are busy), the jobs are run
+    /// on the current thread instead, in which case this blocks until they have completed.
+    ///
+    pub fn ensure_started(&self, queue: &Arc<JobQueue>) {
+        if queue.state() != QueueState::Pending {
+            return;
+        }
+
+        if !self.schedule_thread() {
+            // Scheduling an empty sync job drains everything ahead of it on this thread
+            self.sync(queue, || { });
+        }
+    }
+
+    ///
+    /// Schedules a job on this scheduler, which will run after any jobs that are already
     /// in the specified queue and as soon as a thread is available to run it.
     ///
     fn schedule_job_desync(&self, queue: &Arc<JobQueue>, job: Box<dyn ScheduledJob>) {
+        if self.schedule_jobs_desync(queue, vec![job]) {
+            panic!("Cannot schedule jobs on a panicked {}", queue.description());
+        }
+    }
+
+    ///
+    /// Schedules an asynchronous job on a queue. Returns false if the queue is not panicked, or true if it is (in which
+    /// case the job will never run), but otherwise behaves like desync()
+    ///
+    pub (crate) fn desync_no_panic<TFn: 'static+Send+FnOnce()>(&self, queue: &Arc<JobQueue>, job: TFn) -> bool {
+        self.schedule_jobs_desync(queue, vec![Box::new(Job::new(job))])
+    }
+
+    ///
+    /// Blocks until every job that is currently on a queue has finished. Returns false once they have, or true if the
+    /// queue panicked instead (in which case the job that panicked has finished unwinding)
+    ///
+    /// This waits for a sentinel job that can't be cancelled, so it's still scheduled if the scheduler is shutting down
+    /// and can't be removed by a forced shutdown or a full queue: if the sentinel is dropped, the queue has panicked.
+    /// If there is no thread available to run the queue, the jobs are run on the current thread, and a panic in one of
+    /// those is also reported by returning true.
+    ///
+    pub (crate) fn wait_for_jobs_no_panic(&self, queue: &Arc<JobQueue>) -> bool {
+        let (send_done, receive_done)   = mpsc::channel();
+        let sentinel                    = Box::new(Job::uncancellable(move || { send_done.send(()).ok(); }));
+
+        if self.push_jobs(queue, vec![sentinel], WhenFull::Schedule) != Ok(false) {
+            return true;
+        }
+
+        // A job that panics while draining on this thread marks the queue as panicked, which drops the sentinel
+        panic::catch_unwind(panic::AssertUnwindSafe(|| self.ensure_started(queue))).ok();
+
+        receive_done.recv().is_err()
+    }
+
+    ///
+    /// Schedules a set of jobs on this scheduler, which will run in order after any jobs that are already
+    /// in the specified queue. The jobs are added to the queue all at once, so no other jobs can be
+    /// scheduled in between them.
+    ///
+    pub fn desync_batch(&self, queue: &Arc<JobQueue>, jobs: Vec<Box<dyn FnOnce()+Send>>) {
+        let jobs = jobs.into_iter()
+            .map(|job| Box::new(Job::new(job)) as Box<dyn ScheduledJob>)
+            .collect();
+
+        if self.schedule_jobs_desync(queue, jobs) {
+            panic!("Cannot schedule jobs on a panicked {}", queue.description());
+        }
+    }
+
+    ///
+    /// Adds a set of jobs to the end of a queue while holding its lock, and schedules the queue if it was idle
+    ///
+    /// Returns true if the queue is panicked (in which case the jobs will never run)
+    ///
+    fn schedule_jobs_desync(&self, queue: &Arc<JobQueue>, jobs: Vec<Box<dyn ScheduledJob>>) -> bool {
+        // The queue is never full when its capacity is ignored (and the jobs are just dropped if the scheduler has shut down)
+        self.schedule_jobs_when_full(queue, jobs, WhenFull::Schedule).unwrap_or(false)
+    }
+
+    ///
+    /// As for `schedule_jobs_desync()`, but also specifying what to do if the queue is bounded and full
+    ///
+    fn schedule_jobs_when_full(&self, queue: &Arc<JobQueue>, jobs: Vec<Box<dyn ScheduledJob>>, when_full: WhenFull) -> Result<bool, TryDesyncError> {
+        // Jobs are discarded once the scheduler has shut down (futures waiting for them are cancelled)
+        if !self.core.accepting_jobs.load(Ordering::SeqCst) {
+            mem::drop(jobs);
+            return Err(TryDesyncError::ShutDown);
+        }
+
+        self.push_jobs(queue, jobs, when_full)
+    }
+
+    ///
+    /// Adds a set of jobs to a queue and schedules it if it was idle, whether or not the scheduler is accepting jobs
+    ///
+    /// This is used directly for the sentinel jobs that `shutdown()` uses to wait for the jobs in flight.
+    ///
+    pub (super) fn push_jobs(&self, queue: &Arc<JobQueue>, jobs: Vec<Box<dyn ScheduledJob>>, when_full: WhenFull) -> Result<bool, TryDesyncError> {
         enum ScheduleState {
             Idle,
             Running,
             Panicked
         }
 
+        // Jobs discarded to make room on a full queue or because the queue has panicked (these are dropped outside of the lock)
+        let mut discarded_jobs = vec![];
+
         let schedule_queue = {
             let mut core    = queue.core.lock().expect("JobQueue core lock");
 
-            // Push the job onto the queue
-            core.queue.push_back(job);
+            // Wait for space on a bounded queue (giving up if it panics, as it will never have space then)
+            while when_full != WhenFull::Schedule && core.is_full() && core.state != QueueState::Panicked {
+                match core.overflow_policy {
+                    QueueOverflowPolicy::Block      => { }
+
+                    QueueOverflowPolicy::DropOldest => {
+                        // Jobs that a thread is waiting for can't be discarded
+                        if let Some(oldest) = core.queue.iter().position(|job| job.can_cancel()) {
+                            discarded_jobs.extend(core.queue.remove(oldest));
+                            continue;
+                        }
+                    }
+
+                    QueueOverflowPolicy::DropNewest => {
+                        mem::drop(core);
+                        mem::drop(jobs);
+                        return Err(TryDesyncError::QueueFull);
+                    }
+                }
+
+                if when_full == WhenFull::Fail {
+                    // The jobs are dropped outside of the lock
+                    mem::drop(core);
+                    mem::drop(jobs);
+                    return Err(TryDesyncError::QueueFull);
+                }
+
+                // Jobs can also leave the queue without signalling (eg, when they're cancelled), so this re-checks periodically
+                core = queue.space_available.wait_timeout(core, SPACE_CHECK_INTERVAL).expect("JobQueue core lock").0;
+            }
+
+            // Push the jobs onto the queue
+            core.queue.extend(jobs);
+            core.job_scheduled();
 
             match core.state {
                 QueueState::Idle => {
@@ -188,7 +599,11 @@ impl Scheduler {
                     ScheduleState::Idle
                 },
 
-                QueueState::Panicked => ScheduleState::Panicked,
+                QueueState::Panicked => {
+                    // A panicked queue never runs its jobs
+                    discarded_jobs.extend(core.queue.drain(..));
+                    ScheduleState::Panicked
+                },
 
                 _=> {
                     // If the queue is in any other state, then we leave it alone
@@ -200,18 +615,13 @@ impl Scheduler {
         // If when we were queuing the jobs we found that the queue was idle, then move it to the pending list
         match schedule_queue {
             ScheduleState::Idle => {
-                // Add the queue to the schedule
-                self.core.schedule.lock().expect("Schedule lock").push_back(queue.clone());
-
-                // Wake up a thread to run it if we can
-                self.schedule_thread();
+                // Add the queue to the schedule, and wake up a thread to run it if we can
+                self.core.schedule_queue(Arc::clone(queue), Arc::clone(&self.core));
+                Ok(false)
             },
 
-            ScheduleState::Running => { }
-
-            ScheduleState::Panicked => {
-                panic!("Cannot schedule jobs on a panicked queue");
-            },
+            ScheduleState::Running  => Ok(false),
+            ScheduleState::Panicked => Ok(true)
         }
     }
 
@@ -244,6 +654,36 @@ impl Scheduler {
         receive
     }
 
+    ///
+    /// Schedules a job to run in the background and returns a future for retrieving the result
+    ///
+    /// Unlike `future()`, polling the returned future never runs the job on the polling thread,
+    /// so it's safe to combine with other futures (such as timeouts) without blocking them.
+    ///
+    pub fn future_desync<TFn, TFuture>(&self, queue: &Arc<JobQueue>, job: TFn) -> impl Future<Output=Result<TFuture::Output, oneshot::Canceled>>+Send
+    where   TFn:                'static+Send+FnOnce() -> TFuture,
+            TFuture:            'static+Send+Future,
+            TFuture::Output:    Send {
+        let (send, receive) = oneshot::channel();
+
+        let perform_job = FutureJob::new(move || {
+            // Create the job when we're queued up
+            let job = job();
+
+            async {
+                // Run the future and send the result to the channel
+                let val = job.await;
+                send.send(val).ok();
+            }
+        });
+
+        // Schedule the job
+        self.schedule_job_desync(queue, Box::new(perform_job));
+
+        // Receive channel will be notified when the job is completed
+        receive
+    }
+
     ///
     /// Pauses a queue until a particular future has completed, before performing a
     /// task with the result of that future
@@ -275,6 +715,9 @@ impl Scheduler {
     ///
     /// Requests that a queue be suspended once it has finished all of its active jobs
     ///
+    /// Every suspension must be paired with a call to `QueueResumer::resume()`: the queue stays suspended until the
+    /// resumer is used or dropped. `suspend_guard()` is usually easier to use correctly.
+    ///
     pub fn suspend(&self, queue: &Arc<JobQueue>) -> impl Future<Output=Result<QueueResumer, oneshot::Canceled>>+Send {
         let (finished_suspending, notify_finished_suspending) = SchedulerFuture::new(queue, Arc::clone(&self.core));
 
@@ -297,6 +740,19 @@ impl Scheduler {
         finished_suspending
     }
 
+    ///
+    /// Suspends a queue once it has finished all of its active jobs, returning a guard that resumes it when dropped
+    ///
+    /// The future completes once the queue has actually been suspended. Jobs scheduled while the guard exists are
+    /// queued and run after it has been dropped.
+    ///
+    pub fn suspend_guard(&self, queue: &Arc<JobQueue>) -> impl Future<Output=Result<SuspensionGuard, oneshot::Canceled>>+Send {
+        let queue = Arc::clone(queue);
+
+        self.suspend(&queue)
+            .map(move |resumer| resumer.map(move |resumer| SuspensionGuard::new(queue, resumer)))
+    }
+
     ///
     /// Runs a sync job immediately on the current thread. Queue must be in Running mode for this to be valid
     ///
@@ -310,7 +766,11 @@ impl Scheduler {
         let result = job();
 
         // Queue is now idle
-        queue.core.lock().expect("JobQueue core lock").state = QueueState::Idle;
+        {
+            let mut core = queue.core.lock().expect("JobQueue core lock");
+            core.job_completed();
+            core.state = QueueState::Idle;
+        }
 
         // Not running any more
         self.reschedule_queue(queue);
@@ -324,20 +784,12 @@ impl Scheduler {
     fn sync_drain<Result: Send, TFn: Send+FnOnce() -> Result>(&self, queue: &Arc<JobQueue>, job: TFn) -> Result {
         debug_assert!(queue.core.lock().expect("JobQueue core lock").state.is_running());
 
-        // Set the queue as active
-        let _active = ActiveQueue { queue: &*queue };
-
         // When the task runs on the queue, we'll put it here
         let result = Arc::new((Mutex::new(None), Condvar::new()));
 
         // Queue a job that'll run the requested job and then set the result
         // We'll unpark the thread in case we need to handle a suspension
-        let queue_result        = result.clone();
-        let result_job          = Box::new(Job::new(move || {
-            let job_result = job();
-            *queue_result.0.lock().expect("Sync queue result lock") = Some(job_result);
-            queue_result.1.notify_one();
-        }));
+        let result_job          = Self::result_job(&result, job);
 
         // Stuff on the queue normally has a 'static lifetime. When we're running
         // sync, the task will be done by the time this method is finished, so
@@ -345,6 +797,31 @@ impl Scheduler {
         let unsafe_result_job   = UnsafeJob::new(&*result_job);
         queue.core.lock().expect("JobQueue core lock").queue.push_back(Box::new(unsafe_result_job));
 
+        self.drain_until_result(queue, &result)
+    }
+
+    ///
+    /// Creates a job that runs a sync job and stores its result so that it can be retrieved by `drain_until_result()`
+    /// or `wait_for_result()`
+    ///
+    fn result_job<Result: Send, TFn: Send+FnOnce() -> Result>(result: &Arc<(Mutex<Option<Result>>, Condvar)>, job: TFn) -> Box<impl ScheduledJob> {
+        let queue_result = Arc::clone(result);
+
+        Box::new(Job::new(move || {
+            let job_result = job();
+            *queue_result.0.lock().expect("Sync queue result lock") = Some(job_result);
+            queue_result.1.notify_one();
+        }))
+    }
+
+    ///
+    /// With a queue in the running state, runs jobs on the current thread until a job created by `result_job()` has
+    /// produced a result
+    ///
+    fn drain_until_result<Result: Send>(&self, queue: &Arc<JobQueue>, result: &(Mutex<Option<Result>>, Condvar)) -> Result {
+        // Set the queue as active
+        let _active = ActiveQueue { queue };
+
         // While there is no result, run a job from the queue
         while result.0.lock().expect("Sync queue result lock").is_none() {
             match JobQueue::run_one_job_now(queue) {
@@ -373,19 +850,9 @@ impl Scheduler {
     fn sync_background<Result: Send, TFn: Send+FnOnce() -> Result>(&self, queue: &Arc<JobQueue>, job: TFn) -> Result {
         // Queue a job that unparks this thread when done
         let pair    = Arc::new((Mutex::new(None), Condvar::new()));
-        let pair2   = pair.clone();
 
         // Safe job that signals the condvar when needed
-        let job     = Box::new(Job::new(move || {
-            let &(ref result, ref cvar) = &*pair2;
-
-            // Run the job
-            let actual_result = job();
-
-            // Set the result and notify the waiting thread
-            *result.lock().expect("Background job result lock") = Some(actual_result);
-            cvar.notify_one();
-        }));
+        let job     = Self::result_job(&pair, job);
         
         // Unsafe job with unbounded lifetime is needed because stuff on the queue normally needs a static lifetime
         let need_reschedule = {
@@ -399,7 +866,14 @@ impl Scheduler {
         if need_reschedule { self.reschedule_queue(queue); }
 
         // Wait for the result to arrive (and the sweet relief of no more unsafe job)
-        let &(ref lock, ref cvar) = &*pair;
+        Self::wait_for_result(&pair)
+    }
+
+    ///
+    /// Waits for a job created by `result_job()` that is running in the background to produce a result
+    ///
+    fn wait_for_result<Result: Send>(pair: &(Mutex<Option<Result>>, Condvar)) -> Result {
+        let (lock, cvar) = pair;
         let mut result = lock.lock().expect("Background job result lock");
         
         while result.is_none() {
@@ -433,6 +907,7 @@ impl Scheduler {
         // If the queue is idle when this is called, we need to schedule this task on this thread rather than one owned by the background process
         let run_action = {
             let mut core = queue.core.lock().expect("JobQueue core lock");
+            core.job_scheduled();
 
             match core.state {
                 QueueState::Running             => RunAction::WaitForBackground,
@@ -450,10 +925,206 @@ impl Scheduler {
             RunAction::Immediate            => self.sync_immediate(queue, job),
             RunAction::DrainOnThisThread    => self.sync_drain(queue, job),
             RunAction::WaitForBackground    => self.sync_background(queue, job),
-            RunAction::Panic                => panic!("Cannot schedule new jobs on a panicked queue")
+            RunAction::Panic                => panic!("Cannot schedule new jobs on a panicked {}", queue.description())
+        }
+    }
+
+    ///
+    /// As for `sync()`, except that this gives up waiting for the result if the job has not completed within `timeout`
+    ///
+    /// If the queue is idle, the job runs immediately on the current thread. Otherwise it's scheduled in the same way
+    /// as a `desync()` job and this waits for the result. A job that times out is not cancelled: it will still run once
+    /// the jobs ahead of it have finished, which is why it must have a `'static` lifetime. This returns
+    /// `SyncTimeout::QueuePanicked` instead of panicking if the queue has panicked, or if the job itself panics,
+    /// and `SyncTimeout::ShutDown` without running the job if the scheduler has shut down.
+    ///
+    pub fn sync_timeout<Result, TFn>(&self, queue: &Arc<JobQueue>, timeout: Duration, job: TFn) -> std::result::Result<Result, SyncTimeout>
+    where   Result: 'static+Send,
+            TFn:    'static+Send+FnOnce() -> Result {
+        let deadline = Instant::now() + timeout;
+
+        if self.is_shutting_down() {
+            return Err(SyncTimeout::ShutDown);
+        }
+
+        // Run immediately if the queue is idle
+        let run_immediately = {
+            let mut core = queue.core.lock().expect("JobQueue core lock");
+
+            match core.state {
+                QueueState::Panicked    => { return Err(SyncTimeout::QueuePanicked); },
+                QueueState::Idle        => { core.job_scheduled(); core.state = QueueState::Running; true },
+                _                       => false
+            }
+        };
+
+        if run_immediately {
+            // A panicking job leaves the queue in the panicked state
+            return panic::catch_unwind(panic::AssertUnwindSafe(|| self.sync_immediate(queue, job))).map_err(|_| SyncTimeout::QueuePanicked);
+        }
+
+        // The result is sent back via a channel so we can stop waiting for it
+        let (send_result, receive_result) = mpsc::channel();
+
+        if self.desync_no_panic(queue, move || { send_result.send(job()).ok(); }) {
+            return Err(SyncTimeout::QueuePanicked);
+        }
+
+        // The job is dropped without running if the queue panics or the scheduler shuts down, which disconnects the channel
+        match receive_result.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(result)                                  => Ok(result),
+            Err(mpsc::RecvTimeoutError::Disconnected)   => if self.is_shutting_down() && !queue.is_panicked() { Err(SyncTimeout::ShutDown) } else { Err(SyncTimeout::QueuePanicked) },
+            Err(mpsc::RecvTimeoutError::Timeout)        => Err(SyncTimeout::TimedOut)
+        }
+    }
+
+    ///
+    /// Runs a job immediately on the current thread if the queue is idle, or returns `TrySyncError::QueueBusy` without
+    /// scheduling it if not
+    ///
+    /// This never waits: if there are jobs running or waiting on the queue, or the queue has panicked, the job is
+    /// discarded. It's also discarded if the scheduler has shut down, in which case `TrySyncError::ShutDown` is returned.
+    ///
+    pub fn try_sync<Result, TFn: FnOnce() -> Result>(&self, queue: &Arc<JobQueue>, job: TFn) -> std::result::Result<Result, TrySyncError> {
+        if self.is_shutting_down() {
+            return Err(TrySyncError::ShutDown);
+        }
+
+        // The queue must move to the running state while it's still locked, so no other job can start in between
+        let is_idle = {
+            let mut core = queue.core.lock().expect("JobQueue core lock");
+
+            if core.state == QueueState::Idle {
+                core.job_scheduled();
+                core.state = QueueState::Running;
+                true
+            } else {
+                false
+            }
+        };
+
+        if is_idle {
+            Ok(self.sync_immediate(queue, job))
+        } else {
+            Err(TrySyncError::QueueBusy)
+        }
+    }
+
+    ///
+    /// Calls a function while holding the lock for a queue, if the queue is idle, or returns `None` if it isn't
+    ///
+    /// The queue's state isn't changed, so this is only suitable for functions that don't modify the data the queue
+    /// protects. No jobs can be scheduled on the queue until the function returns, so it must not try to.
+    ///
+    pub (crate) fn run_while_idle<Result, TFn: FnOnce() -> Result>(&self, queue: &Arc<JobQueue>, job: TFn) -> Option<Result> {
+        let result = {
+            let core = queue.core.lock().expect("JobQueue core lock");
+
+            if core.state != QueueState::Idle {
+                return None;
+            }
+
+            // A panic must not poison the queue's lock, so it's caught here and resumed once the lock is released
+            panic::catch_unwind(panic::AssertUnwindSafe(job))
+        };
+
+        match result {
+            Ok(result)  => Some(result),
+            Err(panic)  => panic::resume_unwind(panic)
         }
     }
 
+    ///
+    /// Removes the pending jobs from a queue and then runs a job synchronously on it
+    ///
+    /// If `cancel_pending` is true, the pending jobs are dropped, so any futures waiting for them will
+    /// return `Canceled`. Otherwise, the pending jobs are put back on the queue once the job has completed
+    /// and will run before anything that was scheduled in the meantime. Jobs that cannot be cancelled
+    /// (synchronous jobs that another thread is waiting for and futures that have already started) are
+    /// left on the queue and will run before this job. The pending jobs are removed and the job is added
+    /// to the queue in a single step, so a job scheduled by another thread at the same time is either
+    /// removed along with the other pending jobs or runs after this one.
+    ///
+    pub fn run_exclusive<Result: Send, TFn: Send+FnOnce() -> Result>(&self, queue: &Arc<JobQueue>, job: TFn, cancel_pending: bool) -> Result {
+        // The pending jobs are restored from the queue itself so they run ahead of anything scheduled while the job was running
+        let paused          = Arc::new(Mutex::new(vec![]));
+        let restore_jobs    = Arc::clone(&paused);
+        let restore_queue   = Arc::clone(queue);
+
+        let result          = Arc::new((Mutex::new(None), Condvar::new()));
+        let exclusive_job   = Self::result_job(&result, move || {
+            let result  = job();
+            let paused  = mem::take(&mut *restore_jobs.lock().expect("Paused jobs lock"));
+            restore_queue.core.lock().expect("JobQueue core lock").restore_jobs(paused);
+
+            result
+        });
+
+        // The pending jobs are removed and the job is scheduled in the same critical section, so nothing can be scheduled in between them
+        let (pending, drain_here) = {
+            let mut core = queue.core.lock().expect("JobQueue core lock");
+
+            if core.state == QueueState::Panicked {
+                let description = core.description();
+                mem::drop(core);
+                panic!("Cannot schedule new jobs on a panicked {}", description);
+            }
+
+            let pending = core.take_cancellable_jobs();
+
+            // Stuff on the queue normally has a 'static lifetime, but this waits for the job to finish before returning
+            core.queue.push_back(Box::new(UnsafeJob::new(&*exclusive_job)));
+            core.job_scheduled();
+
+            let drain_here = match core.state {
+                QueueState::Idle | QueueState::Pending  => { core.state = QueueState::Running; true },
+                _                                       => false
+            };
+
+            // Jobs that are cancelled are dropped outside of the lock as they might try to wake futures
+            if cancel_pending {
+                (pending, drain_here)
+            } else {
+                *paused.lock().expect("Paused jobs lock") = pending;
+                (vec![], drain_here)
+            }
+        };
+
+        mem::drop(pending);
+
+        if drain_here {
+            self.drain_until_result(queue, &result)
+        } else {
+            Self::wait_for_result(&result)
+        }
+    }
+
+    ///
+    /// Returns a panicked queue to the idle state so that jobs can be scheduled on it again
+    ///
+    /// The jobs that were waiting when the queue panicked are discarded without running, as whatever scheduled them
+    /// will already have been told that the queue panicked. Returns false if the queue had not panicked, in which
+    /// case it is left alone.
+    ///
+    pub fn reset_panicked(&self, queue: &Arc<JobQueue>) -> bool {
+        let discarded = {
+            let mut core = queue.core.lock().expect("JobQueue core lock");
+
+            if core.state != QueueState::Panicked {
+                return false;
+            }
+
+            core.state = QueueState::Idle;
+            core.queue.drain(..).collect::<Vec<_>>()
+        };
+
+        // Drop the old jobs outside of the lock, and let any threads waiting for space know they can continue
+        mem::drop(discarded);
+        queue.space_available.notify_all();
+
+        true
+    }
+
     ///
     /// Schedules a synchronous event to the queue. Returns false if the queue is not panicked, or true if it is,
     /// but otherwise behaves like sync()
@@ -506,9 +1177,13 @@ impl fmt::Debug for Scheduler {
 
             busyness
         };
-        let queue_size = format!("Pending queue count: {}", self.core.schedule.lock().expect("Schedule lock").len());
+        let queue_size  = format!("Pending queue count: {}", self.core.schedule.len());
+
+        let running     = self.core.active_queues().into_iter()
+            .filter(|queue| queue.state().is_running())
+            .map(|queue| format!("{:?}", queue)).collect::<Vec<_>>().join(", ");
 
-        fmt.write_str(&format!("{} {}", threads, queue_size))
+        fmt.write_str(&format!("{} {} Running: [{}]", threads, queue_size, running))
     }
 }
 
@@ -516,7 +1191,17 @@ impl fmt::Debug for Scheduler {
 /// Retrieves the global scheduler
 ///
 pub fn scheduler<'a>() -> &'a Scheduler {
-    &SCHEDULER
+    let scheduler = SCHEDULER.read().expect("Global scheduler lock");
+
+    // Global schedulers are never freed (they're moved to RETIRED_SCHEDULERS when replaced), so the reference stays valid
+    unsafe { &*Arc::as_ptr(&*scheduler) }
+}
+
+///
+/// Retrieves a reference to the global scheduler that can be stored
+///
+pub (crate) fn shared_scheduler() -> Arc<Scheduler> {
+    Arc::clone(&*SCHEDULER.read().expect("Global scheduler lock"))
 }
 
 ///
@@ -532,13 +1217,13 @@ pub fn queue() -> Arc<JobQueue> {
 #[inline]
 #[deprecated(since="0.3.0", note="please use `desync` instead")]
 pub fn r#async<TFn: 'static+Send+FnOnce() -> ()>(queue: &Arc<JobQueue>, job: TFn) {
-    desync(queue, job)
+    desync(queue, job);
 }
 
 ///
 /// Performs an action asynchronously on the specified queue
 ///
-pub fn desync<TFn: 'static+Send+FnOnce() -> ()>(queue: &Arc<JobQueue>, job: TFn) {
+pub fn desync<TFn: 'static+Send+FnOnce()>(queue: &Arc<JobQueue>, job: TFn) -> JobHandle {
     scheduler().desync(queue, job)
 }
 
@@ -558,3 +1243,11 @@ where   TFn:                'static+Send+FnOnce() -> TFuture,
 pub fn sync<Result: Send, TFn: Send+FnOnce() -> Result>(queue: &Arc<JobQueue>, job: TFn) -> Result {
     scheduler().sync(queue, job)
 }
+
+///
+/// Returns a future that calls a function once all of the jobs currently scheduled on a set of queues have finished
+///
+pub fn join_queues<TFn, TResult>(queues: &[Arc<JobQueue>], action: TFn) -> JoinQueuesFuture<TFn>
+where TFn: Unpin+FnOnce() -> TResult {
+    scheduler().join_queues(queues, action)
+}
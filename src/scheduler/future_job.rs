@@ -71,4 +71,9 @@ where   TFn:        FnOnce() -> TFuture+Send,
             panic!("Cannot schedule an action twice");
         }
     }
+
+    fn can_cancel(&self) -> bool {
+        // Once the future has started it's in progress (it's only on the queue because it's waiting to be woken)
+        matches!(self.action, JobState::FutureNotCreated(_))
+    }
 }
@@ -1,3 +1,5 @@
+use super::job_handle::*;
+
 use futures::task::{Context, Poll};
 
 ///
@@ -6,19 +8,50 @@ use futures::task::{Context, Poll};
 pub trait ScheduledJob : Send {
     /// Runs this particular job
     fn run(&mut self, context: &mut Context) -> Poll<()>;
+
+    /// True if this job can be removed from its queue without running (false for jobs that a thread is waiting on)
+    fn can_cancel(&self) -> bool { true }
+
+    /// True if this job has been cancelled using its `JobHandle` (cancelled jobs are skipped instead of being run)
+    fn is_cancelled(&self) -> bool { false }
 }
 
 ///
 /// Basic job is just a FnOnce
 ///
 pub struct Job<TFn> {
-    action: Option<TFn>
+    action: Option<TFn>,
+
+    /// If this job has a `JobHandle`, the state used to cancel it
+    cancellation: Option<JobCancellation>,
+
+    /// False if this job must not be removed from its queue without running (it's still dropped if the queue panics)
+    can_cancel: bool
 }
 
 impl<TFn> Job<TFn> 
 where TFn: Send+FnOnce() -> () {
     pub fn new(action: TFn) -> Job<TFn> {
-        Job { action: Some(action) }
+        Job { action: Some(action), cancellation: None, can_cancel: true }
+    }
+
+    ///
+    /// Creates a job that is only dropped without running if its queue panics
+    ///
+    /// This is used for sentinel jobs that signal when the jobs ahead of them have finished, where removing the job
+    /// (eg, when a scheduler is shut down or a bounded queue overflows) would look the same as all of those jobs finishing.
+    ///
+    pub fn uncancellable(action: TFn) -> Job<TFn> {
+        Job { action: Some(action), cancellation: None, can_cancel: false }
+    }
+
+    ///
+    /// Creates a job along with a handle that can be used to cancel it
+    ///
+    pub fn cancellable(action: TFn) -> (Job<TFn>, JobHandle) {
+        let (handle, cancellation) = JobHandle::new();
+
+        (Job { action: Some(action), cancellation: Some(cancellation), can_cancel: true }, handle)
     }
 }
 
@@ -28,6 +61,13 @@ where TFn: Send+FnOnce() -> () {
         // Consume the action when it's run
         let action = self.action.take();
 
+        // A job that was cancelled after it was dequeued is skipped here
+        if let Some(cancellation) = &self.cancellation {
+            if !cancellation.start() {
+                return Poll::Ready(());
+            }
+        }
+
         if let Some(action) = action {
             action();
             Poll::Ready(())
@@ -35,4 +75,12 @@ where TFn: Send+FnOnce() -> () {
             panic!("Cannot schedule an action twice");
         }
     }
+
+    fn can_cancel(&self) -> bool {
+        self.can_cancel
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancellation.as_ref().map(|cancellation| cancellation.is_cancelled()).unwrap_or(false)
+    }
 }
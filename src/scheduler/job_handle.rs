@@ -0,0 +1,118 @@
+use std::sync::*;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use futures::channel::oneshot;
+use futures::future::{Future, FutureExt, Shared};
+
+/// The job is waiting on its queue
+const JOB_WAITING: u8   = 0;
+
+/// The job has started running (or has finished)
+const JOB_STARTED: u8   = 1;
+
+/// The job was cancelled before it started, and will be skipped
+const JOB_CANCELLED: u8 = 2;
+
+/// The job was never scheduled, because its scheduler had shut down or its queue was full
+const JOB_REJECTED: u8  = 3;
+
+///
+/// Handle returned when scheduling a job with `desync()`, which can be used to cancel the job before it starts
+///
+/// Dropping the handle does not cancel the job.
+///
+pub struct JobHandle {
+    /// The state of the job (one of the `JOB_` constants)
+    state: Arc<AtomicU8>,
+
+    /// Resolves once the job has left its queue, either because it ran or because it was skipped
+    finished: Shared<oneshot::Receiver<()>>
+}
+
+///
+/// The part of a `JobHandle` that's owned by the job itself
+///
+pub (super) struct JobCancellation {
+    /// The state of the job (shared with the handle)
+    state: Arc<AtomicU8>,
+
+    /// Dropped along with the job, which signals the handle's `finished` receiver
+    _finished: oneshot::Sender<()>
+}
+
+impl JobHandle {
+    ///
+    /// Creates a new handle, and the cancellation state for the job it belongs to
+    ///
+    pub (super) fn new() -> (JobHandle, JobCancellation) {
+        let state               = Arc::new(AtomicU8::new(JOB_WAITING));
+        let (send, receive)     = oneshot::channel();
+
+        let handle              = JobHandle { state: Arc::clone(&state), finished: receive.shared() };
+        let cancellation        = JobCancellation { state, _finished: send };
+
+        (handle, cancellation)
+    }
+
+    ///
+    /// Stops the job from running if it has not already started
+    ///
+    /// Returns true if the job had already started (in which case it can no longer be cancelled), or false if it
+    /// will be skipped when it reaches the front of its queue.
+    ///
+    pub fn cancel(&self) -> bool {
+        match self.state.compare_exchange(JOB_WAITING, JOB_CANCELLED, Ordering::SeqCst, Ordering::SeqCst) {
+            Ok(_)                   => false,
+            Err(JOB_CANCELLED)      => false,
+            Err(JOB_REJECTED)       => false,
+            Err(_)                  => true
+        }
+    }
+
+    ///
+    /// True if the job was discarded instead of being scheduled, because its scheduler had shut down or because it
+    /// was scheduled on a full queue using `QueueOverflowPolicy::DropNewest`
+    ///
+    pub fn is_rejected(&self) -> bool {
+        self.state.load(Ordering::SeqCst) == JOB_REJECTED
+    }
+
+    ///
+    /// Marks the job as rejected by its scheduler (the job itself should already have been dropped)
+    ///
+    pub (super) fn reject(&self) {
+        self.state.store(JOB_REJECTED, Ordering::SeqCst);
+    }
+
+    ///
+    /// Cancels the job, returning a future that completes once the job has left its queue
+    ///
+    /// The future returns the same value as `cancel()`. If the job had already started, the future completes when
+    /// it has finished running, otherwise it completes when the cancelled job has been skipped.
+    ///
+    pub fn cancel_and_wait(&self) -> impl 'static+Future<Output=bool>+Send {
+        let started     = self.cancel();
+        let finished    = self.finished.clone();
+
+        async move {
+            finished.await.ok();
+            started
+        }
+    }
+}
+
+impl JobCancellation {
+    ///
+    /// Marks the job as started, returning false if it has been cancelled and should be skipped instead
+    ///
+    pub (super) fn start(&self) -> bool {
+        self.state.compare_exchange(JOB_WAITING, JOB_STARTED, Ordering::SeqCst, Ordering::SeqCst).is_ok()
+    }
+
+    ///
+    /// True if the job has been cancelled
+    ///
+    pub (super) fn is_cancelled(&self) -> bool {
+        self.state.load(Ordering::SeqCst) == JOB_CANCELLED
+    }
+}
@@ -0,0 +1,55 @@
+use std::sync::*;
+
+///
+/// Limits the number of jobs that a scheduler can run at once
+///
+/// This works like a semaphore: a permit must be acquired before a job can run on a scheduler
+/// thread, and is released when the job finishes (or returns to wait for a future).
+///
+pub (super) struct JobLimit {
+    /// The number of permits that are currently available
+    available: Mutex<usize>,
+
+    /// Signalled when a permit is released
+    released: Condvar
+}
+
+///
+/// A permit to run jobs, which is returned to the limit when dropped
+///
+pub (super) struct JobPermit<'a> {
+    limit: &'a JobLimit
+}
+
+impl JobLimit {
+    ///
+    /// Creates a new job limit that allows the specified number of jobs to run at once
+    ///
+    pub (super) fn new(max_jobs_in_flight: usize) -> JobLimit {
+        JobLimit {
+            available:  Mutex::new(max_jobs_in_flight),
+            released:   Condvar::new()
+        }
+    }
+
+    ///
+    /// Waits for a permit to become available and takes it
+    ///
+    pub (super) fn acquire(&self) -> JobPermit<'_> {
+        let mut available = self.available.lock().expect("Job limit lock");
+
+        while *available == 0 {
+            available = self.released.wait(available).expect("Job limit wait");
+        }
+
+        *available -= 1;
+        JobPermit { limit: self }
+    }
+}
+
+impl<'a> Drop for JobPermit<'a> {
+    fn drop(&mut self) {
+        *self.limit.available.lock().expect("Job limit lock") += 1;
+        self.limit.released.notify_one();
+    }
+}
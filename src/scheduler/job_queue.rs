@@ -1,14 +1,23 @@
 
 use super::job::*;
 use super::active_queue::*;
+use super::dedicated_thread::*;
 use super::queue_state::*;
+use super::queue_overflow_policy::*;
+use super::queue_config::*;
 use super::wake_thread::*;
+use super::job_limit::*;
 
 use std::fmt;
+use std::mem;
 use std::sync::*;
 use std::thread;
+use std::time::{Instant};
 use std::collections::vec_deque::*;
 
+use futures::{FutureExt};
+use futures::future::{Future};
+use futures::channel::oneshot;
 use futures::task;
 use futures::task::{Context, Poll};
 
@@ -17,7 +26,10 @@ use futures::task::{Context, Poll};
 /// 
 pub struct JobQueue {
     /// The shared data for this queue is stored within a mutex
-    pub (super) core: Mutex<JobQueueCore>
+    pub (super) core: Mutex<JobQueueCore>,
+
+    /// Signalled when a job is removed from a bounded queue (or the queue panics), so a thread waiting for space can continue
+    pub (super) space_available: Condvar
 }
 
 ///
@@ -34,19 +46,130 @@ pub (super) enum JobStatus {
 ///
 /// Structure protected by the jobqueue matrix
 ///
+/// The jobs and the state share a single lock because they have to change together: a queue that has jobs added
+/// while it's idle must move to pending before anything else can see the jobs, and a thread that finds the queue
+/// empty must move it back to idle before anything else can add a job. Cancelling jobs and putting them back (see
+/// `take_cancellable_jobs()` and `restore_jobs()`) also need to work on the whole list of jobs at once.
+///
 pub (super) struct JobQueueCore {
     /// The jobs that are scheduled on this queue
     pub (super) queue: VecDeque<Box<dyn ScheduledJob>>,
 
     /// The current state of this queue
     pub (super) state: QueueState,
+
+    /// When the most recent job on this queue finished running
+    pub (super) last_job_completed_at: Option<Instant>,
+
+    /// When the most recent job was scheduled on this queue
+    pub (super) last_job_scheduled_at: Option<Instant>,
+
+    /// The name of this queue, if it has one (used in panic messages and debug output)
+    pub (super) name: Option<String>,
+
+    /// The maximum number of jobs that can be waiting on this queue before `desync()` blocks (None if the queue is unbounded)
+    pub (super) capacity: Option<usize>,
+
+    /// What happens when a job is scheduled with `desync()` while this queue is full
+    pub (super) overflow_policy: QueueOverflowPolicy,
+
+    /// If set, the thread started by `spawn_dedicated_thread()` that runs this queue instead of the shared threads
+    pub (super) dedicated_thread: Option<Arc<DedicatedThreadSignal>>,
+
+    /// Signalled the next time this queue is idle with no jobs waiting (registered by `on_idle()`)
+    pub (super) idle_notifiers: Vec<oneshot::Sender<()>>,
+
+    /// The ID of the scheduler's `QueueRegistry` that this queue was last added to (0 if it has never been scheduled)
+    pub (super) registered_with: usize
+}
+
+impl JobQueueCore {
+    ///
+    /// Removes the jobs that can be cancelled from this queue and returns them
+    /// 
+    /// The jobs should be dropped after the queue has been unlocked, as dropping some jobs
+    /// can wake futures that will try to lock the queue.
+    ///
+    pub (super) fn take_cancellable_jobs(&mut self) -> Vec<Box<dyn ScheduledJob>> {
+        let (cancelled, remaining): (VecDeque<_>, VecDeque<_>) = self.queue.drain(..).partition(|job| job.can_cancel());
+        self.queue = remaining;
+
+        cancelled.into_iter().collect()
+    }
+
+    ///
+    /// Puts a set of jobs (usually previously removed by `take_cancellable_jobs`) at the front of this queue
+    ///
+    pub (super) fn restore_jobs(&mut self, jobs: Vec<Box<dyn ScheduledJob>>) {
+        for job in jobs.into_iter().rev() {
+            self.queue.push_front(job);
+        }
+    }
+
+    ///
+    /// Records that a job on this queue has finished running
+    ///
+    pub (super) fn job_completed(&mut self) {
+        self.last_job_completed_at = Some(Instant::now());
+    }
+
+    ///
+    /// Records that a new job has been scheduled on this queue
+    ///
+    pub (super) fn job_scheduled(&mut self) {
+        self.last_job_scheduled_at = Some(Instant::now());
+    }
+
+    ///
+    /// True if this is a bounded queue with as many jobs waiting as it can hold
+    ///
+    pub (super) fn is_full(&self) -> bool {
+        match self.capacity {
+            Some(capacity)  => self.queue.len() >= capacity,
+            None            => false
+        }
+    }
+
+    ///
+    /// If this queue is idle with no jobs waiting, removes the notifiers registered by `on_idle()` so they can be signalled
+    ///
+    /// The notifiers should be signalled with `signal_idle()` after the queue has been unlocked, as signalling them can
+    /// wake futures that will try to lock the queue.
+    ///
+    pub (super) fn take_idle_notifiers(&mut self) -> Vec<oneshot::Sender<()>> {
+        if self.state == QueueState::Idle && self.queue.is_empty() {
+            mem::take(&mut self.idle_notifiers)
+        } else {
+            vec![]
+        }
+    }
+
+    ///
+    /// Describes this queue for use in messages ("queue", or "queue 'name'" for a named queue)
+    ///
+    pub (super) fn description(&self) -> String {
+        match &self.name {
+            Some(name)  => format!("queue '{}'", name),
+            None        => "queue".to_string()
+        }
+    }
+}
+
+///
+/// Signals a set of notifiers returned by `take_idle_notifiers()`
+///
+pub (super) fn signal_idle(idle_notifiers: Vec<oneshot::Sender<()>>) {
+    idle_notifiers.into_iter().for_each(|notifier| { notifier.send(()).ok(); });
 }
 
 impl fmt::Debug for JobQueue {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         let core = self.core.lock().expect("JobQueue core lock");
 
-        fmt.write_str(&format!("JobQueue: State: {:?}, Pending: {}", core.state, core.queue.len()))
+        match &core.name {
+            Some(name)  => fmt.write_str(&format!("JobQueue '{}': State: {:?}, Pending: {}", name, core.state, core.queue.len())),
+            None        => fmt.write_str(&format!("JobQueue: State: {:?}, Pending: {}", core.state, core.queue.len()))
+        }
     }
 }
 
@@ -57,28 +180,198 @@ impl JobQueue {
     pub (super) fn new() -> JobQueue {
         JobQueue { 
             core: Mutex::new(JobQueueCore {
-                queue:              VecDeque::new(),
-                state:              QueueState::Idle
-            })
+                queue:                  VecDeque::new(),
+                state:                  QueueState::Idle,
+                last_job_completed_at:  None,
+                last_job_scheduled_at:  None,
+                name:                   None,
+                capacity:               None,
+                overflow_policy:        QueueOverflowPolicy::Block,
+                dedicated_thread:       None,
+                idle_notifiers:         vec![],
+                registered_with:        0
+            }),
+            space_available: Condvar::new()
         }
     }
 
+    ///
+    /// Creates a new job queue that can hold at most `capacity` waiting jobs
+    ///
+    /// Once the queue is full, `desync()` blocks until a job has been removed from the queue to run, and `try_desync()`
+    /// returns `TryDesyncError::QueueFull`. The job that is currently running does not count towards the capacity, which
+    /// must be at least 1. Other ways of scheduling jobs (such as `sync()` and `future()`) are not limited.
+    ///
+    pub fn new_bounded(capacity: usize) -> Arc<JobQueue> {
+        JobQueue::new_bounded_with_policy(capacity, QueueOverflowPolicy::Block)
+    }
+
+    ///
+    /// Creates a new job queue that can hold at most `capacity` waiting jobs, and which deals with a job scheduled by
+    /// `desync()` while it's full according to `policy`
+    ///
+    pub fn new_bounded_with_policy(capacity: usize, policy: QueueOverflowPolicy) -> Arc<JobQueue> {
+        JobQueue::new_with_config(QueueConfig { capacity: Some(capacity), overflow_policy: policy, ..QueueConfig::default() })
+    }
+
+    ///
+    /// Creates a new job queue with a name, which is shown in its debug output and in the message if it panics
+    ///
+    pub fn with_name(name: impl Into<String>) -> Arc<JobQueue> {
+        JobQueue::new_with_config(QueueConfig { name: Some(name.into()), ..QueueConfig::default() })
+    }
+
+    ///
+    /// Creates a new job queue with the specified settings
+    ///
+    pub fn new_with_config(config: QueueConfig) -> Arc<JobQueue> {
+        if let Some(capacity) = config.capacity {
+            assert!(capacity > 0, "Bounded queues must have a capacity of at least 1");
+        }
+
+        let queue = JobQueue::new();
+
+        {
+            let mut core            = queue.core.lock().expect("JobQueue core lock");
+            core.name               = config.name;
+            core.capacity           = config.capacity;
+            core.overflow_policy    = config.overflow_policy;
+        }
+
+        Arc::new(queue)
+    }
+
+    ///
+    /// The name of this queue, if it was created with `with_name()`
+    ///
+    pub fn name(&self) -> Option<String> {
+        self.core.lock().expect("JobQueue core lock").name.clone()
+    }
+
+    ///
+    /// Describes this queue for use in messages ("queue", or "queue 'name'" for a named queue)
+    ///
+    pub (super) fn description(&self) -> String {
+        self.core.lock().expect("JobQueue core lock").description()
+    }
+
+    ///
+    /// True if a job running on this queue has panicked (no further jobs will run on a panicked queue)
+    ///
+    pub fn is_panicked(&self) -> bool {
+        self.core.lock().expect("JobQueue core lock").state == QueueState::Panicked
+    }
+
+    ///
+    /// Retrieves the current state of this queue
+    ///
+    pub fn state(&self) -> QueueState {
+        self.core.lock().expect("JobQueue core lock").state
+    }
+
+    ///
+    /// Returns the number of jobs that are waiting to run on this queue
+    ///
+    pub fn pending_jobs(&self) -> usize {
+        self.core.lock().expect("JobQueue core lock").queue.len()
+    }
+
+    ///
+    /// True if this is a bounded queue with as many jobs waiting as it can hold
+    ///
+    pub fn is_full(&self) -> bool {
+        self.core.lock().expect("JobQueue core lock").is_full()
+    }
+
+    ///
+    /// True if this queue is idle and has no jobs waiting to run
+    ///
+    pub fn is_empty(&self) -> bool {
+        let core = self.core.lock().expect("JobQueue core lock");
+        core.state == QueueState::Idle && core.queue.is_empty()
+    }
+
+    ///
+    /// Returns a future that completes the next time this queue is idle with no jobs waiting to run
+    ///
+    /// The future completes immediately if the queue is already idle. It also completes if the queue panics, as it
+    /// will never become idle after that.
+    ///
+    pub fn on_idle(&self) -> impl 'static+Future<Output=()>+Send {
+        let (notifier, idle) = oneshot::channel();
+
+        let notify_now = {
+            let mut core = self.core.lock().expect("JobQueue core lock");
+
+            if (core.state == QueueState::Idle && core.queue.is_empty()) || core.state == QueueState::Panicked {
+                Some(notifier)
+            } else {
+                core.idle_notifiers.push(notifier);
+                None
+            }
+        };
+
+        if let Some(notifier) = notify_now {
+            signal_idle(vec![notifier]);
+        }
+
+        idle.map(|_| ())
+    }
+
+    ///
+    /// Returns the time that the most recent job on this queue finished running, if any job has finished
+    ///
+    pub fn last_job_completed_at(&self) -> Option<Instant> {
+        self.core.lock().expect("JobQueue core lock").last_job_completed_at
+    }
+
+    ///
+    /// Returns the time that a job was most recently scheduled on this queue, if any job has been scheduled
+    ///
+    pub fn last_job_scheduled_at(&self) -> Option<Instant> {
+        self.core.lock().expect("JobQueue core lock").last_job_scheduled_at
+    }
+
     ///
     /// If there are any jobs waiting, dequeues the next one
     ///
+    /// Jobs that have been cancelled are skipped.
+    ///
     pub (super) fn dequeue(&self) -> Option<Box<dyn ScheduledJob>> {
-        let mut core = self.core.lock().expect("JobQueue core lock");
+        let mut skipped = vec![];
 
-        match core.state {
-            QueueState::WaitingForWake      => None,
-            QueueState::WaitingForPoll(_)   => None,
-            QueueState::WaitingForUnpark    => None,
+        let next_job = {
+            let mut core = self.core.lock().expect("JobQueue core lock");
 
-            other                           => {
-                debug_assert!(other.is_running(), "State is {:?}", core.state);
-                core.queue.pop_front()
+            match core.state {
+                QueueState::WaitingForWake      => None,
+                QueueState::WaitingForPoll(_)   => None,
+                QueueState::WaitingForUnpark    => None,
+
+                other                           => {
+                    debug_assert!(other.is_running(), "State is {:?}", core.state);
+
+                    let next_job = loop {
+                        match core.queue.pop_front() {
+                            Some(job) if job.is_cancelled() => skipped.push(job),
+                            next_job                        => break next_job
+                        }
+                    };
+
+                    // Removing a job makes space on a bounded queue
+                    if core.capacity.is_some() {
+                        self.space_available.notify_all();
+                    }
+
+                    next_job
+                }
             }
-        }
+        };
+
+        // Skipped jobs are dropped outside of the lock, as this wakes anything waiting for them to be cancelled
+        mem::drop(skipped);
+
+        next_job
     }
 
     ///
@@ -93,7 +386,10 @@ impl JobQueue {
     ///
     /// Runs jobs on this queue until there are none left, marking the job as inactive when done
     /// 
-    pub (super) fn drain(&self, context: &mut Context) {
+    /// If there's a job limit, a permit is held while each job is running (but not in between jobs), so
+    /// a long queue doesn't stop other queues from running, and a job can wait for another queue.
+    /// 
+    pub (super) fn drain(&self, context: &mut Context, job_limit: Option<&JobLimit>) {
         let _active = ActiveQueue { queue: self };
 
         debug_assert!(self.core.lock().unwrap().state.is_running());
@@ -104,10 +400,13 @@ impl JobQueue {
             while let Some(mut job) = self.dequeue() {
                 debug_assert!(self.core.lock().unwrap().state.is_running());
 
-                let poll_result = job.run(context);
+                let poll_result = {
+                    let _permit = job_limit.map(|job_limit| job_limit.acquire());
+                    job.run(context)
+                };
 
                 match poll_result {
-                    Poll::Ready(()) => { },
+                    Poll::Ready(()) => { self.core.lock().expect("JobQueue core lock").job_completed(); },
                     Poll::Pending   => { 
                         // Job needs requeing
                         self.requeue(job);
@@ -129,7 +428,7 @@ impl JobQueue {
             }
 
             // Try to move back to the 'not running' state
-            {
+            let idle_notifiers = {
                 let mut core = self.core.lock().expect("JobQueue core lock");
                 debug_assert!(core.state.is_running());
 
@@ -144,7 +443,11 @@ impl JobQueue {
                     // Will restart when we get re-scheduled
                     done = true;
                 }
-            }
+
+                core.take_idle_notifiers()
+            };
+
+            signal_idle(idle_notifiers);
         }
     }
 
@@ -201,7 +504,10 @@ impl JobQueue {
             }
 
             // Queue should still be running once we resume
-            debug_assert!(queue.core.lock().unwrap().state.is_running());
+            let mut core = queue.core.lock().expect("JobQueue core lock");
+            debug_assert!(core.state.is_running());
+            core.job_completed();
+
             JobStatus::Finished
         } else {
             JobStatus::NoJobsWaiting
@@ -0,0 +1,95 @@
+use super::job_queue::*;
+use super::desync_scheduler::*;
+
+use std::pin::{Pin};
+use std::sync::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use futures::future::{Future};
+use futures::task::{Context, Poll, Waker};
+
+///
+/// The state shared between a `JoinQueuesFuture` and the sentinel jobs on its queues
+///
+struct JoinState {
+    /// The number of sentinel jobs that have not run yet
+    remaining: AtomicUsize,
+
+    /// The waker for the future, once it has been polled
+    waker: Mutex<Option<Waker>>
+}
+
+///
+/// Future returned by `join_queues()`, which calls a function once a set of queues have finished their jobs
+///
+pub struct JoinQueuesFuture<TFn> {
+    /// The state shared with the sentinel jobs
+    state: Arc<JoinState>,
+
+    /// The function to call once all of the queues have caught up (None once it has been called)
+    action: Option<TFn>
+}
+
+impl Scheduler {
+    ///
+    /// Returns a future that calls a function once all of the jobs currently scheduled on a set of queues have finished
+    ///
+    /// This schedules a sentinel job on each queue, and the function is called when the future is polled after the last
+    /// of these has run. The queues carry on running in parallel, so this is a barrier across them rather than a way to
+    /// stop them: jobs scheduled after this is called may run before the function does. A queue that has already
+    /// panicked is treated as finished, but a queue that panics after this is called will stop the future from completing.
+    ///
+    pub fn join_queues<TFn, TResult>(&self, queues: &[Arc<JobQueue>], action: TFn) -> JoinQueuesFuture<TFn>
+    where TFn: Unpin+FnOnce() -> TResult {
+        let state = Arc::new(JoinState {
+            remaining:  AtomicUsize::new(queues.len()),
+            waker:      Mutex::new(None)
+        });
+
+        for queue in queues.iter() {
+            let sentinel_state  = Arc::clone(&state);
+            let panicked        = self.desync_no_panic(queue, move || sentinel_state.sentinel_finished());
+
+            if panicked {
+                state.sentinel_finished();
+            }
+        }
+
+        JoinQueuesFuture {
+            state,
+            action: Some(action)
+        }
+    }
+}
+
+impl JoinState {
+    ///
+    /// Records that one of the sentinel jobs has run, waking the future if it was the last one
+    ///
+    fn sentinel_finished(&self) {
+        if self.remaining.fetch_sub(1, Ordering::SeqCst) == 1 {
+            let waker = self.waker.lock().expect("Join waker lock").take();
+
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl<TFn, TResult> Future for JoinQueuesFuture<TFn>
+where TFn: Unpin+FnOnce() -> TResult {
+    type Output = TResult;
+
+    fn poll(mut self: Pin<&mut Self>, context: &mut Context) -> Poll<TResult> {
+        // Store the waker before checking the count, so a sentinel that finishes in between will wake us
+        *self.state.waker.lock().expect("Join waker lock") = Some(context.waker().clone());
+
+        if self.state.remaining.load(Ordering::SeqCst) == 0 {
+            let action = self.action.take().expect("JoinQueuesFuture polled after it completed");
+            Poll::Ready(action())
+        } else {
+            Poll::Pending
+        }
+    }
+}
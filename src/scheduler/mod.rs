@@ -48,17 +48,43 @@
 mod desync_scheduler;
 mod core;
 mod job;
+mod job_handle;
 mod future_job;
 mod unsafe_job;
 mod scheduler_thread;
 mod job_queue;
 mod queue_state;
+mod queue_overflow_policy;
+mod queue_config;
+mod queue_registry;
 mod active_queue;
 mod wake_queue;
 mod wake_thread;
 mod scheduler_future;
 mod queue_resumer;
+mod suspension_guard;
+mod queue_group;
+mod job_limit;
+mod scheduler_config;
+mod scheduler_metrics;
+mod shutdown;
+mod join_queues;
+mod select_queue;
+mod autoscale;
+mod dedicated_thread;
 
 pub use self::desync_scheduler::*;
 pub use self::job_queue::{JobQueue};
+pub use self::job_handle::{JobHandle};
+pub use self::queue_state::{QueueState, FutureId};
+pub use self::queue_overflow_policy::{QueueOverflowPolicy};
+pub use self::queue_config::{QueueConfig};
 pub use self::queue_resumer::{QueueResumer};
+pub use self::suspension_guard::{SuspensionGuard};
+pub use self::scheduler_thread::{ThreadSpawner};
+pub use self::queue_group::{QueueGroup, GroupId};
+pub use self::scheduler_config::{SchedulerConfig};
+pub use self::scheduler_metrics::{SchedulerMetrics};
+pub use self::shutdown::{ShutdownResult, ShutdownFuture, ShutdownTimeout};
+pub use self::join_queues::{JoinQueuesFuture};
+pub use self::dedicated_thread::{DedicatedThreadHandle};
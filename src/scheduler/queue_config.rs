@@ -0,0 +1,29 @@
+use super::queue_overflow_policy::*;
+
+///
+/// Settings used when creating a new job queue with `JobQueue::new_with_config()` or `Desync::new_with_config()`
+///
+/// The settings can be combined, so (for example) a queue can be both named and bounded:
+///
+/// ```
+/// # use desync::scheduler::*;
+/// let queue = JobQueue::new_with_config(QueueConfig {
+///     name:       Some("Writer".to_string()),
+///     capacity:   Some(16),
+///     ..QueueConfig::default()
+/// });
+///
+/// assert!(queue.name() == Some("Writer".to_string()));
+/// ```
+///
+#[derive(Clone, Debug, Default)]
+pub struct QueueConfig {
+    /// The name shown in the debug output for the queue, and in the panic message if a job is scheduled after it has panicked
+    pub name: Option<String>,
+
+    /// The maximum number of jobs that can be waiting on the queue, or `None` for an unbounded queue (must be at least 1)
+    pub capacity: Option<usize>,
+
+    /// What happens when a job is scheduled with `desync()` while the queue is full (ignored if `capacity` is `None`)
+    pub overflow_policy: QueueOverflowPolicy
+}
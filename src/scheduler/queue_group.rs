@@ -0,0 +1,161 @@
+use super::core::*;
+use super::job::*;
+use super::job_queue::*;
+use super::queue_resumer::*;
+use super::desync_scheduler::*;
+
+use std::sync::*;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use futures::future;
+use futures::future::{Future, FutureExt};
+
+lazy_static! {
+    static ref NEXT_GROUP_ID: AtomicU64 = AtomicU64::new(0);
+}
+
+///
+/// Identifies a queue group that has been registered with a scheduler
+///
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct GroupId(u64);
+
+impl GroupId {
+    ///
+    /// Creates a new unique group ID
+    ///
+    pub (super) fn new() -> GroupId {
+        GroupId(NEXT_GROUP_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+///
+/// A queue group is a set of related queues that can be suspended, resumed, drained or cancelled together
+///
+/// This is useful for a subsystem that uses several queues internally, where it's necessary to
+/// be able to manage them all at once (for example, to pause a component or to wait for it to
+/// finish all of its pending work).
+///
+pub struct QueueGroup {
+    /// The scheduler that the queues in this group belong to
+    core: Arc<SchedulerCore>,
+
+    /// The queues in this group
+    queues: Mutex<Vec<Arc<JobQueue>>>,
+
+    /// The resumers for the queues that were suspended by `suspend_all()`
+    resumers: Arc<Mutex<Vec<QueueResumer>>>
+}
+
+impl QueueGroup {
+    ///
+    /// Creates a new, empty, queue group
+    ///
+    pub (super) fn new(core: Arc<SchedulerCore>) -> QueueGroup {
+        QueueGroup {
+            core,
+            queues:     Mutex::new(vec![]),
+            resumers:   Arc::new(Mutex::new(vec![]))
+        }
+    }
+
+    ///
+    /// Creates a scheduler that can be used to schedule work on the queues in this group
+    ///
+    fn scheduler(&self) -> Scheduler {
+        Scheduler::from_core(Arc::clone(&self.core))
+    }
+
+    ///
+    /// Adds a queue to this group
+    ///
+    pub fn add_queue(&self, queue: &Arc<JobQueue>) {
+        let mut queues = self.queues.lock().expect("Queue group lock");
+
+        if !queues.iter().any(|existing| Arc::ptr_eq(existing, queue)) {
+            queues.push(Arc::clone(queue));
+        }
+    }
+
+    ///
+    /// Creates a new job queue and adds it to this group
+    ///
+    pub fn create_job_queue(&self) -> Arc<JobQueue> {
+        let queue = self.scheduler().create_job_queue();
+        self.add_queue(&queue);
+
+        queue
+    }
+
+    ///
+    /// Retrieves the queues in this group, sorted into a canonical order
+    ///
+    pub fn queues(&self) -> Vec<Arc<JobQueue>> {
+        let mut queues = self.queues.lock().expect("Queue group lock").clone();
+        queues.sort_by_key(|queue| Arc::as_ptr(queue) as usize);
+
+        queues
+    }
+
+    ///
+    /// Suspends all of the queues in this group, returning a future that completes once they have all
+    /// finished the jobs that were scheduled before they were suspended
+    ///
+    pub fn suspend_all(&self) -> impl Future<Output=()>+Send {
+        let scheduler   = self.scheduler();
+        let resumers    = Arc::clone(&self.resumers);
+        let suspended   = self.queues().iter()
+            .map(|queue| scheduler.suspend(queue))
+            .collect::<Vec<_>>();
+
+        future::join_all(suspended).map(move |suspended| {
+            let mut resumers = resumers.lock().expect("Queue group resumers lock");
+            resumers.extend(suspended.into_iter().filter_map(|resumer| resumer.ok()));
+        })
+    }
+
+    ///
+    /// Resumes any queues that were suspended by `suspend_all()`
+    ///
+    pub fn resume_all(&self) {
+        let resumers = { self.resumers.lock().expect("Queue group resumers lock").drain(..).collect::<Vec<_>>() };
+        resumers.into_iter().for_each(|resumer| resumer.resume());
+    }
+
+    ///
+    /// Returns a future that completes once all of the jobs currently scheduled on the queues in this group
+    /// have completed
+    ///
+    pub fn drain_all(&self) -> impl Future<Output=()>+Send {
+        let scheduler   = self.scheduler();
+        let drained     = self.queues().iter()
+            .map(|queue| scheduler.future(queue, || future::ready(())))
+            .collect::<Vec<_>>();
+
+        future::join_all(drained).map(|_| ())
+    }
+
+    ///
+    /// Removes any jobs that have not started yet from all of the queues in this group
+    ///
+    /// Any futures waiting for the jobs will return `Canceled`. Jobs that are already running and
+    /// synchronous jobs (which have a thread waiting for them) are not cancelled.
+    ///
+    pub fn cancel_all(&self) {
+        let queues = self.queues();
+
+        let cancelled = {
+            // Lock all the queues in order so the cancellation happens at the same time for all of them
+            let mut cores = queues.iter()
+                .map(|queue| queue.core.lock().expect("JobQueue core lock"))
+                .collect::<Vec<_>>();
+
+            cores.iter_mut()
+                .flat_map(|core| core.take_cancellable_jobs())
+                .collect::<Vec<Box<dyn ScheduledJob>>>()
+        };
+
+        // Drop the cancelled jobs outside of the lock
+        std::mem::drop(cancelled);
+    }
+}
@@ -0,0 +1,18 @@
+///
+/// What `desync()` does when it's called on a bounded queue that already has as many jobs waiting as it can hold
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum QueueOverflowPolicy {
+    /// Wait for a job to be removed from the queue before adding the new one (`try_desync()` returns `TryDesyncError::QueueFull`)
+    #[default]
+    Block,
+
+    /// Discard the oldest job that is waiting on the queue to make room for the new one
+    ///
+    /// Only jobs scheduled by `desync()` are discarded: if the queue is full of other kinds of job (such as those
+    /// waiting for `sync()` or `future()`), this waits for space as for `Block`.
+    DropOldest,
+
+    /// Discard the new job (`try_desync()` returns `TryDesyncError::QueueFull`, and `desync()` returns a rejected `JobHandle`)
+    DropNewest
+}
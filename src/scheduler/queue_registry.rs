@@ -0,0 +1,77 @@
+use super::job_queue::*;
+
+use std::sync::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+lazy_static! {
+    static ref NEXT_REGISTRY_ID: AtomicUsize = AtomicUsize::new(1);
+}
+
+/// The number of registered queues at which freed queues are first removed from a registry
+const REGISTRY_SWEEP_SIZE: usize = 64;
+
+///
+/// The queues that have been scheduled on a scheduler, used to find the ones that are in flight without having to
+/// read the schedule
+///
+/// A queue is added the first time it's scheduled (it remembers the registry it was last added to), so this isn't
+/// locked while the scheduler is running jobs.
+///
+pub (super) struct QueueRegistry {
+    /// Identifies this registry (0 is never used, so it can mean that a queue has not been registered anywhere)
+    id: usize,
+
+    /// The registered queues, and the number that were left the last time the freed ones were removed
+    queues: Mutex<(Vec<Weak<JobQueue>>, usize)>
+}
+
+impl QueueRegistry {
+    ///
+    /// Creates a new, empty registry
+    ///
+    pub (super) fn new() -> QueueRegistry {
+        QueueRegistry {
+            id:     NEXT_REGISTRY_ID.fetch_add(1, Ordering::Relaxed),
+            queues: Mutex::new((vec![], 0))
+        }
+    }
+
+    ///
+    /// Adds a queue to this registry if it's not already there
+    ///
+    /// `registered_with` is the queue's record of the registry it was last added to, and must be locked by the caller.
+    /// A queue that moves between schedulers can end up being added more than once, so `queues()` removes duplicates.
+    ///
+    pub (super) fn register(&self, queue: &Arc<JobQueue>, registered_with: &mut usize) {
+        if *registered_with == self.id {
+            return;
+        }
+
+        *registered_with    = self.id;
+        let mut queues      = self.queues.lock().expect("Queue registry lock");
+        let (queues, swept) = &mut *queues;
+
+        // Stop tracking freed queues once the list has doubled in size since they were last removed
+        if queues.len() >= REGISTRY_SWEEP_SIZE.max(*swept * 2) {
+            queues.retain(|queue| queue.strong_count() > 0);
+            *swept = queues.len();
+        }
+
+        queues.push(Arc::downgrade(queue));
+    }
+
+    ///
+    /// Returns the registered queues that have not been freed
+    ///
+    pub (super) fn queues(&self) -> Vec<Arc<JobQueue>> {
+        let mut queues = self.queues.lock().expect("Queue registry lock").0
+            .iter()
+            .filter_map(|queue| queue.upgrade())
+            .collect::<Vec<_>>();
+
+        queues.sort_by_key(|queue| Arc::as_ptr(queue) as usize);
+        queues.dedup_by(|a, b| Arc::ptr_eq(a, b));
+
+        queues
+    }
+}
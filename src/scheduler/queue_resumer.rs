@@ -3,6 +3,10 @@ use futures::channel::oneshot;
 ///
 /// The queue resumer is used to resume a queue that was suspended using the `suspend()` function in the scheduler
 ///
+/// Each resumer belongs to a single call to `suspend()`, so it can only resume the queue that was suspended by that
+/// call. It's consumed when it's used, so a suspension can't be resumed twice. Dropping a resumer without using it
+/// also resumes the queue.
+///
 pub struct QueueResumer {
     pub (super) resume: oneshot::Sender<()>
 }
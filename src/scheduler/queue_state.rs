@@ -8,13 +8,13 @@ lazy_static! {
 /// ID of a future used in a state
 ///
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
-pub (super) struct FutureId(pub u64);
+pub struct FutureId(pub u64);
 
 impl FutureId {
     ///
     /// Creates a new unique future ID
     ///
-    pub fn new() -> FutureId {
+    pub (crate) fn new() -> FutureId {
         let next_id = NEXT_FUTURE_ID.fetch_add(1, Ordering::Relaxed);
 
         FutureId(next_id)
@@ -25,7 +25,7 @@ impl FutureId {
 /// Represents the state of a job queue
 ///
 #[derive(PartialEq, Debug, Clone, Copy)]
-pub (super) enum QueueState {
+pub enum QueueState {
     /// Queue is currently not running and not ready to run
     /// 
     /// The queue has this state when it has no jobs in it.
@@ -78,7 +78,7 @@ pub (super) enum QueueState {
     /// We capture this event by setting the queue's status to 'AwokenWhileRunning'.
     AwokenWhileRunning,
 
-    /// Queue received a panic and is no longer able to be scheduled
+    /// Queue received a panic and is no longer able to be scheduled (any jobs that were waiting on it are dropped)
     Panicked
 }
 
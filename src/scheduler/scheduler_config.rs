@@ -0,0 +1,20 @@
+///
+/// Settings used when creating a new scheduler with `Scheduler::new_with_config()`
+///
+#[derive(Default)]
+pub struct SchedulerConfig {
+    /// Generates the name for each thread the scheduler spawns from its index (0, 1, 2, ...). Threads are named `desync-worker-<index>` if this is `None`
+    pub thread_name_fn: Option<Box<dyn Fn(usize) -> String+Send+Sync>>
+}
+
+impl SchedulerConfig {
+    ///
+    /// Returns the name of the thread with the specified index
+    ///
+    pub (super) fn thread_name(&self, index: usize) -> String {
+        match &self.thread_name_fn {
+            Some(thread_name_fn)    => thread_name_fn(index),
+            None                    => format!("desync-worker-{}", index)
+        }
+    }
+}
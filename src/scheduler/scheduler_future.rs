@@ -397,7 +397,7 @@ impl<T> Future for SchedulerFuture<T> {
             SchedulerAction::WaitForCompletion  => task::Poll::Pending,
             SchedulerAction::ReturnValue(value) => task::Poll::Ready(value),
             SchedulerAction::DrainQueue         => self.drain_queue(context),
-            SchedulerAction::Panic              => panic!("Cannot schedule jobs on a panicked queue"),
+            SchedulerAction::Panic              => panic!("Cannot schedule jobs on a panicked {}", self.queue.description()),
         }
     }
 }
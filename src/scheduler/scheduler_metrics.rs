@@ -0,0 +1,20 @@
+///
+/// A snapshot of the state of a scheduler, returned by `Scheduler::metrics()`
+///
+/// The values are read one after the other while the scheduler is running, so they may not be exactly
+/// consistent with each other.
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SchedulerMetrics {
+    /// The number of scheduler threads that are currently running a queue
+    pub active_thread_count: usize,
+
+    /// The number of scheduler threads that are waiting for a queue to run
+    pub idle_thread_count: usize,
+
+    /// The number of queues that are waiting for a thread to become available
+    pub pending_queue_count: usize,
+
+    /// The maximum number of threads that the scheduler can spawn
+    pub max_threads: usize
+}
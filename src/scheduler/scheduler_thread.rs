@@ -1,5 +1,8 @@
+use std::mem;
 use std::thread;
+use std::sync::*;
 use std::sync::mpsc::*;
+use std::time::{Instant};
 
 ///
 /// Creates a FnMut that runs a FnOnce once (or panics)
@@ -7,7 +10,7 @@ use std::sync::mpsc::*;
 /// The scheduler only runs jobs once so it accepts them as FnOnce values, but it's currently not possible to
 /// box FnOnce values such that they can be run, so we wrap them in FnMut values that panic if called more
 /// than once.
-/// 
+///
 /// (Nightly rust has FnBox to get around this)
 ///
 fn wrap_fnonce<TFn: FnOnce() -> ()>(job: TFn) -> impl FnMut() -> () {
@@ -24,35 +27,109 @@ fn wrap_fnonce<TFn: FnOnce() -> ()>(job: TFn) -> impl FnMut() -> () {
     }
 }
 
+///
+/// Trait implemented by objects that can run work on behalf of a scheduler
+///
+/// This can be used to make a scheduler use an existing thread pool rather than spawning its
+/// own threads. Each call to `spawn` is a request to run a function that will process jobs for
+/// a while and then return. This is implemented for any function that accepts a boxed job, so
+/// a thread pool can usually be adapted like this:
+///
+/// ```
+/// # use desync::scheduler::*;
+/// # use std::sync::*;
+/// # use std::thread;
+/// let scheduler = Scheduler::new_with_spawner(Arc::new(|job: Box<dyn FnOnce()+Send>| { thread::spawn(job); }));
+/// # let queue = scheduler.create_job_queue();
+/// # assert!(scheduler.sync(&queue, || 42) == 42);
+/// ```
+///
+pub trait ThreadSpawner : Send+Sync {
+    /// Runs the specified function on a thread belonging to this spawner
+    fn spawn(&self, job: Box<dyn FnOnce()+Send>);
+}
+
+impl<TFn> ThreadSpawner for TFn
+where TFn: Send+Sync+Fn(Box<dyn FnOnce()+Send>) {
+    fn spawn(&self, job: Box<dyn FnOnce()+Send>) {
+        (self)(job)
+    }
+}
+
+///
+/// A job sent to a dedicated scheduler thread
+///
+type ThreadJob = Box<dyn FnMut()+Send>;
+
+///
+/// Where a scheduler thread runs its jobs
+///
+enum ThreadTarget {
+    /// A dedicated thread, which runs the jobs sent to its channel, and a flag that's set when it exits
+    Dedicated(Sender<ThreadJob>, thread::JoinHandle<()>, Arc<ThreadExited>),
+
+    /// Each job is sent to a spawner
+    Spawner(Arc<dyn ThreadSpawner>)
+}
+
+///
+/// Set when a dedicated thread stops running
+///
+struct ThreadExited {
+    exited:         Mutex<bool>,
+    signal_exited:  Condvar
+}
+
+///
+/// Signals that a dedicated thread has exited when dropped (which also happens if the thread panics)
+///
+struct SignalExitOnDrop(Arc<ThreadExited>);
+
+impl Drop for SignalExitOnDrop {
+    fn drop(&mut self) {
+        *self.0.exited.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = true;
+        self.0.signal_exited.notify_all();
+    }
+}
+
 ///
 /// A scheduler thread reads from the scheduler queue
 ///
 pub struct SchedulerThread {
-    /// The jobs that this thread should run
-    jobs: Sender<Box<dyn FnMut() -> ()+Send>>,
-
-    /// The thread itself
-    thread: thread::JoinHandle<()>,
+    /// Where the jobs for this thread are run
+    target: ThreadTarget
 }
 
 impl SchedulerThread {
     ///
-    /// Creates a new scheduler thread 
+    /// Creates a new scheduler thread with the specified name
     ///
-    pub fn new() -> SchedulerThread {
+    pub fn new(name: String) -> SchedulerThread {
         // All the thread does is run jobs from its channel
-        let (jobs_in, jobs_out): (Sender<Box<dyn FnMut() -> ()+Send>>, Receiver<Box<dyn FnMut() -> ()+Send>>) = channel();
-        let thread = thread::Builder::new()
-            .name("desync jobs thread".to_string())
+        let (jobs_in, jobs_out): (Sender<ThreadJob>, Receiver<ThreadJob>) = channel();
+        let exited          = Arc::new(ThreadExited { exited: Mutex::new(false), signal_exited: Condvar::new() });
+        let signal_exit     = SignalExitOnDrop(Arc::clone(&exited));
+        let thread          = thread::Builder::new()
+            .name(name)
             .spawn(move || {
+                let _signal_exit = signal_exit;
+
                 while let Ok(mut job) = jobs_out.recv() {
                     (*job)();
                 }
             }).unwrap();
 
         SchedulerThread {
-            jobs:   jobs_in,
-            thread: thread
+            target: ThreadTarget::Dedicated(jobs_in, thread, exited)
+        }
+    }
+
+    ///
+    /// Creates a scheduler thread that runs its jobs using a spawner
+    ///
+    pub fn with_spawner(spawner: Arc<dyn ThreadSpawner>) -> SchedulerThread {
+        SchedulerThread {
+            target: ThreadTarget::Spawner(spawner)
         }
     }
 
@@ -60,13 +137,54 @@ impl SchedulerThread {
     /// Schedules a job to be run on this thread
     ///
     pub fn run<Job: 'static+FnOnce() -> ()+Send>(&self, job: Job) {
-        self.jobs.send(Box::new(wrap_fnonce(job))).unwrap();
+        match &self.target {
+            ThreadTarget::Dedicated(jobs, _thread, _exited) => jobs.send(Box::new(wrap_fnonce(job))).unwrap(),
+            ThreadTarget::Spawner(spawner)                  => spawner.spawn(Box::new(job))
+        }
+    }
+
+    ///
+    /// De-spawns this thread and returns the join handle (if it's running on a dedicated thread)
+    ///
+    pub fn despawn(self) -> Option<thread::JoinHandle<()>> {
+        match self.target {
+            ThreadTarget::Dedicated(_jobs, thread, _exited) => Some(thread),
+            ThreadTarget::Spawner(_spawner)                 => None
+        }
     }
 
     ///
-    /// De-spawns this thread and returns the join handle 
+    /// De-spawns this thread and waits for it to exit, giving up at `deadline` (if there is one)
     ///
-    pub fn despawn(self) -> thread::JoinHandle<()> {
-        self.thread
+    /// Returns false if the thread was still running at the deadline. Threads that run using a spawner can't be
+    /// waited for, so this returns true for them straight away.
+    ///
+    pub fn despawn_and_wait(self, deadline: Option<Instant>) -> bool {
+        match self.target {
+            ThreadTarget::Spawner(_spawner)                 => true,
+            ThreadTarget::Dedicated(jobs, thread, exited)   => {
+                // The thread stops once its channel is closed and it has finished its current job
+                mem::drop(jobs);
+
+                let mut has_exited = exited.exited.lock().expect("Thread exited lock");
+                while !*has_exited {
+                    match deadline {
+                        None            => { has_exited = exited.signal_exited.wait(has_exited).expect("Thread exited lock"); }
+                        Some(deadline)  => {
+                            let now = Instant::now();
+                            if now >= deadline {
+                                return false;
+                            }
+
+                            has_exited = exited.signal_exited.wait_timeout(has_exited, deadline - now).expect("Thread exited lock").0;
+                        }
+                    }
+                }
+
+                mem::drop(has_exited);
+                thread.join().ok();
+                true
+            }
+        }
     }
 }
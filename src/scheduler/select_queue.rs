@@ -0,0 +1,62 @@
+use super::job_queue::*;
+use super::desync_scheduler::*;
+
+use std::sync::*;
+use std::time::{Duration};
+
+/// How often `select()` checks to see if all of the queues it's waiting for have panicked
+const SELECT_PANIC_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+impl Scheduler {
+    ///
+    /// Runs a job synchronously on whichever of a set of queues finishes the jobs it already has first
+    ///
+    /// This schedules a sentinel job on each queue and waits for the first of them to run. The job is then run on that
+    /// queue as for `sync()`, and is passed the queue that was chosen. The other queues are left to carry on as normal.
+    /// Queues that have panicked are never chosen, and this panics if all of the queues have panicked.
+    ///
+    pub fn select<TFn, TResult>(&self, queues: &[Arc<JobQueue>], job: TFn) -> TResult
+    where   TFn:        Send+FnOnce(&Arc<JobQueue>) -> TResult,
+            TResult:    Send {
+        assert!(!queues.is_empty(), "select() needs at least one queue to choose from");
+
+        // The index of the first queue to run its sentinel job
+        let selected = Arc::new((Mutex::new(None), Condvar::new()));
+
+        for (index, queue) in queues.iter().enumerate() {
+            let selected = Arc::clone(&selected);
+
+            self.desync_no_panic(queue, move || {
+                let (selected, notify)  = &*selected;
+                let mut selected        = selected.lock().expect("Select lock");
+
+                if selected.is_none() {
+                    *selected = Some(index);
+                    notify.notify_one();
+                }
+            });
+        }
+
+        // Wait for one of the sentinels to run
+        let index = {
+            let (selected, notify)  = &*selected;
+            let mut selected        = selected.lock().expect("Select lock");
+
+            loop {
+                if let Some(index) = *selected {
+                    break index;
+                }
+
+                // A sentinel job never runs on a queue that has panicked
+                if queues.iter().all(|queue| queue.is_panicked()) {
+                    panic!("Cannot select from a set of queues that have all panicked");
+                }
+
+                selected = notify.wait_timeout(selected, SELECT_PANIC_CHECK_INTERVAL).expect("Select lock").0;
+            }
+        };
+
+        let queue = &queues[index];
+        self.sync(queue, move || job(queue))
+    }
+}
@@ -0,0 +1,180 @@
+use super::core::*;
+use super::job::*;
+use super::desync_scheduler::*;
+
+use crate::timer;
+
+use std::mem;
+use std::pin::{Pin};
+use std::sync::*;
+use std::sync::atomic::{Ordering};
+use std::time::{Duration, Instant};
+
+use futures::executor;
+use futures::channel::oneshot;
+use futures::future;
+use futures::future::{Future, FutureExt, BoxFuture};
+use futures::task::{Context, Poll};
+
+///
+/// The result of shutting down a scheduler
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ShutdownResult {
+    /// All of the jobs that were in flight finished before the timeout
+    Clean,
+
+    /// The timeout expired (or the shutdown future was dropped), and this many jobs were removed without running
+    Forced(usize)
+}
+
+///
+/// Error returned by `Scheduler::shutdown()` if the jobs in flight or the scheduler threads did not finish before the timeout
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ShutdownTimeout {
+    /// The number of jobs that were removed from their queues without running
+    pub removed_jobs: usize
+}
+
+///
+/// Future returned by `Scheduler::shutdown_timeout()`
+///
+/// If this is dropped before it completes, the shutdown is forced immediately.
+///
+pub struct ShutdownFuture {
+    /// The scheduler being shut down
+    core: Arc<SchedulerCore>,
+
+    /// Completes with true once every sentinel has been reached, or false when the timeout expires
+    waiting: BoxFuture<'static, bool>,
+
+    /// Set once the shutdown has either finished cleanly or been forced
+    finished: bool
+}
+
+impl Scheduler {
+    ///
+    /// Stops accepting new background jobs, and returns a future that completes once the jobs that are in flight have finished
+    ///
+    /// This adds a sentinel job to the end of every queue that's waiting for or running on a scheduler thread. The
+    /// future completes when all of the sentinels have been reached (or have been dropped because their queue panicked).
+    ///
+    fn stop_accepting_jobs(&self) -> impl 'static+Future<Output=()>+Send {
+        self.core.accepting_jobs.store(false, Ordering::SeqCst);
+
+        let queues      = self.core.active_queues();
+        let sentinels   = queues.iter()
+            .map(|queue| {
+                let (reached, wait_reached) = oneshot::channel();
+
+                // The sentinel has to bypass the check for the scheduler accepting jobs, and can't be removed (or counted) if the shutdown is forced
+                let sentinel = Job::uncancellable(move || { reached.send(()).ok(); });
+
+                if let Ok(false) = self.push_jobs(queue, vec![Box::new(sentinel)], WhenFull::Schedule) {
+                    wait_reached.map(|_| ()).left_future()
+                } else {
+                    future::ready(()).right_future()
+                }
+            })
+            .collect::<Vec<_>>();
+
+        future::join_all(sentinels).map(|_| ())
+    }
+
+    ///
+    /// Shuts down this scheduler, waiting up to `timeout` for the jobs that are in flight to finish
+    ///
+    /// The scheduler stops accepting new background jobs immediately: anything scheduled with `desync()` after this
+    /// is called is discarded (the returned `JobHandle` reports it as rejected), `try_desync()`, `try_sync()` and
+    /// `sync_timeout()` return a `ShutDown` error, and futures waiting for background jobs return `Canceled`. Jobs run
+    /// by `sync()` still run on the calling thread. If the jobs that were already scheduled have not all finished once
+    /// the timeout expires, any that have not started are removed from their queues and the future returns
+    /// `ShutdownResult::Forced` with the number of jobs that were removed.
+    ///
+    /// Only the queues that are waiting for or running on a scheduler thread are considered to be in flight: a queue
+    /// waiting for a future to wake it up is not included.
+    ///
+    pub fn shutdown_timeout(&self, timeout: Duration) -> ShutdownFuture {
+        let in_flight   = self.stop_accepting_jobs().map(|_| true);
+        let timeout     = timer::delay(timeout).map(|_| false);
+
+        ShutdownFuture {
+            core:       Arc::clone(&self.core),
+            waiting:    future::select(in_flight.boxed(), timeout.boxed()).map(|either| either.factor_first().0).boxed(),
+            finished:   false
+        }
+    }
+
+    ///
+    /// Shuts down this scheduler and stops its threads, blocking until the jobs in flight have finished and the threads
+    /// have exited, or the timeout has expired
+    ///
+    /// This stops accepting new background jobs in the same way as `shutdown_timeout()`, and waits indefinitely if no
+    /// timeout is supplied. Once the jobs have finished, the maximum number of threads is set to 0 and this waits for
+    /// the scheduler threads to exit. If the timeout expires, the jobs that have not started are removed and
+    /// `ShutdownTimeout` is returned: the threads are still told to stop, but this doesn't wait for any that are
+    /// still running.
+    ///
+    /// Like `despawn_threads_if_overloaded()`, this must not be called from a scheduler thread.
+    ///
+    pub fn shutdown(&self, timeout: Option<Duration>) -> Result<(), ShutdownTimeout> {
+        let deadline    = timeout.map(|timeout| Instant::now() + timeout);
+        let result      = match timeout {
+            Some(timeout)   => executor::block_on(self.shutdown_timeout(timeout)),
+            None            => { executor::block_on(self.stop_accepting_jobs()); ShutdownResult::Clean }
+        };
+
+        // Stop all of the threads, waiting for them to exit if the jobs finished in time
+        *self.core.max_threads.lock().expect("Max threads lock") = 0;
+        let threads     = mem::take(&mut *self.core.threads.lock().expect("Scheduler threads lock"));
+        let exited      = threads.into_iter()
+            .map(|(_, thread)| thread.despawn_and_wait(deadline))
+            .collect::<Vec<_>>();
+        let all_exited  = exited.into_iter().all(|exited| exited);
+
+        match result {
+            ShutdownResult::Clean if all_exited     => Ok(()),
+            ShutdownResult::Clean                   => Err(ShutdownTimeout { removed_jobs: 0 }),
+            ShutdownResult::Forced(removed_jobs)    => Err(ShutdownTimeout { removed_jobs })
+        }
+    }
+
+    ///
+    /// True once this scheduler has started shutting down (background jobs scheduled after this point are discarded)
+    ///
+    pub fn is_shutting_down(&self) -> bool {
+        !self.core.accepting_jobs.load(Ordering::SeqCst)
+    }
+}
+
+impl Future for ShutdownFuture {
+    type Output = ShutdownResult;
+
+    fn poll(mut self: Pin<&mut Self>, context: &mut Context) -> Poll<ShutdownResult> {
+        if self.finished {
+            return Poll::Ready(ShutdownResult::Clean);
+        }
+
+        match self.waiting.poll_unpin(context) {
+            Poll::Pending       => Poll::Pending,
+            Poll::Ready(true)   => {
+                self.finished = true;
+                Poll::Ready(ShutdownResult::Clean)
+            }
+            Poll::Ready(false)  => {
+                self.finished = true;
+                Poll::Ready(ShutdownResult::Forced(self.core.terminate()))
+            }
+        }
+    }
+}
+
+impl Drop for ShutdownFuture {
+    fn drop(&mut self) {
+        // Abandoning the shutdown forces it straight away
+        if !self.finished {
+            self.core.terminate();
+        }
+    }
+}
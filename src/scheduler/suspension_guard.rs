@@ -0,0 +1,42 @@
+use super::job_queue::*;
+use super::queue_resumer::*;
+
+use std::sync::*;
+
+///
+/// Keeps a queue suspended until it's dropped (returned by `Scheduler::suspend_guard()`)
+///
+pub struct SuspensionGuard {
+    /// The queue that is suspended
+    queue: Arc<JobQueue>,
+
+    /// Used to resume the queue when this guard is dropped
+    resumer: Option<QueueResumer>
+}
+
+impl SuspensionGuard {
+    ///
+    /// Creates a guard for a queue that has finished suspending
+    ///
+    pub (super) fn new(queue: Arc<JobQueue>, resumer: QueueResumer) -> SuspensionGuard {
+        SuspensionGuard {
+            queue,
+            resumer: Some(resumer)
+        }
+    }
+
+    ///
+    /// The queue that this guard is keeping suspended
+    ///
+    pub fn queue(&self) -> &Arc<JobQueue> {
+        &self.queue
+    }
+}
+
+impl Drop for SuspensionGuard {
+    fn drop(&mut self) {
+        if let Some(resumer) = self.resumer.take() {
+            resumer.resume();
+        }
+    }
+}
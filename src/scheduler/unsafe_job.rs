@@ -32,4 +32,9 @@ impl ScheduledJob for UnsafeJob {
             (*action).run(context)
         }
     }
+
+    fn can_cancel(&self) -> bool {
+        // Unsafe jobs are only used for sync jobs, where the thread that scheduled them is blocked until they run
+        false
+    }
 }
@@ -0,0 +1,69 @@
+//!
+//! Racing jobs on two `Desync` objects
+//!
+//! `select_with` schedules a job on two different objects and returns the result from
+//! whichever one runs first. As jobs run after everything else that is already queued on an
+//! object, this picks the object that clears its backlog first.
+//!
+
+use super::desync::*;
+
+use futures::future;
+use futures::future::{Future, FutureExt};
+use futures::channel::oneshot;
+
+use std::sync::*;
+
+///
+/// Indicates which of the two objects passed to `select_with` produced a result first
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SelectResult<R> {
+    /// The job on the object that `select_with` was called on finished first
+    First(R),
+
+    /// The job on the other object finished first
+    Second(R)
+}
+
+impl<R> SelectResult<R> {
+    ///
+    /// Returns the result, regardless of which object produced it
+    ///
+    pub fn into_inner(self) -> R {
+        match self {
+            SelectResult::First(result)     => result,
+            SelectResult::Second(result)    => result
+        }
+    }
+}
+
+impl<T: 'static+Send+Unpin> Desync<T> {
+    ///
+    /// Schedules a job on this object and another job on a second object, returning the result of whichever
+    /// finishes first
+    ///
+    /// The job that loses the race still runs (it may already be running), but its result is discarded. The
+    /// future returns `Canceled` only if both jobs are cancelled.
+    ///
+    pub fn select_with<U, TFn1, TFn2, R>(self: Arc<Self>, other: Arc<Desync<U>>, first: TFn1, second: TFn2) -> impl 'static+Future<Output=Result<SelectResult<R>, oneshot::Canceled>>+Send
+    where   U:      'static+Send+Unpin,
+            TFn1:   'static+Send+FnOnce(&mut T) -> R,
+            TFn2:   'static+Send+FnOnce(&mut U) -> R,
+            R:      'static+Send {
+        // Both jobs run in the background: polling a job with `future()` can run it on the current thread, which would block the other
+        let first   = self.future_desync(move |data| future::ready(first(data)).boxed());
+        let second  = other.future_desync(move |data| future::ready(second(data)).boxed());
+
+        async move {
+            match future::select(first, second).await {
+                future::Either::Left((Ok(result), _))   => Ok(SelectResult::First(result)),
+                future::Either::Right((Ok(result), _))  => Ok(SelectResult::Second(result)),
+
+                // If one job was cancelled, use the result from the other
+                future::Either::Left((Err(_), second))  => second.await.map(SelectResult::Second),
+                future::Either::Right((Err(_), first))  => first.await.map(SelectResult::First)
+            }
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//!
+//! Using a `Desync` object as the destination for a stream
+//!
+//! `as_sink()` creates a `DesyncSink`, which implements `futures::Sink`. Each item sent to the
+//! sink is processed by a job on the object, so a `Desync` object can be used at the end of an
+//! asynchronous pipeline with combinators such as `SinkExt::send_all()`:
+//!
+//! ```
+//! # extern crate desync;
+//! # extern crate futures;
+//! # use ::desync::*;
+//! # use futures::executor;
+//! # use futures::stream;
+//! # use futures::sink::{SinkExt};
+//! # use std::sync::*;
+//! let total       = Arc::new(Desync::new(0));
+//! let mut sink    = total.as_sink(|total, item: i32| *total += item);
+//!
+//! executor::block_on(async {
+//!     sink.send_all(&mut stream::iter(vec![Ok(1), Ok(2), Ok(3)])).await.unwrap();
+//! });
+//!
+//! assert!(total.sync(|total| *total) == 6);
+//! ```
+//!
+
+use super::desync::*;
+use super::timer::*;
+
+use futures::future::{BoxFuture, FutureExt};
+use futures::channel::oneshot;
+use futures::sink::{Sink};
+use futures::task::{Poll, Context};
+
+use std::marker::{PhantomData};
+use std::pin::{Pin};
+use std::sync::*;
+use std::time::{Duration};
+
+/// How often a sink checks whether a full queue has space for more items
+const SPACE_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+///
+/// A `Sink` that processes the items sent to it by running jobs on a `Desync` object
+///
+/// The sink is ready for more items whenever the object's queue has space for them (queues created with
+/// `Desync::new()` always have space). Flushing or closing the sink waits for the items that have been sent to be
+/// processed. The sink returns `Canceled` if a job on the object panics.
+///
+pub struct DesyncSink<T: 'static+Send+Unpin, Item, ProcessFn> {
+    /// The object that processes the items
+    desync: Arc<Desync<T>>,
+
+    /// The function that processes each item (only ever called from jobs on the object's queue)
+    process: Arc<Mutex<ProcessFn>>,
+
+    /// Timer used to check for space again while the queue is full
+    waiting_for_space: Option<BoxFuture<'static, ()>>,
+
+    /// Completes once the items that were sent before the current flush began have been processed
+    flushing: Option<BoxFuture<'static, ()>>,
+
+    /// The sink accepts items of this type
+    item: PhantomData<fn(Item)>
+}
+
+impl<T: 'static+Send+Unpin> Desync<T> {
+    ///
+    /// Creates a `Sink` that calls a function on this object for every item sent to it
+    ///
+    /// Each item is processed by a separate job, so other jobs can be interleaved with the items.
+    ///
+    pub fn as_sink<Item, ProcessFn>(self: &Arc<Self>, process: ProcessFn) -> DesyncSink<T, Item, ProcessFn>
+    where   Item:       'static+Send,
+            ProcessFn:  'static+Send+FnMut(&mut T, Item) {
+        DesyncSink {
+            desync:             Arc::clone(self),
+            process:            Arc::new(Mutex::new(process)),
+            waiting_for_space:  None,
+            flushing:           None,
+            item:               PhantomData
+        }
+    }
+}
+
+impl<T: 'static+Send+Unpin, Item, ProcessFn> DesyncSink<T, Item, ProcessFn> {
+    ///
+    /// Polls until the items that have been sent to this sink have been processed
+    ///
+    fn poll_processed(&mut self, context: &mut Context) -> Poll<Result<(), oneshot::Canceled>> {
+        // Drain the queue, unless a drain is already in progress
+        let desync      = &self.desync;
+        let flushing    = self.flushing.get_or_insert_with(|| desync.drain().boxed());
+
+        match flushing.poll_unpin(context) {
+            Poll::Pending   => Poll::Pending,
+            Poll::Ready(()) => {
+                self.flushing = None;
+
+                // Draining also completes if the queue panicked, in which case the items might not have been processed
+                if self.desync.queue().is_panicked() {
+                    Poll::Ready(Err(oneshot::Canceled))
+                } else {
+                    Poll::Ready(Ok(()))
+                }
+            }
+        }
+    }
+}
+
+impl<T, Item, ProcessFn> Sink<Item> for DesyncSink<T, Item, ProcessFn>
+where   T:          'static+Send+Unpin,
+        Item:       'static+Send,
+        ProcessFn:  'static+Send+FnMut(&mut T, Item) {
+    type Error = oneshot::Canceled;
+
+    fn poll_ready(self: Pin<&mut Self>, context: &mut Context) -> Poll<Result<(), Self::Error>> {
+        let sink = self.get_mut();
+
+        loop {
+            if sink.desync.queue().is_panicked() {
+                return Poll::Ready(Err(oneshot::Canceled));
+            }
+
+            // Wait for the previous check to finish before checking whether or not the queue is full again
+            if let Some(waiting_for_space) = sink.waiting_for_space.as_mut() {
+                if waiting_for_space.poll_unpin(context).is_pending() {
+                    return Poll::Pending;
+                }
+
+                sink.waiting_for_space = None;
+            }
+
+            if !sink.desync.queue().is_full() {
+                return Poll::Ready(Ok(()));
+            }
+
+            // Bounded queues don't notify when a job is removed, so check again after a short delay
+            sink.waiting_for_space = Some(delay(SPACE_CHECK_INTERVAL).boxed());
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        let process     = Arc::clone(&self.process);
+        let job         = self.desync.data_job(move |data| {
+            let mut process = process.lock().expect("Sink process function lock");
+            (*process)(data, item)
+        });
+
+        // Report an error instead of panicking if the queue can no longer run jobs
+        if self.desync.scheduler().desync_no_panic(self.desync.queue(), job) {
+            Err(oneshot::Canceled)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, context: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().poll_processed(context)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, context: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().poll_processed(context)
+    }
+}
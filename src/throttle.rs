@@ -0,0 +1,196 @@
+//!
+//! `ThrottleHandle` provides rate-limited updates for a `Desync` object
+//!
+//! A throttled update runs at most once in every time window, no matter how often it's
+//! triggered. This is useful for things like UI updates or writes that are driven by
+//! high-frequency events, where only the most recent state matters:
+//!
+//! ```
+//! # extern crate desync;
+//! # use ::desync::*;
+//! # use std::time::Duration;
+//! let redraws = Desync::new(0);
+//! let redraw  = redraws.throttle(Duration::from_millis(100), |count| *count += 1);
+//!
+//! for _ in 0..10 { redraw.trigger(); }
+//!
+//! // The first trigger runs immediately, the rest are combined into one update when the window ends
+//! // (which is discarded here, as dropping the handle cancels any update that's waiting to run)
+//! drop(redraw);
+//! assert!(redraws.sync(|count| *count) == 1);
+//! ```
+//!
+//! `throttled_future()` throttles individual jobs by key instead: a job is discarded if another job with
+//! the same key was scheduled too recently. This makes it possible to limit how often each of a set of
+//! resources is updated (such as the files being written by an object) without a separate handle for each.
+//!
+
+use super::desync::*;
+
+use futures::future;
+use futures::future::{Future, BoxFuture, FutureExt};
+
+use std::sync::*;
+use std::collections::HashMap;
+use std::thread;
+use std::time::{Duration, Instant};
+
+///
+/// The number of keys `throttled_future()` tracks before it starts removing the ones that have expired
+const THROTTLE_SWEEP_SIZE: usize = 64;
+
+///
+/// For each key passed to `throttled_future()`, the time until which jobs with that key are discarded
+///
+pub (crate) struct ThrottledKeys {
+    throttled_until: Mutex<HashMap<u64, Instant>>
+}
+
+impl ThrottledKeys {
+    ///
+    /// Creates a new set of throttled keys
+    ///
+    pub (crate) fn new() -> ThrottledKeys {
+        ThrottledKeys { throttled_until: Mutex::new(HashMap::new()) }
+    }
+
+    ///
+    /// Returns true if a job with the specified key can run now, and throttles the key for `min_interval` if it can
+    ///
+    fn try_start(&self, throttle_key: u64, min_interval: Duration) -> bool {
+        let mut throttled_until = self.throttled_until.lock().expect("Throttled keys lock");
+        let now                 = Instant::now();
+
+        // Stop tracking keys that are no longer throttled once there are a lot of them
+        if throttled_until.len() >= THROTTLE_SWEEP_SIZE {
+            throttled_until.retain(|_, until| *until > now);
+        }
+
+        match throttled_until.get(&throttle_key) {
+            Some(until) if *until > now => false,
+            _                           => { throttled_until.insert(throttle_key, now + min_interval); true }
+        }
+    }
+}
+
+///
+/// The state of a throttled update
+///
+struct ThrottleState {
+    /// When the update last ran
+    last_run: Option<Instant>,
+
+    /// The job to schedule at the end of the current window, if the update has been triggered since it last ran
+    pending: Option<Box<dyn FnOnce()+Send>>
+}
+
+///
+/// Handle for triggering a throttled update to a `Desync` object
+///
+/// When the handle is dropped, any update that is waiting for the end of the current window is discarded.
+///
+pub struct ThrottleHandle<'a, T: 'static+Send+Unpin> {
+    /// The object that is being updated
+    desync: &'a Desync<T>,
+
+    /// The minimum time between updates
+    rate: Duration,
+
+    /// The function to run when the update happens
+    update: Arc<dyn Fn(&mut T)+Send+Sync>,
+
+    /// The state of the throttle
+    state: Arc<Desync<ThrottleState>>
+}
+
+impl<T: 'static+Send+Unpin> Desync<T> {
+    ///
+    /// Creates a handle that can be used to run an update on this object at most once per `rate`
+    ///
+    /// Calling `trigger()` on the handle runs the update immediately if it hasn't run within the last
+    /// `rate`, otherwise it runs once when the window ends (further triggers before then have no
+    /// additional effect).
+    ///
+    pub fn throttle<TFn>(&self, rate: Duration, update: TFn) -> ThrottleHandle<'_, T>
+    where TFn: 'static+Send+Sync+Fn(&mut T) {
+        ThrottleHandle {
+            desync: self,
+            rate,
+            update: Arc::new(update),
+            state:  Arc::new(Desync::new(ThrottleState { last_run: None, pending: None }))
+        }
+    }
+
+    ///
+    /// As for `future()`, except the job is discarded if a job with the same key was scheduled within the last `min_interval`
+    ///
+    /// The future returns `None` immediately if the job was discarded. It also returns `None` if the job was
+    /// cancelled before it could run (for example, because an earlier job panicked). Keys stop being tracked
+    /// once their interval has passed, so any number of different keys can be used.
+    ///
+    pub fn throttled_future<TFn, TOutput>(&self, throttle_key: u64, min_interval: Duration, job: TFn) -> impl Future<Output=Option<TOutput>>+Send
+    where   TFn:        'static+Send+for<'a> FnOnce(&'a mut T) -> BoxFuture<'a, TOutput>,
+            TOutput:    'static+Send {
+        let allowed = self.throttled_keys().try_start(throttle_key, min_interval);
+
+        if allowed {
+            self.future(job).map(|result| result.ok()).left_future()
+        } else {
+            future::ready(None).right_future()
+        }
+    }
+}
+
+impl<'a, T: 'static+Send+Unpin> ThrottleHandle<'a, T> {
+    ///
+    /// Requests that the update runs at the next allowed time
+    ///
+    pub fn trigger(&self) {
+        let update      = Arc::clone(&self.update);
+        let job         = Box::new(self.desync.data_job(move |data| update(data)));
+        let queue       = Arc::clone(self.desync.queue());
+        let scheduler   = self.desync.scheduler();
+        let rate        = self.rate;
+        let timer_state = Arc::clone(&self.state);
+
+        self.state.desync(move |state| {
+            // The last trigger in a window replaces the pending job
+            if state.pending.is_some() {
+                state.pending = Some(job);
+                return;
+            }
+
+            let now = Instant::now();
+            match state.last_run.map(|last_run| now.duration_since(last_run)) {
+                Some(elapsed) if elapsed < rate => {
+                    // Schedule the job to run at the end of the window
+                    state.pending = Some(job);
+
+                    thread::spawn(move || {
+                        thread::sleep(rate - elapsed);
+
+                        timer_state.desync(move |state| {
+                            if let Some(job) = state.pending.take() {
+                                state.last_run = Some(Instant::now());
+                                scheduler.desync(&queue, job);
+                            }
+                        });
+                    });
+                }
+
+                _ => {
+                    // Run the job straight away
+                    state.last_run = Some(now);
+                    scheduler.desync(&queue, job);
+                }
+            }
+        });
+    }
+}
+
+impl<'a, T: 'static+Send+Unpin> Drop for ThrottleHandle<'a, T> {
+    fn drop(&mut self) {
+        // Discard any pending update: it can't be scheduled once this returns, so it can't outlive the object it's updating
+        self.state.sync(|state| state.pending = None);
+    }
+}
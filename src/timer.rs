@@ -0,0 +1,25 @@
+//!
+//! A simple timer future, used where jobs need to wait for a period of time
+//!
+
+use futures::future::{Future, FutureExt};
+use futures::channel::oneshot;
+
+use std::thread;
+use std::time::{Duration};
+
+///
+/// Returns a future that completes after the specified duration has passed
+///
+/// The timer runs on a separate thread, so this is suitable for delays that aren't too frequent.
+///
+pub (crate) fn delay(duration: Duration) -> impl Future<Output=()>+Send {
+    let (done, timeout) = oneshot::channel();
+
+    thread::spawn(move || {
+        thread::sleep(duration);
+        done.send(()).ok();
+    });
+
+    timeout.map(|_| ())
+}
@@ -0,0 +1,74 @@
+//!
+//! Non-owning references to `Desync` objects
+//!
+//! A `WeakDesync` refers to a shared `Desync` object without keeping it alive. This is useful when
+//! an object is used as a sink for events from another component: the component can send jobs to
+//! the object for as long as its owner keeps it around, and just stops once it has been dropped:
+//!
+//! ```
+//! # extern crate desync;
+//! # use ::desync::*;
+//! # use std::sync::*;
+//! let log     = Arc::new(Desync::new(vec![]));
+//! let sink    = log.downgrade();
+//!
+//! sink.desync(|log| log.push("Event")).unwrap();
+//! assert!(log.sync(|log| log.clone()) == vec!["Event"]);
+//!
+//! drop(log);
+//! assert!(sink.desync(|log| log.push("Ignored")).is_err());
+//! ```
+//!
+
+use super::desync::*;
+use super::scheduler::*;
+
+use std::sync::*;
+
+///
+/// Error returned when using a `WeakDesync` whose object has been dropped
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Dropped;
+
+///
+/// A reference to a shared `Desync` object that does not keep it alive
+///
+pub struct WeakDesync<T: 'static+Send+Unpin> {
+    /// The object that this refers to
+    desync: Weak<Desync<T>>
+}
+
+impl<T: 'static+Send+Unpin> Desync<T> {
+    ///
+    /// Creates a reference to this object that does not keep it alive
+    ///
+    pub fn downgrade(self: &Arc<Self>) -> WeakDesync<T> {
+        WeakDesync { desync: Arc::downgrade(self) }
+    }
+}
+
+impl<T: 'static+Send+Unpin> WeakDesync<T> {
+    ///
+    /// Retrieves the object that this refers to, or `None` if it has been dropped
+    ///
+    pub fn upgrade(&self) -> Option<Arc<Desync<T>>> {
+        self.desync.upgrade()
+    }
+
+    ///
+    /// Schedules a job on the object that this refers to, or returns `Dropped` if it no longer exists
+    ///
+    pub fn desync<TFn>(&self, job: TFn) -> Result<JobHandle, Dropped>
+    where TFn: 'static+Send+FnOnce(&mut T) {
+        self.upgrade()
+            .map(|desync| desync.desync(job))
+            .ok_or(Dropped)
+    }
+}
+
+impl<T: 'static+Send+Unpin> Clone for WeakDesync<T> {
+    fn clone(&self) -> Self {
+        WeakDesync { desync: Weak::clone(&self.desync) }
+    }
+}
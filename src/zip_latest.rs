@@ -0,0 +1,179 @@
+//!
+//! Combining the latest values from two `Desync` objects
+//!
+//! `zip_latest()` creates a stream that produces the contents of two objects as a pair every time
+//! either of them is updated. This is useful for things like a UI component that needs to be redrawn
+//! whenever any of its data sources change:
+//!
+//! ```
+//! # extern crate desync;
+//! # extern crate futures;
+//! # use ::desync::*;
+//! # use futures::executor;
+//! # use futures::prelude::*;
+//! let width       = Desync::new(10);
+//! let height      = Desync::new(20);
+//! let mut sizes   = width.zip_latest_distinct(&height);
+//!
+//! executor::block_on(async {
+//!     assert!(sizes.next().await == Some((10, 20)));
+//!
+//!     height.desync(|height| *height = 30);
+//!     assert!(sizes.next().await == Some((10, 30)));
+//! });
+//! ```
+//!
+
+use super::desync::*;
+
+use futures::stream::{Stream};
+use futures::task::{Poll, Context, Waker};
+
+use std::sync::*;
+use std::pin::{Pin};
+
+///
+/// Function that returns true if a pair is a duplicate of the previous pair
+///
+type IsDuplicateFn<T, U> = fn(&(T, U), &(T, U)) -> bool;
+
+///
+/// The state of a `zip_latest` stream
+///
+struct ZipCore<T, U> {
+    /// The latest value of the first object, once it's known
+    first: Option<T>,
+
+    /// The latest value of the second object, once it's known
+    second: Option<U>,
+
+    /// The most recent pair, if it has not been read from the stream yet
+    pending: Option<(T, U)>,
+
+    /// The most recent pair that was generated
+    last_pair: Option<(T, U)>,
+
+    /// If set, pairs that this returns true for when compared to the previous pair are not generated
+    is_duplicate: Option<IsDuplicateFn<T, U>>,
+
+    /// The task waiting for the next pair
+    waker: Option<Waker>
+}
+
+///
+/// Stream returned by `zip_latest()`
+///
+struct ZipLatest<T, U> {
+    core: Arc<Mutex<ZipCore<T, U>>>
+}
+
+impl<T: Clone, U: Clone> ZipCore<T, U> {
+    ///
+    /// Generates a new pair if both values are known, returning the waker to notify if there is one
+    ///
+    fn generate_pair(&mut self) -> Option<Waker> {
+        let pair = match (&self.first, &self.second) {
+            (Some(first), Some(second)) => (first.clone(), second.clone()),
+            _                           => { return None; }
+        };
+
+        if let (Some(is_duplicate), Some(last_pair)) = (self.is_duplicate, &self.last_pair) {
+            if is_duplicate(&pair, last_pair) {
+                return None;
+            }
+        }
+
+        // Only the most recent pair is kept, so a slow reader skips straight to the latest values
+        self.last_pair  = Some(pair.clone());
+        self.pending    = Some(pair);
+        self.waker.take()
+    }
+}
+
+impl<T: 'static+Send+Unpin+Clone> Desync<T> {
+    ///
+    /// Creates a stream that produces the contents of this object and another object as a pair whenever either of them is updated
+    ///
+    /// The first pair is generated once the jobs that are currently queued on both objects have completed, and then a new
+    /// pair is generated after every job on either object. Only the latest pair is kept: if several jobs finish before
+    /// the stream is read, it skips to the most recent values. The stream does not end: it stops receiving updates when
+    /// the objects are dropped.
+    ///
+    pub fn zip_latest<U>(&self, other: &Desync<U>) -> impl Stream<Item=(T, U)>+Send+Unpin
+    where U: 'static+Send+Unpin+Clone {
+        self.create_zip_latest(other, None)
+    }
+
+    ///
+    /// As for `zip_latest()`, except that a pair is not generated if it's the same as the previous pair
+    ///
+    pub fn zip_latest_distinct<U>(&self, other: &Desync<U>) -> impl Stream<Item=(T, U)>+Send+Unpin
+    where   T: PartialEq,
+            U: 'static+Send+Unpin+Clone+PartialEq {
+        self.create_zip_latest(other, Some(|pair, last_pair| pair == last_pair))
+    }
+
+    ///
+    /// Creates a zip_latest stream, with an optional function for detecting duplicate pairs
+    ///
+    fn create_zip_latest<U>(&self, other: &Desync<U>, is_duplicate: Option<IsDuplicateFn<T, U>>) -> ZipLatest<T, U>
+    where U: 'static+Send+Unpin+Clone {
+        let core = ZipCore {
+            first:          None,
+            second:         None,
+            pending:        None,
+            last_pair:      None,
+            is_duplicate,
+            waker:          None
+        };
+        let core = Arc::new(Mutex::new(core));
+
+        // The observers stop once the stream has been dropped
+        let first_core  = Arc::downgrade(&core);
+        let second_core = Arc::downgrade(&core);
+
+        self.observe_changes(move |first: &T| {
+            let core = match first_core.upgrade() { Some(core) => core, None => { return true; } };
+
+            let waker = {
+                let mut core    = core.lock().expect("Zip latest lock");
+                core.first      = Some(first.clone());
+                core.generate_pair()
+            };
+
+            if let Some(waker) = waker { waker.wake(); }
+            false
+        });
+
+        other.observe_changes(move |second: &U| {
+            let core = match second_core.upgrade() { Some(core) => core, None => { return true; } };
+
+            let waker = {
+                let mut core    = core.lock().expect("Zip latest lock");
+                core.second     = Some(second.clone());
+                core.generate_pair()
+            };
+
+            if let Some(waker) = waker { waker.wake(); }
+            false
+        });
+
+        ZipLatest { core }
+    }
+}
+
+impl<T, U> Stream for ZipLatest<T, U> {
+    type Item = (T, U);
+
+    fn poll_next(self: Pin<&mut Self>, context: &mut Context) -> Poll<Option<(T, U)>> {
+        let mut core = self.core.lock().expect("Zip latest lock");
+
+        match core.pending.take() {
+            Some(pair)  => Poll::Ready(Some(pair)),
+            None        => {
+                core.waker = Some(context.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
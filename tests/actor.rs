@@ -0,0 +1,47 @@
+extern crate desync;
+extern crate futures;
+
+use desync::*;
+use futures::executor;
+
+use std::sync::*;
+
+enum CounterMessage {
+    Add(i32),
+    Report(mpsc::Sender<i32>)
+}
+
+fn counter() -> DesyncActor<CounterMessage, i32> {
+    DesyncActor::new(0, |count, msg| {
+        match msg {
+            CounterMessage::Add(val)        => *count += val,
+            CounterMessage::Report(sender)  => sender.send(*count).unwrap()
+        }
+    })
+}
+
+#[test]
+fn process_messages_in_order() {
+    let actor           = counter();
+    let (send, recv)    = mpsc::channel();
+
+    actor.send(CounterMessage::Add(1));
+    actor.send(CounterMessage::Add(2));
+    actor.send(CounterMessage::Report(send));
+
+    assert!(recv.recv().unwrap() == 3);
+}
+
+#[test]
+fn send_and_wait_via_address() {
+    let actor           = counter();
+    let addr            = actor.addr();
+    let other_addr      = addr.clone();
+    let (send, recv)    = mpsc::channel();
+
+    executor::block_on(addr.send_and_wait(CounterMessage::Add(40))).unwrap();
+    executor::block_on(other_addr.send_and_wait(CounterMessage::Add(2))).unwrap();
+    executor::block_on(actor.send_and_wait(CounterMessage::Report(send))).unwrap();
+
+    assert!(recv.try_recv().unwrap() == 42);
+}
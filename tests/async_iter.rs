@@ -0,0 +1,63 @@
+extern crate desync;
+extern crate futures;
+
+use desync::*;
+use futures::executor;
+use futures::prelude::*;
+
+use std::sync::*;
+
+#[test]
+fn read_all_items() {
+    let numbers = Desync::new(0..5);
+    let all     = executor::block_on(numbers.async_iter().collect::<Vec<_>>());
+
+    assert!(all == vec![0, 1, 2, 3, 4]);
+}
+
+#[test]
+fn items_are_serialized_with_other_jobs() {
+    let numbers     = Desync::new(vec![1, 2, 3, 4].into_iter());
+
+    // Skip the first item using a normal job
+    numbers.desync(|iter| { iter.next(); });
+
+    let mut stream  = numbers.async_iter();
+    let first       = executor::block_on(stream.next());
+
+    // Skip another item in between reads from the stream
+    numbers.desync(|iter| { iter.next(); });
+    let rest        = executor::block_on(stream.collect::<Vec<_>>());
+
+    assert!(first == Some(2));
+    assert!(rest == vec![4]);
+}
+
+#[test]
+fn into_stream_produces_items_until_none() {
+    let counter = Arc::new(Desync::new(0));
+    let stream  = Arc::clone(&counter).into_stream(|count| {
+        *count += 1;
+        if *count <= 3 { Some(*count * 10) } else { None }
+    });
+
+    let all     = executor::block_on(stream.collect::<Vec<_>>());
+
+    assert!(all == vec![10, 20, 30]);
+    assert!(counter.sync(|count| *count) == 4);
+}
+
+#[test]
+fn into_stream_releases_queue_between_items() {
+    let numbers     = Arc::new(Desync::new(vec![1, 2, 3, 4]));
+    let mut stream  = Arc::clone(&numbers).into_stream(|numbers| if numbers.is_empty() { None } else { Some(numbers.remove(0)) });
+
+    let first       = executor::block_on(stream.next());
+
+    // Other jobs can run in between reads from the stream
+    numbers.desync(|numbers| { numbers.remove(0); });
+    let rest        = executor::block_on(stream.collect::<Vec<_>>());
+
+    assert!(first == Some(1));
+    assert!(rest == vec![3, 4]);
+}
@@ -0,0 +1,48 @@
+extern crate desync;
+
+use desync::*;
+
+#[test]
+fn replay_onto_fresh_object() {
+    let desynced = Desync::new(vec![0]);
+    let capture  = desynced.capture();
+
+    capture.desync(|data| data.push(1));
+    capture.desync(|data| data.push(2));
+
+    let replayed = capture.replay_new();
+
+    assert!(capture.len() == 2);
+    assert!(replayed.sync(|data| data.clone()) == vec![0, 1, 2]);
+}
+
+#[test]
+fn replay_onto_existing_object() {
+    let desynced = Desync::new(1);
+    let capture  = desynced.capture();
+
+    capture.desync(|val| *val *= 2);
+    capture.desync(|val| *val += 1);
+
+    let target   = Desync::new(10);
+    capture.replay(&target);
+
+    assert!(desynced.sync(|val| *val) == 3);
+    assert!(target.sync(|val| *val) == 21);
+}
+
+#[test]
+fn operations_are_not_recorded_when_capturing_is_off() {
+    let desynced = Desync::new(0);
+    let capture  = desynced.capture();
+
+    capture.desync(|val| *val += 1);
+    capture.set_capturing(false);
+    capture.desync(|val| *val += 10);
+    capture.set_capturing(true);
+    capture.desync(|val| *val += 100);
+
+    assert!(!capture.is_empty());
+    assert!(desynced.sync(|val| *val) == 111);
+    assert!(capture.replay_new().sync(|val| *val) == 101);
+}
@@ -0,0 +1,113 @@
+extern crate desync;
+extern crate futures;
+
+use desync::*;
+use desync::scheduler::*;
+
+use futures::future;
+use futures::executor;
+use futures::prelude::*;
+
+use std::sync::*;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn send_and_receive() {
+    let (sender, mut receiver) = scheduler().create_channel(4);
+
+    executor::block_on(async {
+        sender.send(1).await.unwrap();
+        sender.send(2).await.unwrap();
+
+        assert!(receiver.next().await == Some(1));
+        assert!(receiver.next().await == Some(2));
+    });
+}
+
+#[test]
+fn receiver_stops_when_senders_dropped() {
+    let (sender, mut receiver)  = scheduler().create_channel(4);
+    let other_sender            = sender.clone();
+
+    executor::block_on(async {
+        sender.send(1).await.unwrap();
+        drop(sender);
+        other_sender.send(2).await.unwrap();
+        drop(other_sender);
+
+        assert!(receiver.next().await == Some(1));
+        assert!(receiver.next().await == Some(2));
+        assert!(receiver.next().await.is_none());
+    });
+}
+
+#[test]
+fn send_fails_when_receivers_dropped() {
+    let (sender, receiver) = scheduler().create_channel(4);
+    drop(receiver);
+
+    assert!(executor::block_on(sender.send(1)) == Err(ChannelClosed(1)));
+}
+
+#[test]
+fn channel_is_bounded() {
+    let (sender, mut receiver) = scheduler().create_channel(2);
+
+    assert!(sender.try_send(1) == Ok(()));
+    assert!(sender.try_send(2) == Ok(()));
+    assert!(sender.try_send(3) == Err(3));
+
+    // Sending waits until a message has been received
+    let waiting = thread::spawn(move || executor::block_on(sender.send(3)));
+    thread::sleep(Duration::from_millis(20));
+
+    executor::block_on(async {
+        assert!(receiver.next().await == Some(1));
+        assert!(waiting.join().unwrap() == Ok(()));
+        assert!(receiver.next().await == Some(2));
+        assert!(receiver.next().await == Some(3));
+    });
+}
+
+#[test]
+fn each_message_goes_to_one_receiver() {
+    let (sender, mut receiver)  = scheduler().create_channel(8);
+    let mut other_receiver      = receiver.clone();
+
+    executor::block_on(async {
+        for x in 0..4 {
+            sender.send(x).await.unwrap();
+        }
+        drop(sender);
+
+        let mut received = vec![
+            receiver.next().await.unwrap(),
+            other_receiver.next().await.unwrap(),
+            receiver.next().await.unwrap(),
+            other_receiver.next().await.unwrap()
+        ];
+        received.sort();
+
+        assert!(received == vec![0, 1, 2, 3]);
+        assert!(receiver.next().await.is_none());
+        assert!(other_receiver.next().await.is_none());
+    });
+}
+
+#[test]
+fn pipe_receiver_into_desync() {
+    let (sender, receiver)  = scheduler().create_channel(4);
+    let obj                 = Arc::new(Desync::new(vec![]));
+
+    pipe_in(Arc::clone(&obj), receiver, |core: &mut Vec<i32>, item| { core.push(item); future::ready(()).boxed() });
+
+    executor::block_on(async {
+        for x in 0..3 {
+            sender.send(x).await.unwrap();
+        }
+    });
+
+    thread::sleep(Duration::from_millis(20));
+    assert!(obj.sync(|core| core.clone()) == vec![0, 1, 2]);
+}
@@ -0,0 +1,80 @@
+extern crate desync;
+
+use desync::*;
+
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn successful_jobs_keep_circuit_closed() {
+    let desynced    = Desync::new(0);
+    let breaker     = desynced.with_circuit_breaker(2, Duration::from_millis(50));
+
+    assert!(breaker.sync(|val| { *val += 1; *val }) == Ok(1));
+    assert!(breaker.desync(|val| *val += 1) == Ok(()));
+    assert!(desynced.sync(|val| *val) == 2);
+    assert!(!breaker.is_open());
+}
+
+#[test]
+fn panics_are_reported_and_do_not_stop_the_queue() {
+    let desynced    = Desync::new(0);
+    let breaker     = desynced.with_circuit_breaker(2, Duration::from_millis(50));
+
+    assert!(breaker.sync(|_| -> i32 { panic!("Test panic") }) == Err(CircuitBreakerError::Panicked));
+    assert!(desynced.sync(|val| *val) == 0);
+}
+
+#[test]
+fn circuit_opens_after_consecutive_failures() {
+    let desynced    = Desync::new(0);
+    let breaker     = desynced.with_circuit_breaker(2, Duration::from_millis(1000));
+
+    breaker.sync(|_| panic!("Test panic")).ok();
+    assert!(!breaker.is_open());
+    breaker.sync(|_| panic!("Test panic")).ok();
+    assert!(breaker.is_open());
+
+    // Jobs are not run while the circuit is open
+    assert!(breaker.sync(|val| *val = 1) == Err(CircuitBreakerError::CircuitOpen));
+    assert!(breaker.desync(|val| *val = 2) == Err(CircuitBreakerError::CircuitOpen));
+    assert!(desynced.sync(|val| *val) == 0);
+}
+
+#[test]
+fn successes_reset_the_failure_count() {
+    let desynced    = Desync::new(0);
+    let breaker     = desynced.with_circuit_breaker(2, Duration::from_millis(1000));
+
+    breaker.sync(|_| panic!("Test panic")).ok();
+    breaker.sync(|_| { }).unwrap();
+    breaker.sync(|_| panic!("Test panic")).ok();
+
+    assert!(!breaker.is_open());
+}
+
+#[test]
+fn successful_trial_closes_circuit() {
+    let desynced    = Desync::new(0);
+    let breaker     = desynced.with_circuit_breaker(1, Duration::from_millis(50));
+
+    breaker.sync(|_| panic!("Test panic")).ok();
+    assert!(breaker.sync(|_| { }) == Err(CircuitBreakerError::CircuitOpen));
+
+    thread::sleep(Duration::from_millis(60));
+    assert!(breaker.sync(|val| *val = 1) == Ok(()));
+    assert!(!breaker.is_open());
+    assert!(breaker.sync(|val| *val) == Ok(1));
+}
+
+#[test]
+fn failed_trial_reopens_circuit() {
+    let desynced    = Desync::new(0);
+    let breaker     = desynced.with_circuit_breaker(1, Duration::from_millis(50));
+
+    breaker.sync(|_| panic!("Test panic")).ok();
+    thread::sleep(Duration::from_millis(60));
+
+    assert!(breaker.sync(|_| panic!("Test panic")) == Err::<(), _>(CircuitBreakerError::Panicked));
+    assert!(breaker.sync(|_| { }) == Err(CircuitBreakerError::CircuitOpen));
+}
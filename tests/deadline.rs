@@ -0,0 +1,47 @@
+extern crate desync;
+extern crate futures;
+
+use desync::*;
+use futures::executor;
+use futures::future::{self, FutureExt};
+
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+#[test]
+fn deadline_future_completes_in_time() {
+    let desynced    = Desync::new(42);
+    let deadline    = SystemTime::now() + Duration::from_millis(500);
+    let result      = desynced.deadline_future(deadline, |val| future::ready(*val).boxed());
+
+    assert!(executor::block_on(result) == Ok(42));
+}
+
+#[test]
+fn deadline_future_times_out() {
+    let desynced    = Desync::new(42);
+    let deadline    = SystemTime::now() + Duration::from_millis(20);
+
+    desynced.desync(|_| thread::sleep(Duration::from_millis(200)));
+    let result      = desynced.deadline_future(deadline, |val| future::ready(*val).boxed());
+
+    assert!(executor::block_on(result) == Err(DeadlineError::Timeout));
+}
+
+#[test]
+fn deadline_in_the_past_times_out_immediately() {
+    let desynced    = Desync::new(0);
+    let deadline    = SystemTime::now() - Duration::from_millis(100);
+    let result      = desynced.deadline_future(deadline, |val| { *val = 1; future::ready(()).boxed() });
+
+    assert!(executor::block_on(result) == Err(DeadlineError::Timeout));
+    assert!(desynced.sync(|val| *val) == 0);
+}
+
+#[test]
+fn future_is_tagged_with_trace_id() {
+    let desynced    = Desync::new(42);
+    let result      = desynced.future_with_trace_id(1234, |val| future::ready(*val).boxed());
+
+    assert!(executor::block_on(result) == Ok(Traced { trace_id: 1234, result: 42 }));
+}
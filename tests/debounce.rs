@@ -0,0 +1,69 @@
+extern crate desync;
+
+use desync::*;
+
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn update_runs_after_delay() {
+    let desynced    = Desync::new(0);
+    let debounce    = desynced.debounce(Duration::from_millis(50), |val| *val += 1);
+
+    debounce.trigger();
+
+    thread::sleep(Duration::from_millis(10));
+    assert!(desynced.sync(|val| *val) == 0);
+
+    thread::sleep(Duration::from_millis(100));
+    assert!(desynced.sync(|val| *val) == 1);
+}
+
+#[test]
+fn triggers_restart_the_delay() {
+    let desynced    = Desync::new(0);
+    let debounce    = desynced.debounce(Duration::from_millis(50), |val| *val += 1);
+
+    // Keep triggering for longer than the delay
+    for _ in 0..5 {
+        debounce.trigger();
+        thread::sleep(Duration::from_millis(20));
+    }
+
+    assert!(desynced.sync(|val| *val) == 0);
+
+    // The update runs once after the triggers stop
+    thread::sleep(Duration::from_millis(100));
+    assert!(desynced.sync(|val| *val) == 1);
+}
+
+#[test]
+fn leading_update_runs_immediately() {
+    let desynced    = Desync::new(0);
+    let debounce    = desynced.debounce_leading(Duration::from_millis(50), |val| *val += 1);
+
+    for _ in 0..5 {
+        debounce.trigger();
+    }
+
+    thread::sleep(Duration::from_millis(10));
+    assert!(desynced.sync(|val| *val) == 1);
+
+    // Can trigger again once the delay has passed
+    thread::sleep(Duration::from_millis(100));
+    debounce.trigger();
+    thread::sleep(Duration::from_millis(10));
+    assert!(desynced.sync(|val| *val) == 2);
+}
+
+#[test]
+fn dropping_handle_discards_pending_update() {
+    let desynced    = Desync::new(0);
+    let debounce    = desynced.debounce(Duration::from_millis(20), |val| *val += 1);
+
+    debounce.trigger();
+    drop(debounce);
+
+    thread::sleep(Duration::from_millis(50));
+    assert!(desynced.sync(|val| *val) == 0);
+}
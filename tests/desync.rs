@@ -2,9 +2,11 @@ extern crate desync;
 extern crate futures;
 
 use desync::Desync;
+use desync::scheduler::TrySyncError;
 
 mod scheduler;
 use self::scheduler::timeout::*;
+use self::scheduler::stress::*;
 
 use futures::prelude::*;
 use futures::future;
@@ -35,6 +37,23 @@ fn retrieve_data_into_local_var() {
     assert!(val == 42);
 }
 
+#[test]
+fn sync_borrows_local_data_while_queue_is_busy() {
+    let desynced    = Desync::new(TestData { val: 0 });
+    let values      = vec![1, 2, 3];
+    let mut total   = 0;
+
+    // The job has to wait for the queue, so it's run on another thread
+    desynced.desync(|_| sleep(Duration::from_millis(20)));
+    desynced.sync(|data| {
+        data.val    = values.iter().sum();
+        total       = data.val;
+    });
+
+    assert!(total == 6);
+    assert!(values == vec![1, 2, 3]);
+}
+
 #[test]
 fn update_data_asynchronously() {
     let desynced = Desync::new(TestData { val: 0 });
@@ -65,6 +84,78 @@ fn update_data_asynchronously_1000_times() {
     }
 }
 
+#[test]
+fn sync_progress_reports_progress() {
+    let desynced    = Desync::new(TestData { val: 0 });
+    let progress    = Arc::new(Mutex::new(vec![]));
+
+    let reported    = Arc::clone(&progress);
+    let result      = desynced.sync_progress(|data, progress| {
+        for step in 1..=4 {
+            data.val += 1;
+            progress(step as f32 / 4.0);
+        }
+
+        // Out of range values are clamped
+        progress(2.0);
+        data.val
+    }, move |amount| reported.lock().unwrap().push(amount));
+
+    assert!(result == 4);
+    assert!(*progress.lock().unwrap() == vec![0.25, 0.5, 0.75, 1.0, 1.0]);
+}
+
+#[test]
+fn shadow_follows_source_updates() {
+    let source = Arc::new(Desync::new((1, "one".to_string())));
+    let shadow = Desync::shadow(&source, |source: &(i32, String)| source.0);
+
+    assert!(shadow.sync(|val| *val) == 1);
+
+    source.desync(|source| source.0 = 2);
+    source.sync(|_| { });
+
+    assert!(shadow.sync(|val| *val) == 2);
+}
+
+#[test]
+fn shadow_updates_stop_once_dropped() {
+    let source = Arc::new(Desync::new(1));
+    let shadow = Desync::shadow(&source, |source: &i32| *source * 10);
+
+    // Writes to the shadow are overwritten by the next update from the source
+    shadow.desync(|val| *val = 0);
+    source.desync(|source| *source = 2);
+    source.sync(|_| { });
+    assert!(shadow.sync(|val| *val) == 20);
+
+    drop(shadow);
+    source.desync(|source| *source = 3);
+    assert!(source.sync(|source| *source) == 3);
+}
+
+#[test]
+fn ensure_started_runs_jobs_without_threads() {
+    timeout(|| {
+        use desync::scheduler::*;
+
+        // A scheduler with no threads can't run any jobs in the background
+        let scheduler = Arc::new(Scheduler::new());
+        scheduler.set_max_threads(0);
+
+        let desynced = Desync::new(TestData { val: 0 });
+        desynced.migrate_scheduler(scheduler);
+
+        desynced.desync(|data| data.val = 42);
+        sleep(Duration::from_millis(20));
+        assert!(desynced.len() == 1);
+
+        desynced.ensure_started();
+        assert!(desynced.is_empty());
+        assert!(desynced.sync(|data| data.val) == 42);
+    }, 500);
+}
+
 #[test]
 fn update_data_with_future() {
     timeout(|| {
@@ -84,6 +175,73 @@ fn update_data_with_future() {
     }, 500);
 }
 
+#[test]
+fn update_data_with_unboxed_future() {
+    timeout(|| {
+        use futures::executor;
+
+        async fn set_val(data: &mut TestData) -> u32 {
+            future::ready(()).await;
+            data.val = 42;
+            data.val
+        }
+
+        let desynced = Desync::new(TestData { val: 0 });
+
+        assert!(executor::block_on(desynced.future_unboxed(set_val)).unwrap() == 42);
+        assert!(executor::block_on(desynced.future_unboxed(async |data: &mut TestData| { data.val += 1; data.val })).unwrap() == 43);
+    }, 500);
+}
+
+#[test]
+fn arc_future_keeps_object_alive() {
+    timeout(|| {
+        use futures::executor;
+
+        let desynced = Arc::new(Desync::new(TestData { val: 0 }));
+
+        desynced.desync(|data| {
+            sleep(Duration::from_millis(100));
+            data.val = 42;
+        });
+
+        // The future holds the only reference to the object once it's been created
+        let future = desynced.arc_future(|data| { Box::pin(future::ready(data.val)) });
+
+        assert!(executor::block_on(future).unwrap() == 42);
+    }, 500);
+}
+
+#[test]
+fn guarded_future_holds_guard_until_complete() {
+    timeout(|| {
+        use futures::executor;
+
+        // Guard that records when it's dropped
+        struct Guard(Arc<Mutex<bool>>);
+        impl Drop for Guard {
+            fn drop(&mut self) { *self.0.lock().unwrap() = true; }
+        }
+
+        let desynced    = Desync::new(TestData { val: 0 });
+        let released    = Arc::new(Mutex::new(false));
+        let guard       = Guard(Arc::clone(&released));
+
+        let job_released = Arc::clone(&released);
+        let future      = desynced.guarded_future(guard, move |data| {
+            async move {
+                // Guard is still held while the job is running
+                sleep(Duration::from_millis(20));
+                data.val = if *job_released.lock().unwrap() { 1 } else { 42 };
+                data.val
+            }.boxed()
+        });
+
+        assert!(executor::block_on(future).unwrap() == 42);
+        assert!(*released.lock().unwrap());
+    }, 500);
+}
+
 #[test]
 fn update_data_with_future_1000_times() {
     // Seems to timeout fairly reliably after signalling the future
@@ -168,6 +326,34 @@ fn wait_for_future() {
     }, 500);
 }
 
+#[test]
+fn after_all_waits_for_every_future() {
+    timeout(|| {
+        use futures::executor;
+        use futures::channel::oneshot;
+
+        let desynced        = Desync::new(vec![]);
+        let (tx1, rx1)      = oneshot::channel();
+        let (tx2, rx2)      = oneshot::channel();
+
+        desynced.desync(|val| val.push(1));
+        let future = desynced.after_all(vec![rx1, rx2], |val, results| {
+            val.extend(results.into_iter().map(|result| result.unwrap()));
+            val.len()
+        });
+        desynced.desync(|val| val.push(4));
+
+        executor::block_on(async {
+            // Results arrive in the order of the futures, not the order they complete in
+            tx2.send(3).unwrap();
+            tx1.send(2).unwrap();
+
+            assert!(future.await == Ok(3));
+            assert!(desynced.sync(|val| val.clone()) == vec![1, 2, 3, 4]);
+        })
+    }, 500);
+}
+
 #[test]
 fn future_and_sync() {
     // This test seems to produce different behaviour if it's run by itself (this sleep tends to force it to run after the other tests and thus fail)
@@ -269,3 +455,1004 @@ fn double_future_and_sync() {
     assert!(initiator_2.sync(|val| { *val }) == Some(2));
     assert!(initiator_1.sync(|val| { *val }) == Some(1));
 }
+
+#[test]
+fn eq_sync_matches_partial_eq() {
+    let a = Desync::new(1);
+    let b = Desync::new(1);
+    let c = Desync::new(2);
+
+    a.desync(|val| { sleep(Duration::from_millis(10)); *val += 1 });
+
+    assert!(a.eq_sync(&a));
+    assert!(a.eq_sync(&c));
+    assert!(c.eq_sync(&a));
+    assert!(!a.eq_sync(&b));
+    assert!(!b.eq_sync(&a));
+}
+
+#[test]
+fn eq_sync_from_two_threads_does_not_deadlock() {
+    timeout(|| {
+        let a = Arc::new(Desync::new(0));
+        let b = Arc::new(Desync::new(0));
+
+        // Compare in both directions from several threads at once
+        run_stress_test(a, 4, 500, Arc::new(move |a| {
+            assert!(a.eq_sync(&b));
+            assert!(b.eq_sync(a));
+        }));
+    }, 2000);
+}
+
+#[test]
+fn wait_for_resolves_immediately_if_true() {
+    timeout(|| {
+        use futures::executor;
+
+        let desynced = Desync::new(42);
+
+        executor::block_on(desynced.wait_for(|val| *val == 42));
+    }, 500);
+}
+
+#[test]
+fn wait_for_resolves_after_update() {
+    timeout(|| {
+        use futures::executor;
+
+        let desynced    = Arc::new(Desync::new(0));
+        let wait        = desynced.wait_for(|val| *val >= 3);
+
+        let updater     = Arc::clone(&desynced);
+        spawn(move || {
+            for _ in 0..3 {
+                sleep(Duration::from_millis(10));
+                updater.desync(|val| *val += 1);
+            }
+        });
+
+        executor::block_on(wait);
+        assert!(desynced.sync(|val| *val) == 3);
+    }, 500);
+}
+
+#[test]
+fn wait_for_survives_panicking_predicate() {
+    timeout(|| {
+        use futures::executor;
+
+        let desynced    = Desync::new(0);
+        let panicking   = desynced.wait_for(|val| if *val == 1 { panic!("Predicate panic") } else { false });
+        let waiting     = desynced.wait_for(|val| *val >= 2);
+
+        // The panicking predicate is removed, completing its future, and the other waiter is unaffected
+        desynced.desync(|val| *val = 1);
+        executor::block_on(panicking);
+
+        desynced.desync(|val| *val = 2);
+        executor::block_on(waiting);
+        executor::block_on(desynced.wait_for(|val| *val == 2));
+    }, 500);
+}
+
+#[test]
+fn run_if_only_runs_when_predicate_is_true() {
+    timeout(|| {
+        let desynced = Desync::new(1);
+
+        desynced.run_if(|val| *val == 1, |val| *val = 2);
+        desynced.run_if(|val| *val == 1, |val| *val = 3);
+
+        assert!(desynced.sync(|val| *val) == 2);
+    }, 500);
+}
+
+#[test]
+fn run_unless_only_runs_when_predicate_is_false() {
+    timeout(|| {
+        let desynced = Desync::new(1);
+
+        desynced.run_unless(|val| *val == 1, |val| *val = 2);
+        desynced.run_unless(|val| *val == 2, |val| *val = 3);
+
+        assert!(desynced.sync(|val| *val) == 3);
+    }, 500);
+}
+
+#[test]
+fn run_if_is_atomic() {
+    timeout(|| {
+        // Many threads try to claim the same value: only one should succeed
+        let desynced = Arc::new(Desync::new((false, 0)));
+
+        run_stress_test(Arc::clone(&desynced), 8, 10, Arc::new(|desynced| {
+            desynced.run_if(|(claimed, _)| !*claimed, |(claimed, claims)| { *claimed = true; *claims += 1; })
+        }));
+
+        assert!(desynced.sync(|(_, claims)| *claims) == 1);
+    }, 2000);
+}
+
+#[test]
+fn migrate_scheduler_moves_jobs_to_new_scheduler() {
+    timeout(|| {
+        use desync::scheduler::{Scheduler, SchedulerConfig};
+        use std::sync::mpsc::*;
+
+        let dedicated   = Arc::new(Scheduler::new_with_config(SchedulerConfig { thread_name_fn: Some(Box::new(|index| format!("dedicated-{}", index))) }));
+        let desynced    = Desync::new(0);
+
+        desynced.desync(|val| { sleep(Duration::from_millis(20)); *val += 1; });
+        desynced.migrate_scheduler(Arc::clone(&dedicated));
+
+        // Jobs scheduled before the migration have finished
+        assert!(desynced.sync(|val| *val) == 1);
+
+        // New background jobs run on the threads belonging to the new scheduler
+        let (tx, rx) = channel();
+        desynced.desync(move |_| { tx.send(current().name().map(|name| name.to_string())).unwrap(); });
+
+        assert!(rx.recv().unwrap() == Some("dedicated-0".to_string()));
+    }, 500);
+}
+
+#[test]
+fn new_with_scheduler_runs_jobs_on_scheduler() {
+    timeout(|| {
+        use desync::scheduler::{Scheduler, SchedulerConfig};
+        use std::sync::mpsc::*;
+
+        let io_pool     = Arc::new(Scheduler::new_with_config(SchedulerConfig { thread_name_fn: Some(Box::new(|index| format!("io-{}", index))) }));
+        let desynced    = Desync::new_with_scheduler(0, Arc::clone(&io_pool));
+
+        let (tx, rx) = channel();
+        desynced.desync(move |_| { tx.send(current().name().map(|name| name.to_string())).unwrap(); });
+
+        assert!(rx.recv().unwrap() == Some("io-0".to_string()));
+    }, 500);
+}
+
+#[test]
+fn future_with_fallback_runs_job_when_not_overloaded() {
+    timeout(|| {
+        use futures::executor;
+
+        let desynced = Desync::new(42);
+
+        assert!(executor::block_on(desynced.future_with_fallback(4, |val| future::ready(*val).boxed(), || 0)) == 42);
+    }, 500);
+}
+
+#[test]
+fn future_with_fallback_sheds_load_when_overloaded() {
+    timeout(|| {
+        use futures::executor;
+
+        let desynced = Desync::new(42);
+
+        // Block the queue and build up a backlog
+        desynced.desync(|_| sleep(Duration::from_millis(100)));
+        for _ in 0..4 {
+            desynced.desync(|val| *val += 1);
+        }
+
+        let start   = Instant::now();
+        let result  = executor::block_on(desynced.future_with_fallback(4, |val| future::ready(*val).boxed(), || 0));
+
+        // The fallback is returned straight away, and the job never runs
+        assert!(result == 0);
+        assert!(start.elapsed() < Duration::from_millis(50));
+        assert!(desynced.sync(|val| *val) == 46);
+    }, 500);
+}
+
+#[test]
+fn into_mutex_waits_for_pending_jobs() {
+    timeout(|| {
+        let desynced = Desync::new(0);
+
+        desynced.desync(|val| { sleep(Duration::from_millis(20)); *val += 1; });
+        let mutex = desynced.into_mutex();
+
+        assert!(*mutex.lock().unwrap() == 1);
+    }, 500);
+}
+
+#[test]
+fn round_trip_through_mutex() {
+    timeout(|| {
+        let desynced    = Desync::from_mutex(Mutex::new(vec![1, 2]));
+        desynced.desync(|val| val.push(3));
+
+        let mutex       = desynced.into_mutex();
+        mutex.lock().unwrap().push(4);
+
+        let desynced    = Desync::from_mutex(mutex);
+        assert!(desynced.sync(|val| val.clone()) == vec![1, 2, 3, 4]);
+    }, 500);
+}
+
+#[test]
+fn into_mutex_does_not_call_drop_handler() {
+    timeout(|| {
+        let dropped     = Arc::new(Mutex::new(false));
+        let handler     = Arc::clone(&dropped);
+        let desynced    = Desync::with_drop_handler(1, move |_| *handler.lock().unwrap() = true);
+
+        let mutex       = desynced.into_mutex();
+
+        assert!(*mutex.lock().unwrap() == 1);
+        assert!(!*dropped.lock().unwrap());
+    }, 500);
+}
+
+#[test]
+fn into_inner_waits_for_pending_jobs() {
+    timeout(|| {
+        let dropped     = Arc::new(Mutex::new(false));
+        let handler     = Arc::clone(&dropped);
+        let desynced    = Desync::with_drop_handler(0, move |_| *handler.lock().unwrap() = true);
+
+        desynced.desync(|val| { sleep(Duration::from_millis(20)); *val += 1; });
+
+        assert!(desynced.into_inner().unwrap() == 1);
+        assert!(!*dropped.lock().unwrap());
+    }, 500);
+}
+
+#[test]
+fn into_inner_returns_error_after_panic() {
+    timeout(|| {
+        let desynced = Desync::new(0);
+
+        desynced.desync(|val| *val = 1);
+        desynced.desync(|_| { sleep(Duration::from_millis(20)); panic!("Panic on the queue") });
+        desynced.desync(|val| *val = 2);
+
+        // The data can still be recovered from the error, but the job after the panic never runs
+        let result = desynced.into_inner();
+        assert!(result.is_err());
+        assert!(result.unwrap_err().into_inner() == 1);
+    }, 500);
+}
+
+#[test]
+fn into_inner_waits_for_running_job_during_forced_shutdown() {
+    timeout(|| {
+        use desync::scheduler::Scheduler;
+
+        let scheduler   = Arc::new(Scheduler::new());
+        let desynced    = Desync::new_with_scheduler(0, Arc::clone(&scheduler));
+
+        desynced.desync(|val| { sleep(Duration::from_millis(100)); *val = 1; });
+
+        // Forcing the shutdown while into_inner() is waiting must not remove the job it's waiting for
+        let shutdown_scheduler = Arc::clone(&scheduler);
+        let shutdown = spawn(move || {
+            sleep(Duration::from_millis(20));
+            futures::executor::block_on(shutdown_scheduler.shutdown_timeout(Duration::from_millis(1)));
+        });
+
+        assert!(desynced.into_inner().unwrap() == 1);
+        shutdown.join().unwrap();
+    }, 1000);
+}
+
+#[test]
+fn map_runs_pending_jobs_first() {
+    timeout(|| {
+        let order       = Arc::new(Mutex::new(vec![]));
+        let desynced    = Desync::new(1);
+
+        let before      = Arc::clone(&order);
+        desynced.desync(move |val| { sleep(Duration::from_millis(20)); *val += 1; before.lock().unwrap().push("before"); });
+
+        let mapped      = desynced.map(|val| TestData { val });
+
+        let after       = Arc::clone(&order);
+        mapped.desync(move |data| { data.val *= 10; after.lock().unwrap().push("after"); });
+
+        assert!(mapped.sync(|data| data.val) == 20);
+        assert!(*order.lock().unwrap() == vec!["before", "after"]);
+    }, 500);
+}
+
+#[test]
+fn future_stream_relays_items() {
+    timeout(|| {
+        use futures::executor;
+        use futures::stream;
+
+        let desynced    = Desync::new(vec![1, 2, 3]);
+        let items       = desynced.future_stream(|val| stream::iter(val.clone()).boxed());
+
+        assert!(executor::block_on(items.collect::<Vec<_>>()) == vec![1, 2, 3]);
+    }, 500);
+}
+
+#[test]
+fn future_stream_blocks_queue_until_finished() {
+    timeout(|| {
+        use futures::executor;
+        use futures::channel::mpsc;
+
+        let desynced            = Desync::new(0);
+        let (mut sender, rows)  = mpsc::channel(4);
+        let mut items           = desynced.future_stream(move |_| rows.boxed());
+
+        // This job can't run until the stream has finished
+        desynced.desync(|val| *val = 2);
+
+        executor::block_on(async {
+            sender.send(1).await.unwrap();
+            assert!(items.next().await == Some(1));
+            assert!(desynced.try_into_sync(Duration::from_millis(20), |val| *val).is_err());
+
+            drop(sender);
+            assert!(items.next().await.is_none());
+        });
+
+        assert!(desynced.sync(|val| *val) == 2);
+    }, 500);
+}
+
+#[test]
+fn async_map_follows_updates() {
+    timeout(|| {
+        let desynced    = Desync::new(1);
+        let doubled     = desynced.async_map(|val| *val * 2);
+
+        assert!(doubled.sync(|val| *val) == 2);
+
+        desynced.desync(|val| *val = 4);
+        desynced.sync(|_| { });
+
+        // The update is sent to the output after the job on the source has finished
+        assert!(doubled.sync(|val| *val) == 8);
+    }, 500);
+}
+
+#[test]
+fn async_map_calls_map_once_per_update() {
+    timeout(|| {
+        let map_count   = Arc::new(Mutex::new(0));
+        let desynced    = Desync::new(1);
+
+        let counter     = Arc::clone(&map_count);
+        let mapped      = desynced.async_map(move |val| { *counter.lock().unwrap() += 1; *val });
+
+        // The initial value is only generated once
+        assert!(mapped.sync(|val| *val) == 1);
+        assert!(*map_count.lock().unwrap() == 1);
+
+        desynced.desync(|val| *val = 2);
+        desynced.sync(|_| { });
+        assert!(mapped.sync(|val| *val) == 2);
+
+        // One call for the update, and one for the empty sync() job
+        assert!(*map_count.lock().unwrap() == 3);
+    }, 500);
+}
+
+#[test]
+fn async_map_stops_when_output_dropped() {
+    timeout(|| {
+        let map_count   = Arc::new(Mutex::new(0));
+        let desynced    = Desync::new(1);
+
+        let counter     = Arc::clone(&map_count);
+        let mapped      = desynced.async_map(move |val| { *counter.lock().unwrap() += 1; *val });
+
+        desynced.sync(|val| *val = 2);
+        let count_before_drop = *map_count.lock().unwrap();
+        drop(mapped);
+
+        // The observer removes itself when it next runs, then is never called again
+        desynced.sync(|val| *val = 3);
+        desynced.sync(|val| *val = 4);
+        assert!(*map_count.lock().unwrap() == count_before_drop);
+    }, 500);
+}
+
+#[test]
+fn transform_in_place_signals_completion() {
+    timeout(|| {
+        use futures::executor;
+
+        let desynced = Desync::new(TestData { val: 0 });
+
+        desynced.desync(|data| {
+            sleep(Duration::from_millis(50));
+            data.val = 1;
+        });
+
+        let transformed = desynced.transform_in_place(|data| data.val += 41);
+
+        executor::block_on(transformed).unwrap();
+        assert!(desynced.sync(|data| data.val) == 42);
+    }, 500);
+}
+
+#[test]
+fn run_exclusive_cancels_pending_jobs() {
+    timeout(|| {
+        use futures::executor;
+        use std::sync::mpsc;
+
+        let desynced            = Desync::new(vec![]);
+        let (started, wait)     = mpsc::channel();
+
+        desynced.desync(move |data| {
+            started.send(()).unwrap();
+            sleep(Duration::from_millis(50));
+            data.push(1);
+        });
+        wait.recv().unwrap();
+
+        desynced.desync(|data| data.push(2));
+        let pending = desynced.future(|data| { data.push(3); future::ready(()).boxed() });
+
+        desynced.run_exclusive(|data| data.push(4), true);
+
+        assert!(executor::block_on(pending).is_err());
+        assert!(desynced.sync(|data| data.clone()) == vec![1, 4]);
+    }, 500);
+}
+
+#[test]
+fn run_exclusive_pauses_pending_jobs() {
+    timeout(|| {
+        use std::sync::mpsc;
+
+        let desynced            = Desync::new(vec![]);
+        let (started, wait)     = mpsc::channel();
+
+        desynced.desync(move |data| {
+            started.send(()).unwrap();
+            sleep(Duration::from_millis(50));
+            data.push(1);
+        });
+        wait.recv().unwrap();
+
+        desynced.desync(|data| data.push(2));
+        desynced.desync(|data| data.push(3));
+
+        desynced.run_exclusive(|data| data.push(4), false);
+
+        assert!(desynced.sync(|data| data.clone()) == vec![1, 4, 2, 3]);
+    }, 500);
+}
+
+#[test]
+fn drop_handler_receives_data_after_pending_jobs() {
+    timeout(|| {
+        use std::mem;
+
+        let (tx, rx)    = mpsc::channel();
+        let desynced    = Desync::with_drop_handler(TestData { val: 0 }, move |data| tx.send(data.val).unwrap());
+
+        desynced.desync(|data| {
+            sleep(Duration::from_millis(50));
+            data.val = 42;
+        });
+
+        mem::drop(desynced);
+
+        assert!(rx.recv().unwrap() == 42);
+    }, 500);
+}
+
+#[test]
+fn batch_future_is_faster_than_individual_futures() {
+    timeout(|| {
+        use futures::executor;
+
+        let desynced    = Desync::new(0u64);
+
+        // Schedule 1000 individual futures
+        let start       = Instant::now();
+        let individual  = (0..1000u64).map(|item| desynced.future(move |total| { *total += item; future::ready(item * 2).boxed() })).collect::<Vec<_>>();
+        let individual  = executor::block_on(future::join_all(individual)).into_iter().map(|result| result.unwrap()).collect::<Vec<_>>();
+        let individual_time = start.elapsed();
+
+        // Schedule the same items as a batch
+        let start       = Instant::now();
+        let batch       = desynced.batch_future((0..1000u64).collect(), |total, items| items.into_iter().map(|item| { *total += item; item * 2 }).collect());
+        let batch       = executor::block_on(batch).unwrap();
+        let batch_time  = start.elapsed();
+
+        assert!(individual == batch);
+        assert!(desynced.sync(|total| *total) == 999000);
+        assert!(batch_time < individual_time, "Batch took {:?}, individual futures took {:?}", batch_time, individual_time);
+    }, 5000);
+}
+
+#[test]
+fn try_into_sync_succeeds() {
+    timeout(|| {
+        let desynced = Desync::new(TestData { val: 0 });
+
+        desynced.desync(|data| data.val = 42);
+
+        assert!(desynced.try_into_sync(Duration::from_millis(100), |data| data.val) == Ok(42));
+    }, 500);
+}
+
+#[test]
+fn try_into_sync_times_out() {
+    timeout(|| {
+        use desync::SyncFailed;
+
+        let desynced = Desync::new(TestData { val: 0 });
+
+        desynced.desync(|_| sleep(Duration::from_millis(200)));
+
+        assert!(desynced.try_into_sync(Duration::from_millis(10), |data| data.val) == Err(SyncFailed::Timeout));
+    }, 1000);
+}
+
+#[test]
+fn try_into_sync_detects_panicked_queue() {
+    timeout(|| {
+        use desync::SyncFailed;
+        use std::mem;
+
+        let desynced = Desync::new(TestData { val: 0 });
+
+        desynced.desync(|_| panic!("Panic on the queue"));
+
+        // Detects the panic while waiting, and also if the queue is already panicked
+        assert!(desynced.try_into_sync(Duration::from_millis(100), |data| data.val) == Err(SyncFailed::Panicked));
+        assert!(desynced.try_into_sync(Duration::from_millis(100), |data| data.val) == Err(SyncFailed::Panicked));
+
+        // Dropping an object with a panicked queue panics, so leak it instead
+        mem::forget(desynced);
+    }, 500);
+}
+
+#[test]
+fn cancel_waiting_job() {
+    timeout(|| {
+        let desynced = Desync::new(TestData { val: 0 });
+
+        desynced.desync(|_| sleep(Duration::from_millis(50)));
+        let handle = desynced.desync(|data| data.val = 42);
+
+        assert!(!handle.cancel());
+        assert!(desynced.sync(|data| data.val) == 0);
+    }, 500);
+}
+
+#[test]
+fn cancel_started_job() {
+    timeout(|| {
+        let desynced        = Desync::new(TestData { val: 0 });
+        let (send, recv)    = mpsc::channel();
+
+        let handle          = desynced.desync(move |data| { send.send(()).unwrap(); sleep(Duration::from_millis(20)); data.val = 42 });
+        recv.recv().unwrap();
+
+        // Jobs that have started are not stopped
+        assert!(handle.cancel());
+        assert!(desynced.sync(|data| data.val) == 42);
+    }, 500);
+}
+
+#[test]
+fn dropping_handle_does_not_cancel_job() {
+    timeout(|| {
+        let desynced = Desync::new(TestData { val: 0 });
+
+        desynced.desync(|_| sleep(Duration::from_millis(20)));
+        drop(desynced.desync(|data| data.val = 42));
+
+        assert!(desynced.sync(|data| data.val) == 42);
+    }, 500);
+}
+
+#[test]
+fn cancel_and_wait_for_skipped_job() {
+    timeout(|| {
+        use futures::executor;
+
+        let desynced = Desync::new(TestData { val: 0 });
+
+        desynced.desync(|_| sleep(Duration::from_millis(50)));
+        let handle  = desynced.desync(|data| data.val = 42);
+        desynced.desync(|data| data.val += 1);
+
+        // The future completes once the cancelled job has been skipped, so the job after it may not have run yet
+        assert!(!executor::block_on(handle.cancel_and_wait()));
+        assert!(desynced.sync(|data| data.val) == 1);
+    }, 500);
+}
+
+#[test]
+fn try_desync_fails_when_bounded_queue_is_full() {
+    timeout(|| {
+        use desync::scheduler::TryDesyncError;
+
+        let desynced            = Desync::new_bounded(TestData { val: 0 }, 1);
+        let (started, wait)     = mpsc::channel();
+        let (release, blocked)  = mpsc::channel::<()>();
+
+        // The running job doesn't count towards the capacity
+        desynced.desync(move |_| { started.send(()).unwrap(); blocked.recv().unwrap(); });
+        wait.recv().unwrap();
+
+        assert!(desynced.try_desync(|data| data.val += 1).is_ok());
+        assert!(desynced.try_desync(|data| data.val += 1).err() == Some(TryDesyncError::QueueFull));
+
+        release.send(()).unwrap();
+        assert!(desynced.sync(|data| data.val) == 1);
+    }, 500);
+}
+
+#[test]
+fn desync_blocks_when_bounded_queue_is_full() {
+    timeout(|| {
+        let desynced            = Desync::new_bounded(TestData { val: 0 }, 1);
+        let (started, wait)     = mpsc::channel();
+
+        desynced.desync(move |_| { started.send(()).unwrap(); sleep(Duration::from_millis(50)); });
+        wait.recv().unwrap();
+        desynced.desync(|data| data.val += 1);
+
+        // Has to wait for the first job to finish so the second one can start
+        let start = Instant::now();
+        desynced.desync(|data| data.val += 1);
+
+        assert!(start.elapsed() >= Duration::from_millis(30));
+        assert!(desynced.sync(|data| data.val) == 2);
+    }, 500);
+}
+
+#[test]
+fn drop_oldest_discards_waiting_job_when_full() {
+    timeout(|| {
+        use desync::QueueOverflowPolicy;
+
+        let desynced            = Desync::new_bounded_with_policy(vec![], 2, QueueOverflowPolicy::DropOldest);
+        let (started, wait)     = mpsc::channel();
+        let (release, blocked)  = mpsc::channel::<()>();
+
+        desynced.desync(move |_| { started.send(()).unwrap(); blocked.recv().unwrap(); });
+        wait.recv().unwrap();
+
+        // Doesn't block: the first waiting job makes room for the last one
+        desynced.desync(|data| data.push(1));
+        desynced.desync(|data| data.push(2));
+        desynced.desync(|data| data.push(3));
+        assert!(desynced.try_desync(|data| data.push(4)).is_ok());
+
+        release.send(()).unwrap();
+        assert!(desynced.sync(|data| data.clone()) == vec![3, 4]);
+    }, 500);
+}
+
+#[test]
+fn drop_newest_discards_new_job_when_full() {
+    timeout(|| {
+        use desync::QueueOverflowPolicy;
+        use desync::scheduler::TryDesyncError;
+
+        let desynced            = Desync::new_bounded_with_policy(vec![], 2, QueueOverflowPolicy::DropNewest);
+        let (started, wait)     = mpsc::channel();
+        let (release, blocked)  = mpsc::channel::<()>();
+
+        desynced.desync(move |_| { started.send(()).unwrap(); blocked.recv().unwrap(); });
+        wait.recv().unwrap();
+
+        assert!(!desynced.desync(|data| data.push(1)).is_rejected());
+        assert!(!desynced.desync(|data| data.push(2)).is_rejected());
+
+        // The handle reports that the job was discarded
+        let dropped = desynced.desync(|data| data.push(3));
+        assert!(dropped.is_rejected());
+        assert!(!futures::executor::block_on(dropped.cancel_and_wait()));
+        assert!(desynced.try_desync(|data| data.push(4)).err() == Some(TryDesyncError::QueueFull));
+
+        release.send(()).unwrap();
+        assert!(desynced.sync(|data| data.clone()) == vec![1, 2]);
+    }, 500);
+}
+
+#[test]
+fn named_queue_can_be_bounded() {
+    timeout(|| {
+        use desync::scheduler::*;
+
+        let config              = QueueConfig { name: Some("Bounded".to_string()), capacity: Some(1), ..QueueConfig::default() };
+        let desynced            = Desync::new_with_config(TestData { val: 0 }, Arc::new(Scheduler::new()), config);
+        let (started, wait)     = mpsc::channel();
+        let (release, blocked)  = mpsc::channel::<()>();
+
+        desynced.desync(move |_| { started.send(()).unwrap(); blocked.recv().unwrap(); });
+        wait.recv().unwrap();
+
+        assert!(desynced.try_desync(|data| data.val += 1).is_ok());
+        assert!(desynced.try_desync(|data| data.val += 1).err() == Some(TryDesyncError::QueueFull));
+
+        release.send(()).unwrap();
+        assert!(desynced.sync(|data| data.val) == 1);
+    }, 500);
+}
+
+#[test]
+fn try_sync_runs_on_idle_queue() {
+    timeout(|| {
+        let desynced = Desync::new(TestData { val: 0 });
+
+        assert!(desynced.try_sync(|data| { data.val = 42; data.val }) == Ok(42));
+        assert!(desynced.sync(|data| data.val) == 42);
+    }, 500);
+}
+
+#[test]
+fn try_sync_does_not_wait_for_busy_queue() {
+    timeout(|| {
+        let desynced = Desync::new(TestData { val: 0 });
+
+        desynced.desync(|data| { sleep(Duration::from_millis(50)); data.val = 1 });
+
+        // The job is discarded rather than being scheduled
+        assert!(desynced.try_sync(|data| { data.val = 42; data.val }) == Err(TrySyncError::QueueBusy));
+        assert!(desynced.sync(|data| data.val) == 1);
+        assert!(desynced.try_sync(|data| data.val) == Ok(1));
+    }, 500);
+}
+
+#[test]
+fn try_get_reads_idle_queue() {
+    timeout(|| {
+        let desynced = Desync::new(TestData { val: 42 });
+
+        assert!(desynced.try_get(|data| data.val) == Some(42));
+        assert!(desynced.queue_state() == desync::QueueState::Idle);
+    }, 500);
+}
+
+#[test]
+fn try_get_does_not_wait_for_busy_queue() {
+    timeout(|| {
+        let desynced = Desync::new(TestData { val: 0 });
+
+        desynced.desync(|data| { sleep(Duration::from_millis(50)); data.val = 1 });
+
+        assert!(desynced.try_get(|data| data.val).is_none());
+        assert!(desynced.sync(|data| data.val) == 1);
+        assert!(desynced.try_get(|data| data.val) == Some(1));
+    }, 500);
+}
+
+#[test]
+fn panic_in_try_get_leaves_queue_usable() {
+    timeout(|| {
+        use std::panic;
+
+        let desynced = Desync::new(TestData { val: 1 });
+
+        assert!(panic::catch_unwind(panic::AssertUnwindSafe(|| desynced.try_get(|_| panic!("Oh dear")))).is_err());
+        assert!(desynced.sync(|data| data.val) == 1);
+    }, 500);
+}
+
+#[test]
+fn sync_timeout_succeeds() {
+    timeout(|| {
+        let desynced = Desync::new(TestData { val: 0 });
+
+        // Runs straight away on an idle queue, and waits for a busy one
+        assert!(desynced.sync_timeout(Duration::from_millis(100), |data| data.val) == Ok(0));
+
+        desynced.desync(|data| { sleep(Duration::from_millis(20)); data.val = 42 });
+        assert!(desynced.sync_timeout(Duration::from_millis(200), |data| data.val) == Ok(42));
+    }, 500);
+}
+
+#[test]
+fn sync_timeout_times_out() {
+    timeout(|| {
+        use desync::scheduler::SyncTimeout;
+
+        let desynced = Desync::new(TestData { val: 0 });
+
+        desynced.desync(|_| sleep(Duration::from_millis(200)));
+
+        let start = Instant::now();
+        assert!(desynced.sync_timeout(Duration::from_millis(10), |data| data.val) == Err(SyncTimeout::TimedOut));
+        assert!(start.elapsed() < Duration::from_millis(150));
+    }, 1000);
+}
+
+#[test]
+fn sync_timeout_detects_panicked_queue() {
+    timeout(|| {
+        use desync::scheduler::SyncTimeout;
+        use std::mem;
+
+        let desynced = Desync::new(TestData { val: 0 });
+
+        desynced.desync(|_| panic!("Panic on the queue"));
+
+        // Detects the panic while waiting, and also if the queue is already panicked
+        assert!(desynced.sync_timeout(Duration::from_millis(100), |data| data.val) == Err(SyncTimeout::QueuePanicked));
+        assert!(desynced.sync_timeout(Duration::from_millis(100), |data| data.val) == Err(SyncTimeout::QueuePanicked));
+
+        // Dropping an object with a panicked queue panics, so leak it instead
+        mem::forget(desynced);
+    }, 500);
+}
+
+#[test]
+fn iter_desync_processes_all_items_in_one_job() {
+    timeout(|| {
+        let desynced = Desync::new(vec![]);
+
+        desynced.iter_desync(0..100, |list, item| list.push(item));
+        desynced.desync(|list| list.push(100));
+
+        assert!(desynced.sync(|list| list.clone()) == (0..=100).collect::<Vec<_>>());
+    }, 500);
+}
+
+#[test]
+fn future_desync_runs_in_background() {
+    timeout(|| {
+        use futures::executor;
+
+        let desynced = Desync::new(TestData { val: 0 });
+
+        desynced.desync(|data| data.val = 42);
+        let result = desynced.future_desync(|data| future::ready(data.val).boxed());
+
+        assert!(executor::block_on(result) == Ok(42));
+    }, 500);
+}
+
+#[test]
+fn stress_desync_and_sync_from_many_threads() {
+    timeout(|| {
+        let desynced = Arc::new(Desync::new(TestData { val: 0 }));
+
+        run_stress_test(Arc::clone(&desynced), 8, 200, Arc::new(|desynced| {
+            desynced.desync(|data| data.val += 1);
+            desynced.sync(|data| data.val += 1);
+        }));
+
+        assert!(desynced.sync(|data| data.val) == 8*200*2);
+    }, 5000);
+}
+
+#[test]
+fn future_batch_returns_results_in_order() {
+    timeout(|| {
+        use futures::executor;
+        use futures::future::BoxFuture;
+
+        type BatchJob = Box<dyn for<'a> FnOnce(&'a mut TestData) -> BoxFuture<'a, u32>+Send>;
+
+        let desynced    = Desync::new(TestData { val: 1 });
+        let jobs: Vec<BatchJob> = vec![
+            Box::new(|data| { data.val += 1; future::ready(data.val).boxed() }),
+            Box::new(|data| { data.val *= 10; future::ready(data.val).boxed() }),
+            Box::new(|data| async move { data.val + 1 }.boxed()),
+        ];
+
+        assert!(executor::block_on(desynced.future_batch(jobs)) == Ok(vec![2, 20, 21]));
+    }, 500);
+}
+
+#[test]
+fn catch_unwind_returns_panic_payload() {
+    timeout(|| {
+        let desynced = Desync::new(TestData { val: 0 });
+
+        let result = desynced.catch_unwind(|data| { data.val = 1; panic!("Panic on the queue") });
+        assert!(result.unwrap_err().downcast_ref::<&str>() == Some(&"Panic on the queue"));
+
+        // The queue can still run jobs
+        desynced.desync(|data| data.val += 1);
+        assert!(desynced.sync(|data| data.val) == 2);
+        assert!(desynced.catch_unwind(|data| data.val).ok() == Some(2));
+    }, 500);
+}
+
+#[test]
+fn catch_unwind_resets_panicked_queue() {
+    timeout(|| {
+        let desynced = Desync::new(TestData { val: 0 });
+
+        desynced.desync(|_| { sleep(Duration::from_millis(20)); panic!("Panic on the queue") });
+        desynced.desync(|data| data.val = 42);
+
+        // Wait for the panic
+        while desynced.queue_state() != desync::QueueState::Panicked {
+            sleep(Duration::from_millis(5));
+        }
+
+        // The job that was waiting when the queue panicked is discarded
+        assert!(desynced.catch_unwind(|data| data.val).ok() == Some(0));
+        assert!(desynced.sync(|data| data.val) == 0);
+    }, 500);
+}
+
+#[test]
+fn share_queue_runs_jobs_in_order() {
+    timeout(|| {
+        let log         = Arc::new(Mutex::new(vec![]));
+        let first       = Desync::new(TestData { val: 1 });
+        let second      = first.share_queue(TestData { val: 2 });
+
+        // The slow job on the first object holds up the job on the second
+        let first_log   = Arc::clone(&log);
+        first.desync(move |data| { sleep(Duration::from_millis(20)); first_log.lock().unwrap().push(data.val); });
+        let second_log  = Arc::clone(&log);
+        second.desync(move |data| second_log.lock().unwrap().push(data.val));
+
+        second.sync(|_| { });
+        assert!(*log.lock().unwrap() == vec![1, 2]);
+    }, 500);
+}
+
+#[test]
+fn eq_sync_with_shared_queue() {
+    timeout(|| {
+        let first   = Desync::new(1);
+        let second  = first.share_queue(1);
+        let third   = first.share_queue(2);
+
+        assert!(first.eq_sync(&second));
+        assert!(!first.eq_sync(&third));
+    }, 500);
+}
+
+#[test]
+fn desync_batch_is_not_interleaved() {
+    timeout(|| {
+        let desynced    = Arc::new(Desync::new(vec![]));
+
+        // Another thread schedules jobs as fast as it can while the batch is being added
+        let other       = Arc::clone(&desynced);
+        let spammer     = spawn(move || {
+            for _ in 0..1000 {
+                other.desync(|log| log.push(0));
+            }
+        });
+
+        desynced.desync_batch((1..=100).map(|val| move |log: &mut Vec<i32>| log.push(val)));
+        spammer.join().unwrap();
+
+        // The jobs in the batch all run one after the other
+        let log     = desynced.sync(|log| log.clone());
+        let start   = log.iter().position(|val| *val == 1).unwrap();
+        assert!(log[start..start+100] == (1..=100).collect::<Vec<_>>()[..]);
+    }, 2000);
+}
+
+#[test]
+fn debug_output_shows_data_when_idle() {
+    timeout(|| {
+        let desynced = Desync::new(TestData { val: 42 });
+
+        assert!(format!("{:?}", desynced) == "Desync { state: Idle, data: TestData { val: 42 } }");
+    }, 500);
+}
+
+#[test]
+fn debug_output_shows_pending_jobs_when_busy() {
+    timeout(|| {
+        let desynced                = Desync::new(TestData { val: 42 });
+        let (started, wait_started) = mpsc::channel();
+        let (release, wait_release) = mpsc::channel::<()>();
+
+        desynced.desync(move |_| { started.send(()).unwrap(); wait_release.recv().ok(); });
+        desynced.desync(|_| { });
+        wait_started.recv().unwrap();
+
+        assert!(format!("{:?}", desynced) == "Desync { state: Running, pending_jobs: 1 }");
+
+        release.send(()).unwrap();
+    }, 500);
+}
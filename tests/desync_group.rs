@@ -0,0 +1,52 @@
+extern crate desync;
+extern crate futures;
+
+use desync::*;
+
+use futures::executor;
+use futures::future::{FutureExt};
+
+use std::thread;
+use std::sync::*;
+use std::time::{Duration, Instant};
+
+fn create_group(count: usize) -> DesyncGroup<usize> {
+    DesyncGroup::new((0..count).map(|index| Arc::new(Desync::new(index))).collect())
+}
+
+#[test]
+fn desync_all_updates_every_member() {
+    let group = create_group(4);
+
+    group.desync_all(Arc::new(|val| *val += 10));
+
+    let values = group.members().iter().map(|member| member.sync(|val| *val)).collect::<Vec<_>>();
+    assert!(values == vec![10, 11, 12, 13]);
+}
+
+#[test]
+fn sync_all_returns_results_in_order() {
+    let group   = create_group(4);
+    let results = group.sync_all(Arc::new(|val| *val * 2));
+
+    assert!(results == vec![0, 2, 4, 6]);
+}
+
+#[test]
+fn sync_all_runs_in_parallel() {
+    let group   = create_group(4);
+    let start   = Instant::now();
+
+    group.sync_all(Arc::new(|_| thread::sleep(Duration::from_millis(100))));
+
+    // Would take 400ms if the jobs ran one after the other
+    assert!(start.elapsed() < Duration::from_millis(300));
+}
+
+#[test]
+fn future_all_returns_results_in_order() {
+    let group   = create_group(3);
+    let results = group.future_all(Arc::new(|val: &mut usize| { *val += 1; futures::future::ready(*val).boxed() }));
+
+    assert!(executor::block_on(results) == Ok(vec![1, 2, 3]));
+}
@@ -0,0 +1,53 @@
+extern crate desync;
+extern crate futures;
+
+use desync::*;
+
+use futures::executor;
+use futures::future::{FutureExt};
+
+use std::thread;
+use std::time::{Duration};
+
+#[test]
+fn round_robin_sends_jobs_to_each_member_in_turn() {
+    let pool = DesyncPool::new(3, Vec::new);
+
+    for job in 0..6 {
+        pool.desync(move |jobs| jobs.push(job));
+    }
+
+    let jobs = pool.iter().map(|member| member.sync(|jobs| jobs.clone())).collect::<Vec<_>>();
+    assert!(jobs == vec![vec![0, 3], vec![1, 4], vec![2, 5]]);
+}
+
+#[test]
+fn sync_and_future_run_on_pool_members() {
+    let pool = DesyncPool::new(2, || 1);
+
+    assert!(pool.sync(|val| { *val += 1; *val }) == 2);
+    assert!(executor::block_on(pool.future(|val| async move { *val += 2; *val }.boxed())) == Ok(3));
+
+    assert!(pool.len() == 2);
+    assert!(pool[0].sync(|val| *val) == 2);
+    assert!(pool[1].sync(|val| *val) == 3);
+}
+
+#[test]
+fn least_loaded_avoids_busy_members() {
+    let pool = DesyncPool::new_least_loaded(2, || 0);
+
+    // Keep the first member busy, with more jobs waiting than the other member will get
+    pool[0].desync(|_| thread::sleep(Duration::from_millis(100)));
+    for _ in 0..8 {
+        pool[0].desync(|_| { });
+    }
+    thread::sleep(Duration::from_millis(10));
+
+    for _ in 0..4 {
+        pool.desync(|count| *count += 1);
+    }
+
+    assert!(pool[1].sync(|count| *count) == 4);
+    assert!(pool[0].sync(|count| *count) == 0);
+}
@@ -0,0 +1,78 @@
+extern crate desync;
+
+use desync::*;
+
+use std::sync::*;
+use std::sync::mpsc;
+
+///
+/// Test data that emits its value whenever it's odd
+///
+struct OddNumbers(u32);
+
+impl Emit for OddNumbers {
+    type Output = u32;
+
+    fn emit(&self) -> Option<u32> {
+        if self.0 % 2 == 1 { Some(self.0) } else { None }
+    }
+}
+
+///
+/// Event type that can be created from the output of `OddNumbers`
+///
+#[derive(PartialEq, Debug)]
+struct OddEvent(u64);
+
+impl From<u32> for OddEvent {
+    fn from(val: u32) -> OddEvent { OddEvent(val as u64) }
+}
+
+fn channel_sink<M: 'static+Send>() -> (Arc<dyn Fn(M)+Send+Sync>, mpsc::Receiver<M>) {
+    let (sender, receiver)  = mpsc::channel();
+    let sender              = Mutex::new(sender);
+
+    (Arc::new(move |event| { sender.lock().unwrap().send(event).ok(); }), receiver)
+}
+
+#[test]
+fn sink_receives_events_after_jobs() {
+    let desynced            = Desync::new(OddNumbers(0));
+    let (sink, events)      = channel_sink::<u32>();
+
+    desynced.add_sink(sink);
+    for _ in 0..4 {
+        desynced.desync(|val| val.0 += 1);
+    }
+    desynced.sync(|_| { });
+
+    assert!(events.try_iter().collect::<Vec<_>>() == vec![1, 3]);
+}
+
+#[test]
+fn sinks_convert_events() {
+    let desynced            = Desync::new(OddNumbers(0));
+    let (sink, events)      = channel_sink::<OddEvent>();
+
+    desynced.add_sink(sink);
+    desynced.sync(|val| val.0 = 5);
+
+    assert!(events.try_iter().collect::<Vec<_>>() == vec![OddEvent(5)]);
+}
+
+#[test]
+fn multiple_sinks_can_be_added_and_removed() {
+    let desynced                = Desync::new(OddNumbers(0));
+    let (first, first_events)   = channel_sink::<u32>();
+    let (second, second_events) = channel_sink::<u32>();
+
+    let first_id = desynced.add_sink(first);
+    desynced.add_sink(second);
+
+    desynced.sync(|val| val.0 = 1);
+    desynced.remove_sink(first_id);
+    desynced.sync(|val| val.0 = 3);
+
+    assert!(first_events.try_iter().collect::<Vec<_>>() == vec![1]);
+    assert!(second_events.try_iter().collect::<Vec<_>>() == vec![1, 3]);
+}
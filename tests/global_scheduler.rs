@@ -0,0 +1,44 @@
+extern crate desync;
+
+use desync::*;
+use desync::scheduler::*;
+
+use std::thread;
+use std::sync::*;
+use std::time::{Duration, Instant};
+
+// Replacing the global scheduler affects every test in the same process, so this is the only test in this file
+#[test]
+fn replace_global_scheduler() {
+    let old_desync  = Desync::new(0);
+    let finished    = Arc::new(Mutex::new(false));
+
+    // Replacing the scheduler should wait for this job to finish
+    let job_finished = Arc::clone(&finished);
+    old_desync.desync(move |_| {
+        thread::sleep(Duration::from_millis(50));
+        *job_finished.lock().unwrap() = true;
+    });
+
+    let old_scheduler   = scheduler();
+    let start           = Instant::now();
+    Scheduler::global_replace(Scheduler::new_with_config(SchedulerConfig { thread_name_fn: Some(Box::new(|index| format!("replacement-{}", index))) }));
+
+    assert!(*finished.lock().unwrap());
+    assert!(start.elapsed() >= Duration::from_millis(40));
+
+    // The old scheduler's threads have exited
+    let metrics = old_scheduler.metrics();
+    assert!(metrics.active_thread_count + metrics.idle_thread_count == 0);
+
+    // New objects use the replacement scheduler
+    let new_desync  = Desync::new(0);
+    let (tx, rx)    = mpsc::channel();
+    new_desync.desync(move |_| { tx.send(thread::current().name().map(|name| name.to_string())).unwrap(); });
+
+    assert!(rx.recv().unwrap() == Some("replacement-0".to_string()));
+
+    // Old objects keep working on the old scheduler
+    old_desync.desync(|val| *val = 42);
+    assert!(old_desync.sync(|val| *val) == 42);
+}
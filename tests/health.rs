@@ -0,0 +1,191 @@
+extern crate desync;
+extern crate futures;
+
+use desync::*;
+use desync::scheduler::QueueState;
+
+use futures::executor;
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn idle_object_is_healthy() {
+    let desynced    = Desync::new(0);
+    desynced.sync(|val| *val = 1);
+
+    let health      = desynced.health_check();
+
+    assert!(health.state == QueueState::Idle);
+    assert!(health.pending_jobs == 0);
+    assert!(health.last_job_completed_at.is_some());
+    assert!(health.is_responsive);
+}
+
+#[test]
+fn busy_object_is_unresponsive() {
+    let desynced    = Desync::new(0);
+
+    desynced.desync(|_| thread::sleep(Duration::from_millis(300)));
+    desynced.desync(|val| *val = 1);
+    thread::sleep(Duration::from_millis(20));
+
+    let health      = desynced.health_check();
+
+    assert!(health.state == QueueState::Running);
+    assert!(health.pending_jobs == 1);
+    assert!(!health.is_responsive);
+}
+
+#[test]
+fn timeout_on_idle_completes_when_idle() {
+    let desynced    = Desync::new(0);
+    let start       = Instant::now();
+
+    executor::block_on(desynced.timeout_on_idle(Duration::from_millis(50)));
+
+    assert!(start.elapsed() >= Duration::from_millis(50));
+}
+
+#[test]
+fn timeout_on_idle_resets_when_jobs_are_scheduled() {
+    use std::sync::*;
+    let desynced    = Arc::new(Desync::new(0));
+    let start       = Instant::now();
+    let idle        = desynced.timeout_on_idle(Duration::from_millis(50));
+
+    // Keep the object busy for a while
+    let updater     = Arc::clone(&desynced);
+    let updates     = thread::spawn(move || {
+        for _ in 0..5 {
+            thread::sleep(Duration::from_millis(20));
+            updater.desync(|val| *val += 1);
+        }
+    });
+
+    executor::block_on(idle);
+
+    // The last update is at around 100ms, so the object shouldn't be considered idle until around 150ms
+    assert!(start.elapsed() >= Duration::from_millis(140));
+    updates.join().unwrap();
+}
+
+#[test]
+fn len_counts_waiting_jobs() {
+    let desynced    = Desync::new(0);
+    assert!(desynced.is_empty());
+    assert!(desynced.is_empty());
+
+    desynced.desync(|_| thread::sleep(Duration::from_millis(100)));
+    desynced.desync(|val| *val += 1);
+    desynced.desync(|val| *val += 1);
+    thread::sleep(Duration::from_millis(20));
+
+    // The first job is running, so it isn't counted
+    assert!(!desynced.is_empty());
+    assert!(desynced.len() == 2);
+
+    desynced.sync(|_| { });
+    assert!(desynced.is_empty());
+}
+
+#[test]
+fn queue_state_reports_running_jobs() {
+    let desynced    = Desync::new(0);
+    assert!(desynced.is_idle());
+    assert!(desynced.queue_state() == QueueState::Idle);
+
+    desynced.desync(|_| thread::sleep(Duration::from_millis(100)));
+    desynced.desync(|val| *val += 1);
+    thread::sleep(Duration::from_millis(20));
+
+    assert!(!desynced.is_idle());
+    assert!(desynced.queue_state() == QueueState::Running);
+    assert!(desynced.pending_job_count() == 1);
+
+    desynced.sync(|_| { });
+    assert!(desynced.pending_job_count() == 0);
+}
+
+#[test]
+fn drain_waits_for_scheduled_jobs() {
+    let desynced    = Desync::new(0);
+
+    desynced.desync(|val| { thread::sleep(Duration::from_millis(20)); *val += 1; });
+    desynced.desync(|val| *val += 1);
+
+    executor::block_on(desynced.drain());
+    assert!(desynced.try_sync(|val| *val) == Ok(2));
+}
+
+#[test]
+fn drain_completes_if_queue_panics() {
+    let desynced    = Desync::new(0);
+
+    desynced.desync(|_| { thread::sleep(Duration::from_millis(20)); panic!("Panic on the queue") });
+    executor::block_on(desynced.drain());
+
+    assert!(desynced.queue_state() == QueueState::Panicked);
+    std::mem::forget(desynced);
+}
+
+#[test]
+fn wait_empty_waits_for_jobs() {
+    let desynced    = Desync::new(0);
+
+    for _ in 0..10 {
+        desynced.desync(|val| { thread::sleep(Duration::from_millis(5)); *val += 1 });
+    }
+
+    executor::block_on(desynced.wait_empty());
+
+    assert!(desynced.is_empty());
+    assert!(desynced.sync(|val| *val) == 10);
+}
+
+#[test]
+fn wait_empty_completes_immediately_when_empty() {
+    let desynced    = Desync::new(0);
+    let start       = Instant::now();
+
+    executor::block_on(desynced.wait_empty());
+
+    assert!(start.elapsed() < Duration::from_millis(50));
+}
+
+#[test]
+fn on_idle_completes_once_jobs_have_finished() {
+    let desynced    = Desync::new(0);
+
+    for _ in 0..10 {
+        desynced.desync(|val| { thread::sleep(Duration::from_millis(5)); *val += 1 });
+    }
+
+    executor::block_on(desynced.on_idle());
+
+    assert!(desynced.is_empty());
+    assert!(desynced.try_sync(|val| *val) == Ok(10));
+}
+
+#[test]
+fn on_idle_completes_immediately_when_idle() {
+    let desynced    = Desync::new(0);
+    let start       = Instant::now();
+
+    executor::block_on(desynced.on_idle());
+
+    assert!(start.elapsed() < Duration::from_millis(50));
+}
+
+#[test]
+fn on_idle_completes_when_queue_panics() {
+    let desynced    = Desync::new(0);
+
+    desynced.desync(|_| { thread::sleep(Duration::from_millis(10)); panic!("Oh dear"); });
+    executor::block_on(desynced.on_idle());
+
+    assert!(desynced.queue_state() == QueueState::Panicked);
+
+    // Dropping an object with a panicked queue panics, so leak it instead
+    std::mem::forget(desynced);
+}
@@ -0,0 +1,43 @@
+extern crate desync;
+extern crate futures;
+
+use desync::*;
+use futures::executor;
+
+#[test]
+fn records_snapshot_after_each_job() {
+    let desynced    = Desync::new(0);
+    let history     = desynced.history::<10>();
+
+    history.desync(|val| *val = 1);
+    history.desync(|val| *val = 2);
+    history.sync(|val| *val = 3);
+
+    assert!(executor::block_on(history.all_snapshots()) == vec![0, 1, 2, 3]);
+    assert!(executor::block_on(history.nth_snapshot(0)) == Some(3));
+    assert!(executor::block_on(history.nth_snapshot(3)) == Some(0));
+    assert!(executor::block_on(history.nth_snapshot(4)).is_none());
+}
+
+#[test]
+fn discards_old_snapshots() {
+    let desynced    = Desync::new(0);
+    let history     = desynced.history::<3>();
+
+    for x in 1..10 {
+        history.desync(move |val| *val = x);
+    }
+
+    assert!(executor::block_on(history.all_snapshots()) == vec![7, 8, 9]);
+}
+
+#[test]
+fn jobs_on_desync_are_not_recorded() {
+    let desynced    = Desync::new(0);
+    let history     = desynced.history::<10>();
+
+    desynced.desync(|val| *val = 1);
+    history.desync(|val| *val += 1);
+
+    assert!(executor::block_on(history.all_snapshots()) == vec![0, 2]);
+}
@@ -0,0 +1,47 @@
+extern crate desync;
+
+use desync::*;
+
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn lazy_job_runs_when_committed() {
+    let desynced    = Desync::new(0);
+    let lazy        = desynced.lazy_desync(|val| *val = 42);
+
+    desynced.desync(|val| *val = 1);
+    assert!(desynced.sync(|val| *val) == 1);
+
+    lazy.commit();
+    assert!(desynced.sync(|val| *val) == 42);
+}
+
+#[test]
+fn cancelled_job_does_not_run() {
+    let desynced    = Desync::new(0);
+    let lazy        = desynced.lazy_desync(|val| *val = 42);
+
+    lazy.cancel();
+    assert!(desynced.sync(|val| *val) == 0);
+}
+
+#[test]
+fn commit_all_runs_jobs_in_order() {
+    let first       = Desync::new(vec![]);
+    let second      = Desync::new(vec![]);
+
+    // Keep the first queue busy so the jobs are queued up behind this one
+    first.desync(|_| thread::sleep(Duration::from_millis(20)));
+
+    let handles     = vec![
+        first.lazy_desync(|list| list.push(1)),
+        first.lazy_desync(|list| list.push(2)),
+        second.lazy_desync(|list| list.push(3)),
+        first.lazy_desync(|list| list.push(4)),
+    ];
+    LazyHandle::commit_all(handles);
+
+    assert!(first.sync(|list| list.clone()) == vec![1, 2, 4]);
+    assert!(second.sync(|list| list.clone()) == vec![3]);
+}
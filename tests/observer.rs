@@ -0,0 +1,88 @@
+extern crate desync;
+
+use desync::*;
+
+use std::sync::*;
+use std::thread;
+use std::time::{Duration};
+
+#[test]
+fn observer_keeps_running_total() {
+    let desynced    = Desync::new(0);
+    let totals      = Arc::new(Mutex::new(vec![]));
+
+    let observed    = Arc::clone(&totals);
+    let _observer   = desynced.observe_with_context(0, move |val: &i32, total: &mut i32| {
+        *total += *val;
+        observed.lock().unwrap().push(*total);
+    });
+
+    desynced.desync(|val| *val = 1);
+    desynced.desync(|val| *val = 2);
+    desynced.sync(|val| *val = 3);
+
+    assert!(*totals.lock().unwrap() == vec![0, 1, 3, 6]);
+}
+
+#[test]
+fn dropping_handle_stops_observer() {
+    let desynced    = Desync::new(0);
+    let calls       = Arc::new(Mutex::new(0));
+
+    let observed    = Arc::clone(&calls);
+    let observer    = desynced.observe_with_context((), move |_: &i32, _: &mut ()| *observed.lock().unwrap() += 1);
+
+    desynced.sync(|val| *val = 1);
+    drop(observer);
+    desynced.sync(|val| *val = 2);
+    desynced.sync(|val| *val = 3);
+
+    assert!(*calls.lock().unwrap() == 2);
+}
+
+#[test]
+fn context_is_dropped_once_observer_stops() {
+    let desynced    = Desync::new(0);
+    let context     = Arc::new(());
+
+    let observer    = desynced.observe_with_context(Arc::clone(&context), |_: &i32, _: &mut Arc<()>| { });
+    desynced.sync(|_| { });
+    assert!(Arc::strong_count(&context) == 2);
+
+    drop(observer);
+    desynced.sync(|_| { });
+    assert!(Arc::strong_count(&context) == 1);
+}
+
+#[test]
+fn observe_calls_back_after_changes() {
+    let desynced        = Arc::new(Desync::new(0));
+    let (send, receive) = mpsc::channel();
+
+    // The callback runs on its own queue, so it can read the new value from the object
+    let observed        = Arc::clone(&desynced);
+    let _observer       = desynced.observe(move || send.send(observed.sync(|val| *val)).unwrap());
+
+    desynced.desync(|val| *val = 1);
+    desynced.sync(|val| *val = 2);
+
+    // The callback is called at least once after the last change
+    while receive.recv_timeout(Duration::from_millis(500)).unwrap() != 2 { }
+}
+
+#[test]
+fn dropping_handle_stops_callback() {
+    let desynced    = Desync::new(0);
+    let calls       = Arc::new(Mutex::new(0));
+
+    let observed    = Arc::clone(&calls);
+    let observer    = desynced.observe(move || *observed.lock().unwrap() += 1);
+
+    desynced.sync(|val| *val = 1);
+    drop(observer);
+    desynced.sync(|val| *val = 2);
+    desynced.sync(|val| *val = 3);
+
+    thread::sleep(Duration::from_millis(20));
+    assert!(*calls.lock().unwrap() == 2);
+}
@@ -0,0 +1,75 @@
+extern crate desync;
+extern crate futures;
+
+use desync::*;
+
+use futures::executor;
+use futures::future::{FutureExt};
+
+use std::mem;
+use std::sync::*;
+use std::sync::mpsc;
+use std::time::Duration;
+
+#[test]
+fn observer_receives_panic_payload() {
+    let desynced        = Desync::new(0);
+    let (send, recv)    = mpsc::channel();
+
+    let _observer = desynced.observe_panics(move |payload| {
+        send.send(payload.downcast_ref::<&str>().map(|msg| msg.to_string())).ok();
+    });
+
+    desynced.desync(|_| panic!("Test panic"));
+
+    assert!(recv.recv_timeout(Duration::from_millis(1000)).unwrap() == Some("Test panic".to_string()));
+
+    // The queue is still marked as panicked
+    assert!(desynced.try_into_sync(Duration::from_millis(100), |val| *val) == Err(SyncFailed::Panicked));
+    mem::forget(desynced);
+}
+
+#[test]
+fn observer_sees_panics_in_futures() {
+    let desynced        = Desync::new(0);
+    let (send, recv)    = mpsc::channel();
+
+    let _observer = desynced.observe_panics(move |payload| {
+        send.send(payload.downcast_ref::<&str>().map(|msg| msg.to_string())).ok();
+    });
+
+    let result = desynced.future_desync(|_| async { panic!("Future panic") }.boxed());
+
+    assert!(executor::block_on(result).is_err());
+    assert!(recv.recv_timeout(Duration::from_millis(1000)).unwrap() == Some("Future panic".to_string()));
+    mem::forget(desynced);
+}
+
+#[test]
+fn dropping_handle_removes_observer() {
+    let desynced        = Desync::new(0);
+    let calls           = Arc::new(Mutex::new(0));
+
+    let observer_calls  = Arc::clone(&calls);
+    let observer        = desynced.observe_panics(move |_| *observer_calls.lock().unwrap() += 1);
+    mem::drop(observer);
+
+    desynced.desync(|_| panic!("Test panic"));
+
+    assert!(desynced.try_into_sync(Duration::from_millis(100), |val| *val) == Err(SyncFailed::Panicked));
+    assert!(*calls.lock().unwrap() == 0);
+    mem::forget(desynced);
+}
+
+#[test]
+fn jobs_that_do_not_panic_are_not_observed() {
+    let desynced        = Desync::new(0);
+    let calls           = Arc::new(Mutex::new(0));
+
+    let observer_calls  = Arc::clone(&calls);
+    let _observer       = desynced.observe_panics(move |_| *observer_calls.lock().unwrap() += 1);
+
+    desynced.desync(|val| *val += 1);
+    assert!(desynced.sync(|val| *val) == 1);
+    assert!(*calls.lock().unwrap() == 0);
+}
@@ -10,9 +10,10 @@ use futures::stream::{StreamExt};
 use futures::channel::mpsc;
 use futures::prelude::*;
 
+use std::mem;
 use std::sync::*;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 #[test]
 fn pipe_in_simple_stream() {
@@ -33,6 +34,31 @@ fn pipe_in_simple_stream() {
     assert!(obj.sync(|core| core.clone()) == vec![Ok(1), Ok(2), Ok(3)])
 }
 
+#[test]
+fn pipe_in_async_processes_items_in_order() {
+    // Future that completes on another thread after a delay
+    fn delay(millis: u64) -> impl Future<Output=()> {
+        let (done, wait) = futures::channel::oneshot::channel();
+        thread::spawn(move || { thread::sleep(Duration::from_millis(millis)); done.send(()).ok(); });
+
+        wait.map(|_| ())
+    }
+
+    let stream  = stream::iter(vec![1, 2, 3]);
+    let obj     = Arc::new(Desync::new(vec![]));
+
+    // The earlier items take longer to process, so they would finish last if the items weren't processed in order
+    pipe_in_async(Arc::clone(&obj), stream, async |core: &mut Vec<(i32, bool)>, item| {
+        core.push((item, false));
+        delay(10 * (4 - item as u64)).await;
+        core.push((item, true));
+    });
+
+    thread::sleep(Duration::from_millis(100));
+
+    assert!(obj.sync(|core| core.clone()) == vec![(1, false), (1, true), (2, false), (2, true), (3, false), (3, true)]);
+}
+
 #[test]
 fn pipe_in_mpsc_receiver() {
     // Create a channel to send to the object
@@ -151,3 +177,233 @@ fn pipe_through_produces_backpressure() {
         assert!(channel_full.unwrap_err().is_full());
     });
 }
+
+#[test]
+fn pipe_with_capacity_resumes_when_consumed() {
+    // Create a channel we'll use to send data to the pipe
+    let (mut sender, receiver) = mpsc::channel(0);
+
+    // Create a pipe with a capacity of 2
+    let obj             = Arc::new(Desync::new(0));
+    let mut pipe_out    = pipe_with_capacity(Arc::clone(&obj), receiver, 2, |_core, item: i32| future::ready(item).boxed());
+
+    executor::block_on(async {
+        // Fill the pipe
+        for x in 0..2 {
+            assert!(sender.try_send(x) == Ok(()));
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        // This will stick in the channel, and the next one will be refused
+        assert!(sender.try_send(2) == Ok(()));
+        thread::sleep(Duration::from_millis(5));
+        assert!(sender.try_send(3).unwrap_err().is_full());
+
+        // Reading from the pipe makes space for the producer
+        assert!(pipe_out.next().await == Some(0));
+        thread::sleep(Duration::from_millis(5));
+        assert!(sender.try_send(3) == Ok(()));
+
+        assert!(pipe_out.next().await == Some(1));
+        assert!(pipe_out.next().await == Some(2));
+        assert!(pipe_out.next().await == Some(3));
+    });
+}
+
+#[test]
+fn pipe_bounded_resumes_at_low_water_mark() {
+    // Create a channel we'll use to send data to the pipe
+    let (mut sender, receiver) = mpsc::channel(0);
+
+    // Create a pipe with a capacity of 4, which will resume once there are 2 items left in it
+    let obj             = Arc::new(Desync::new(0));
+    let mut pipe_out    = pipe_bounded(Arc::clone(&obj), receiver, 4, |_core, item: i32| future::ready(item).boxed());
+
+    executor::block_on(async {
+        // Fill the pipe
+        for x in 0..4 {
+            assert!(sender.try_send(x) == Ok(()));
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        // This will stick in the channel, and the next one will be refused
+        assert!(sender.try_send(4) == Ok(()));
+        thread::sleep(Duration::from_millis(5));
+        assert!(sender.try_send(5).unwrap_err().is_full());
+
+        // Reading one item is not enough for the pipe to resume
+        assert!(pipe_out.next().await == Some(0));
+        thread::sleep(Duration::from_millis(5));
+        assert!(sender.try_send(5).unwrap_err().is_full());
+
+        // Reading down to the low-water mark makes space for the producer
+        assert!(pipe_out.next().await == Some(1));
+        thread::sleep(Duration::from_millis(5));
+        assert!(sender.try_send(5) == Ok(()));
+
+        for x in 2..6 {
+            assert!(pipe_out.next().await == Some(x));
+        }
+    });
+}
+
+#[test]
+fn pipe_fan_out_sends_every_item_to_every_target() {
+    let targets = vec![Arc::new(Desync::new(vec![])), Arc::new(Desync::new(vec![]))];
+    let stream  = stream::iter(vec![1, 2, 3]);
+
+    pipe_fan_out(targets.clone(), stream, |core: &mut Vec<i32>, item| core.push(item));
+
+    thread::sleep(Duration::from_millis(20));
+
+    for target in targets.iter() {
+        assert!(target.sync(|core| core.clone()) == vec![1, 2, 3]);
+    }
+}
+
+#[test]
+fn pipe_fan_out_skips_panicked_target() {
+    let panicking   = Arc::new(Desync::new(vec![]));
+    let working     = Arc::new(Desync::new(vec![]));
+    let stream      = stream::iter(vec![1, 2, 3]);
+
+    panicking.desync(|_| panic!("Panic on the queue"));
+    pipe_fan_out(vec![Arc::clone(&panicking), Arc::clone(&working)], stream, |core: &mut Vec<i32>, item| core.push(item));
+
+    thread::sleep(Duration::from_millis(100));
+
+    assert!(working.sync(|core| core.clone()) == vec![1, 2, 3]);
+
+    // Dropping an object with a panicked queue panics, so leak it instead
+    mem::forget(panicking);
+}
+
+#[test]
+fn pipe_fan_out_is_not_held_up_by_busy_target() {
+    let busy        = Arc::new(Desync::new(vec![]));
+    let idle        = Arc::new(Desync::new(vec![]));
+    let stream      = stream::iter(vec![1, 2, 3]);
+
+    // The busy target can't process anything until this job finishes
+    busy.desync(|_| thread::sleep(Duration::from_millis(200)));
+    pipe_fan_out(vec![Arc::clone(&busy), Arc::clone(&idle)], stream, |core: &mut Vec<i32>, item| core.push(item));
+
+    thread::sleep(Duration::from_millis(50));
+
+    assert!(idle.sync(|core| core.clone()) == vec![1, 2, 3]);
+    assert!(busy.sync(|core| core.clone()) == vec![1, 2, 3]);
+}
+
+#[test]
+fn pipe_round_robin_accepts_fn_mut() {
+    let target      = Arc::new(Desync::new(vec![]));
+    let stream      = stream::iter(vec![1, 2, 3]);
+    let mut total   = 0;
+
+    pipe_round_robin(vec![Arc::clone(&target)], stream, move |core: &mut Vec<i32>, item| { total += item; core.push(total); });
+
+    thread::sleep(Duration::from_millis(20));
+
+    assert!(target.sync(|core| core.clone()) == vec![1, 3, 6]);
+}
+
+#[test]
+fn pipe_round_robin_sends_each_item_to_one_target() {
+    let targets = vec![Arc::new(Desync::new(vec![])), Arc::new(Desync::new(vec![])), Arc::new(Desync::new(vec![]))];
+    let stream  = stream::iter(vec![1, 2, 3, 4, 5]);
+
+    pipe_round_robin(targets.clone(), stream, |core: &mut Vec<i32>, item| core.push(item));
+
+    thread::sleep(Duration::from_millis(20));
+
+    assert!(targets[0].sync(|core| core.clone()) == vec![1, 4]);
+    assert!(targets[1].sync(|core| core.clone()) == vec![2, 5]);
+    assert!(targets[2].sync(|core| core.clone()) == vec![3]);
+}
+
+#[test]
+fn pipe_in_reconnecting_reads_from_each_connection() {
+    // Each connection produces two items, tagged with the connection number
+    let connections = Arc::new(Mutex::new(0));
+    let connect     = {
+        let connections = Arc::clone(&connections);
+        move || {
+            let mut connections = connections.lock().unwrap();
+            *connections += 1;
+            stream::iter(vec![(*connections, 1), (*connections, 2)])
+        }
+    };
+
+    // Pipe the stream into an object
+    let obj = Arc::new(Desync::new(vec![]));
+    pipe_in_reconnecting(Arc::clone(&obj), connect, ReconnectPolicy::Backoff(ExponentialBackoff { initial_delay: Duration::from_millis(5), max_delay: Duration::from_millis(20), multiplier: 2.0 }),
+        |core: &mut Vec<(i32, i32)>, item| { core.push(item); Box::pin(future::ready(())) });
+
+    // Delay to allow several connections to be made
+    thread::sleep(Duration::from_millis(50));
+
+    // Items from several connections should have arrived, in order
+    let received = obj.sync(|core| core.clone());
+    assert!(received.len() >= 6);
+    assert!(received[0..6] == [(1, 1), (1, 2), (2, 1), (2, 2), (3, 1), (3, 2)]);
+}
+
+#[test]
+fn pipe_in_reconnecting_stops_when_empty_streams_target_is_dropped() {
+    // Every connection ends immediately without producing anything
+    let connections = Arc::new(Mutex::new(0));
+    let connect     = {
+        let connections = Arc::clone(&connections);
+        move || {
+            *connections.lock().unwrap() += 1;
+            stream::iter(Vec::<i32>::new())
+        }
+    };
+
+    let obj = Arc::new(Desync::new(vec![]));
+    pipe_in_reconnecting(Arc::clone(&obj), connect, ReconnectPolicy::Immediate, |core: &mut Vec<i32>, item| { core.push(item); Box::pin(future::ready(())) });
+
+    thread::sleep(Duration::from_millis(50));
+    mem::drop(obj);
+    thread::sleep(Duration::from_millis(50));
+
+    // The pipe stops reconnecting once the target has gone
+    let after_drop = *connections.lock().unwrap();
+    thread::sleep(Duration::from_millis(50));
+    assert!(*connections.lock().unwrap() == after_drop);
+    assert!(after_drop < 20);
+}
+
+#[test]
+fn pipe_throttle_limits_item_rate() {
+    // Allows a burst of 2 items, then one item every 25ms
+    let obj     = Arc::new(Desync::new(vec![]));
+    let start   = Instant::now();
+
+    pipe_throttle(Arc::clone(&obj), stream::iter(0..5), RateLimit::new(2, Duration::from_millis(50)),
+        move |core: &mut Vec<(i32, Duration)>, item| { core.push((item, start.elapsed())); Box::pin(future::ready(())) });
+
+    thread::sleep(Duration::from_millis(40));
+    let received = obj.sync(|core| core.clone());
+    assert!(received.len() < 5);
+
+    while obj.sync(|core| core.len()) < 5 {
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    // All the items arrive in order, and the last has to wait for 3 intervals after the burst
+    let received = obj.sync(|core| core.clone());
+    assert!(received.iter().map(|(item, _)| *item).collect::<Vec<_>>() == vec![0, 1, 2, 3, 4]);
+    assert!(received[4].1 >= Duration::from_millis(70));
+}
+
+#[test]
+fn leaky_bucket_reports_wait_time() {
+    let mut bucket = LeakyBucket::new(RateLimit::per_second(2));
+
+    assert!(bucket.try_add().is_ok());
+    assert!(bucket.try_add().is_ok());
+
+    let wait = bucket.try_add().unwrap_err();
+    assert!(wait > Duration::from_millis(400) && wait <= Duration::from_millis(500));
+}
@@ -0,0 +1,50 @@
+extern crate desync;
+
+use desync::*;
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn sync_calls_are_spaced_out() {
+    let desynced    = Desync::new(0);
+    let limited     = desynced.rate_limit_sync(50.0);
+    let start       = Instant::now();
+
+    // 20ms between calls, so the last of these runs at least 80ms after the first
+    for _ in 0..5 {
+        limited.sync(|val| *val += 1);
+    }
+
+    assert!(start.elapsed() >= Duration::from_millis(80));
+    assert!(desynced.sync(|val| *val) == 5);
+}
+
+#[test]
+fn first_sync_call_does_not_wait() {
+    let desynced    = Desync::new(0);
+    let limited     = desynced.rate_limit_sync(1.0);
+    let start       = Instant::now();
+
+    limited.sync(|val| *val += 1);
+
+    assert!(start.elapsed() < Duration::from_millis(500));
+}
+
+#[test]
+fn desync_calls_are_delayed() {
+    let desynced    = Desync::new(vec![]);
+    let limited     = desynced.rate_limit_sync(20.0);
+
+    // 50ms between calls: the first runs straight away and the rest are delayed
+    for x in 0..3 {
+        limited.desync(move |val| val.push(x));
+    }
+
+    thread::sleep(Duration::from_millis(20));
+    assert!(desynced.sync(|val| val.clone()) == vec![0]);
+
+    // Dropping the limiter waits for the remaining jobs to be scheduled
+    drop(limited);
+    assert!(desynced.sync(|val| val.clone()) == vec![0, 1, 2]);
+}
@@ -0,0 +1,76 @@
+extern crate desync;
+
+use desync::*;
+
+use std::thread;
+use std::sync::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[test]
+fn reader_sees_initial_value() {
+    let desynced            = Desync::new(42);
+    let (reader, _writer)   = desynced.read_write_split();
+
+    assert!(*reader.read() == 42);
+}
+
+#[test]
+fn reader_sees_writes() {
+    let desynced            = Desync::new(0);
+    let (reader, writer)    = desynced.read_write_split();
+
+    writer.write(|val| *val = 1);
+    writer.write(|val| *val += 1);
+    writer.sync();
+
+    assert!(*reader.read() == 2);
+    assert!(desynced.sync(|val| *val) == 2);
+}
+
+#[test]
+fn snapshot_is_unchanged_by_later_writes() {
+    let desynced            = Desync::new(0);
+    let (reader, writer)    = desynced.read_write_split();
+
+    let snapshot            = reader.read();
+    writer.write(|val| *val = 1);
+    writer.sync();
+
+    assert!(*snapshot == 0);
+    assert!(*reader.read() == 1);
+}
+
+#[test]
+fn concurrent_reads_never_see_partial_writes() {
+    let desynced            = Desync::new((0, 0));
+    let (reader, writer)    = desynced.read_write_split();
+    let done                = Arc::new(AtomicBool::new(false));
+
+    // Readers check that both halves of the pair always match
+    let readers = (0..4).map(|_| {
+        let reader  = reader.clone();
+        let done    = Arc::clone(&done);
+
+        thread::spawn(move || {
+            while !done.load(Ordering::Relaxed) {
+                let (first, second) = *reader.read();
+                assert!(first == second);
+            }
+        })
+    }).collect::<Vec<_>>();
+
+    // The writer updates the two halves separately, with a gap in between
+    for _ in 0..200 {
+        writer.write(|pair| {
+            pair.0 += 1;
+            thread::yield_now();
+            pair.1 += 1;
+        });
+    }
+    writer.sync();
+
+    done.store(true, Ordering::Relaxed);
+    readers.into_iter().for_each(|reader| reader.join().unwrap());
+
+    assert!(*reader.read() == (200, 200));
+}
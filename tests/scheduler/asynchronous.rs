@@ -39,6 +39,58 @@ fn panicking_panics_with_future_queues() {
     }, 100);
 }
 
+#[test]
+fn panic_message_includes_queue_name() {
+    timeout(|| {
+        use std::panic;
+
+        let queue       = JobQueue::with_name("test queue");
+
+        desync(&queue, move || {
+            panic!("Oh dear");
+        });
+
+        while !queue.is_panicked() {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        let payload     = panic::catch_unwind(|| { desync(&queue, move || { }); }).unwrap_err();
+        let message     = payload.downcast_ref::<String>().unwrap();
+
+        assert!(message.contains("queue 'test queue'"));
+    }, 100);
+}
+
+#[test]
+fn named_queue_debug_output() {
+    let queue = JobQueue::with_name("test queue");
+
+    assert!(queue.name() == Some("test queue".to_string()));
+    assert!(format!("{:?}", queue).contains("'test queue'"));
+}
+
+#[test]
+fn blocked_desync_panics_if_bounded_queue_panics() {
+    timeout(|| {
+        use std::panic;
+
+        let queue               = JobQueue::new_bounded(1);
+        let (release, blocked)  = channel::<()>();
+
+        desync(&queue, move || { blocked.recv().unwrap(); panic!("Oh dear"); });
+        desync(&queue, move || { });
+
+        // The queue is full, so this waits until the first job panics
+        let producer_queue      = Arc::clone(&queue);
+        let producer            = thread::spawn(move || panic::catch_unwind(|| { desync(&producer_queue, move || { }); }).is_err());
+
+        thread::sleep(Duration::from_millis(20));
+        release.send(()).unwrap();
+
+        assert!(producer.join().unwrap());
+    }, 500);
+}
+
 #[test]
 fn async_only_runs_once() {
     for _x in 0..1000 {
@@ -154,3 +206,58 @@ fn will_schedule_separate_queues_in_parallel() {
         assert!(rx.recv().unwrap() == true);
     }, 500);
 }
+
+#[test]
+fn schedule_from_16_threads_at_once() {
+    timeout(|| {
+        let (tx, rx)    = channel();
+        let threads     = (0..16).map(|_| {
+            let tx = tx.clone();
+
+            thread::spawn(move || {
+                // Each thread creates its own queues so they all contend on the scheduler
+                let queues = (0..16).map(|_| queue()).collect::<Vec<_>>();
+
+                for _ in 0..100 {
+                    for queue in queues.iter() {
+                        let tx = tx.clone();
+                        desync(queue, move || { tx.send(1).unwrap(); });
+                    }
+                }
+            })
+        }).collect::<Vec<_>>();
+
+        threads.into_iter().for_each(|thread| thread.join().unwrap());
+
+        let total: i32 = (0..16*16*100).map(|_| rx.recv().unwrap()).sum();
+        assert!(total == 16*16*100);
+    }, 5000);
+}
+
+#[test]
+fn run_sync_on_idle_after_waiting_queues() {
+    timeout(|| {
+        let scheduler   = Scheduler::new();
+        let (tx, rx)    = channel();
+
+        // Use a single thread so the queues are started in the order they're scheduled
+        scheduler.set_max_threads(1);
+        scheduler.despawn_threads_if_overloaded();
+
+        for val in 0..3 {
+            let queue   = scheduler.create_job_queue();
+            let tx      = tx.clone();
+
+            scheduler.desync(&queue, move || {
+                thread::sleep(Duration::from_millis(10));
+                tx.send(val).unwrap();
+            });
+        }
+
+        let idle_tx = tx.clone();
+        scheduler.run_sync_on_idle(move || idle_tx.send(3).unwrap());
+        scheduler.run_sync_on_idle(move || tx.send(4).unwrap());
+
+        assert!(rx.iter().take(5).collect::<Vec<_>>() == vec![0, 1, 2, 3, 4]);
+    }, 500);
+}
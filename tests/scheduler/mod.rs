@@ -1,10 +1,14 @@
 pub mod timeout;
+pub mod stress;
 
 mod sync;
 mod asynchronous;
 mod future;
 mod suspend;
 mod thread_management;
+mod queue_group;
+mod state_machine;
+mod shutdown;
 
 extern crate desync;
 extern crate futures;
@@ -0,0 +1,90 @@
+use desync::scheduler::*;
+
+use super::timeout::*;
+
+use futures::executor;
+
+use std::thread;
+use std::time::*;
+use std::sync::*;
+
+#[test]
+fn drain_all_queues_in_group() {
+    timeout(|| {
+        let group   = scheduler().create_queue_group();
+        let queue1  = group.create_job_queue();
+        let queue2  = group.create_job_queue();
+        let count   = Arc::new(Mutex::new(0));
+
+        for queue in [&queue1, &queue2] {
+            let count = Arc::clone(&count);
+            desync(queue, move || { thread::sleep(Duration::from_millis(20)); *count.lock().unwrap() += 1; });
+        }
+
+        executor::block_on(group.drain_all());
+        assert!(*count.lock().unwrap() == 2);
+    }, 500);
+}
+
+#[test]
+fn suspend_and_resume_group() {
+    timeout(|| {
+        let group   = scheduler().create_queue_group();
+        let queue1  = group.create_job_queue();
+        let queue2  = group.create_job_queue();
+        let count   = Arc::new(Mutex::new(0));
+
+        executor::block_on(group.suspend_all());
+
+        for queue in [&queue1, &queue2] {
+            let count = Arc::clone(&count);
+            desync(queue, move || { *count.lock().unwrap() += 1; });
+        }
+
+        // Jobs should not run while the group is suspended
+        thread::sleep(Duration::from_millis(20));
+        assert!(*count.lock().unwrap() == 0);
+
+        group.resume_all();
+        executor::block_on(group.drain_all());
+        assert!(*count.lock().unwrap() == 2);
+    }, 500);
+}
+
+#[test]
+fn cancel_all_cancels_pending_futures() {
+    timeout(|| {
+        let group       = scheduler().create_queue_group();
+        let queue       = group.create_job_queue();
+
+        executor::block_on(group.suspend_all());
+        let cancelled   = future(&queue, || async { 42 });
+
+        group.cancel_all();
+        group.resume_all();
+
+        assert!(executor::block_on(cancelled).is_err());
+
+        // Queue should still be usable after the group is resumed
+        assert!(sync(&queue, || 1) == 1);
+    }, 500);
+}
+
+#[test]
+fn drain_registered_groups() {
+    timeout(|| {
+        let scheduler   = scheduler();
+        let group       = scheduler.create_queue_group();
+        let queue       = group.create_job_queue();
+        let count       = Arc::new(Mutex::new(0));
+        let group_id    = scheduler.register_queue_group(group);
+
+        let job_count   = Arc::clone(&count);
+        desync(&queue, move || { thread::sleep(Duration::from_millis(20)); *job_count.lock().unwrap() += 1; });
+
+        executor::block_on(scheduler.drain_queue_groups());
+        assert!(*count.lock().unwrap() == 1);
+        assert!(scheduler.unregister_queue_group(group_id).is_some());
+        assert!(scheduler.queue_group(group_id).is_none());
+    }, 500);
+}
@@ -0,0 +1,171 @@
+use desync::scheduler::*;
+
+use super::timeout::*;
+
+use futures::executor;
+
+use std::thread;
+use std::time::*;
+use std::sync::*;
+
+#[test]
+fn shutdown_waits_for_jobs_to_finish() {
+    timeout(|| {
+        let scheduler   = Scheduler::new();
+        let queue       = scheduler.create_job_queue();
+        let finished    = Arc::new(Mutex::new(false));
+
+        let job_finished = Arc::clone(&finished);
+        scheduler.desync(&queue, move || {
+            thread::sleep(Duration::from_millis(20));
+            *job_finished.lock().unwrap() = true;
+        });
+
+        let result = executor::block_on(scheduler.shutdown_timeout(Duration::from_millis(1000)));
+
+        assert!(result == ShutdownResult::Clean);
+        assert!(*finished.lock().unwrap());
+    }, 2000);
+}
+
+#[test]
+fn shutdown_removes_jobs_after_timeout() {
+    timeout(|| {
+        let scheduler   = Scheduler::new();
+        let queue       = scheduler.create_job_queue();
+        let ran         = Arc::new(Mutex::new(0));
+
+        // The first job outlasts the timeout, so the others should be removed
+        scheduler.desync(&queue, || thread::sleep(Duration::from_millis(200)));
+        for _ in 0..3 {
+            let ran = Arc::clone(&ran);
+            scheduler.desync(&queue, move || *ran.lock().unwrap() += 1);
+        }
+
+        let result = executor::block_on(scheduler.shutdown_timeout(Duration::from_millis(20)));
+        assert!(result == ShutdownResult::Forced(3));
+
+        thread::sleep(Duration::from_millis(300));
+        assert!(*ran.lock().unwrap() == 0);
+    }, 2000);
+}
+
+#[test]
+fn jobs_are_discarded_after_shutdown() {
+    timeout(|| {
+        let scheduler   = Scheduler::new();
+        let queue       = scheduler.create_job_queue();
+
+        let result      = executor::block_on(scheduler.shutdown_timeout(Duration::from_millis(100)));
+        assert!(result == ShutdownResult::Clean);
+
+        let job         = scheduler.future_desync(&queue, || async { 42 });
+        assert!(executor::block_on(job).is_err());
+    }, 2000);
+}
+
+#[test]
+fn shutdown_timeout_rejects_jobs_while_waiting() {
+    timeout(|| {
+        let scheduler   = Scheduler::new();
+        let queue       = scheduler.create_job_queue();
+        let (tx, rx)    = mpsc::channel();
+
+        scheduler.desync(&queue, move || { rx.recv().ok(); });
+
+        // Jobs are rejected as soon as the shutdown starts, before the jobs in flight have finished
+        let shutdown    = scheduler.shutdown_timeout(Duration::from_millis(1000));
+        assert!(scheduler.desync(&queue, || { }).is_rejected());
+
+        tx.send(()).unwrap();
+        assert!(executor::block_on(shutdown) == ShutdownResult::Clean);
+    }, 2000);
+}
+
+#[test]
+fn dropping_shutdown_future_forces_shutdown() {
+    timeout(|| {
+        let scheduler   = Scheduler::new();
+        let queue       = scheduler.create_job_queue();
+
+        scheduler.desync(&queue, || thread::sleep(Duration::from_millis(100)));
+        let after_long_job = scheduler.future_desync(&queue, || async { 42 });
+
+        // Dropping the future should remove the waiting job straight away instead of waiting for the timeout
+        let shutdown = scheduler.shutdown_timeout(Duration::from_millis(10000));
+        drop(shutdown);
+
+        assert!(executor::block_on(after_long_job).is_err());
+    }, 2000);
+}
+
+#[test]
+fn blocking_shutdown_stops_threads() {
+    timeout(|| {
+        let scheduler   = Scheduler::new();
+        let queue       = scheduler.create_job_queue();
+        let finished    = Arc::new(Mutex::new(false));
+
+        let job_finished = Arc::clone(&finished);
+        scheduler.desync(&queue, move || {
+            thread::sleep(Duration::from_millis(20));
+            *job_finished.lock().unwrap() = true;
+        });
+
+        assert!(!scheduler.is_shutting_down());
+        assert!(scheduler.shutdown(None) == Ok(()));
+
+        assert!(scheduler.is_shutting_down());
+        assert!(*finished.lock().unwrap());
+
+        let metrics = scheduler.metrics();
+        assert!(metrics.max_threads == 0);
+        assert!(metrics.active_thread_count + metrics.idle_thread_count == 0);
+    }, 2000);
+}
+
+#[test]
+fn blocking_shutdown_reports_timeout() {
+    timeout(|| {
+        let scheduler   = Scheduler::new();
+        let queue       = scheduler.create_job_queue();
+
+        scheduler.desync(&queue, || thread::sleep(Duration::from_millis(200)));
+        scheduler.desync(&queue, || { });
+
+        assert!(scheduler.shutdown(Some(Duration::from_millis(20))) == Err(ShutdownTimeout { removed_jobs: 1 }));
+    }, 2000);
+}
+
+#[test]
+fn shutdown_is_reported_after_shutdown() {
+    timeout(|| {
+        let scheduler   = Scheduler::new();
+        let queue       = scheduler.create_job_queue();
+
+        assert!(scheduler.shutdown(Some(Duration::from_millis(100))) == Ok(()));
+
+        assert!(scheduler.desync(&queue, || { }).is_rejected());
+        assert!(scheduler.try_desync(&queue, || { }).err() == Some(TryDesyncError::ShutDown));
+        assert!(scheduler.try_sync(&queue, || 42) == Err(TrySyncError::ShutDown));
+        assert!(scheduler.sync_timeout(&queue, Duration::from_millis(100), || 42) == Err(SyncTimeout::ShutDown));
+    }, 2000);
+}
+
+#[test]
+fn blocking_shutdown_waits_for_threads_to_exit() {
+    timeout(|| {
+        let scheduler   = Scheduler::new();
+        let queues      = (0..4).map(|_| scheduler.create_job_queue()).collect::<Vec<_>>();
+
+        for queue in queues.iter() {
+            scheduler.desync(queue, || thread::sleep(Duration::from_millis(10)));
+        }
+
+        assert!(scheduler.shutdown(Some(Duration::from_millis(1000))) == Ok(()));
+
+        // Every thread should have exited by the time shutdown() returns
+        let metrics = scheduler.metrics();
+        assert!(metrics.active_thread_count + metrics.idle_thread_count == 0);
+    }, 2000);
+}
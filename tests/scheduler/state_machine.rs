@@ -0,0 +1,121 @@
+use desync::Desync;
+
+use super::timeout::*;
+
+use futures::prelude::*;
+use futures::future;
+use futures::executor;
+use futures::channel::oneshot;
+use proptest::prelude::*;
+
+use std::sync::*;
+use std::thread;
+use std::time::Duration;
+
+///
+/// An operation that can be performed on a `Desync<u32>`
+///
+#[derive(Clone, Debug)]
+enum Operation {
+    /// Adds a value in the background
+    Desync(u32),
+
+    /// Adds a value and reads the result synchronously
+    Sync(u32),
+
+    /// Adds a value and reads the result via a future
+    Future(u32),
+
+    /// Blocks the queue until the next `Resume` operation
+    Suspend,
+
+    /// Releases the oldest suspension, if there is one
+    Resume
+}
+
+fn operation() -> impl Strategy<Value=Operation> {
+    prop_oneof![
+        (0..100u32).prop_map(Operation::Desync),
+        (0..100u32).prop_map(Operation::Sync),
+        (0..100u32).prop_map(Operation::Future),
+        Just(Operation::Suspend),
+        Just(Operation::Resume)
+    ]
+}
+
+///
+/// Runs a sequence of operations on a `Desync<u32>`, checking the results against sequential execution, then drops it
+///
+fn run_operations(operations: Vec<Operation>) {
+    let (final_value, read_final_value) = mpsc::channel();
+    let desynced                        = Desync::with_drop_handler(0u32, move |val| { final_value.send(val).ok(); });
+
+    let mut expected    = 0u32;
+    let mut suspensions = vec![];
+    let mut suspended   = vec![];
+    let mut futures     = vec![];
+
+    for operation in operations {
+        match operation {
+            Operation::Desync(val)  => {
+                expected = expected.wrapping_add(val);
+                desynced.desync(move |current| *current = current.wrapping_add(val));
+            }
+
+            Operation::Sync(val)    => {
+                // A sync operation would wait forever if the queue is suspended, so resume everything first
+                suspensions.drain(..).for_each(|resume: oneshot::Sender<()>| { resume.send(()).ok(); });
+
+                expected = expected.wrapping_add(val);
+                assert!(desynced.sync(move |current| { *current = current.wrapping_add(val); *current }) == expected);
+            }
+
+            Operation::Future(val)  => {
+                expected = expected.wrapping_add(val);
+                futures.push((expected, desynced.future_desync(move |current| { *current = current.wrapping_add(val); future::ready(*current).boxed() })));
+            }
+
+            Operation::Suspend      => {
+                let (resume, wait_for_resume) = oneshot::channel();
+                suspensions.push(resume);
+
+                suspended.push(desynced.future_desync(move |_| async move { wait_for_resume.await.ok(); }.boxed()));
+            }
+
+            Operation::Resume       => {
+                if !suspensions.is_empty() {
+                    suspensions.remove(0).send(()).ok();
+                }
+            }
+        }
+    }
+
+    // Drop the object while it may still be suspended (it has to wait for the queue to resume)
+    let resumer = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(5));
+        suspensions.into_iter().for_each(|resume| { resume.send(()).ok(); });
+    });
+
+    drop(desynced);
+    resumer.join().unwrap();
+
+    // Every job must have run, in order
+    assert!(read_final_value.recv().unwrap() == expected);
+
+    for (expected, future) in futures {
+        assert!(executor::block_on(future) == Ok(expected));
+    }
+
+    for future in suspended {
+        assert!(executor::block_on(future) == Ok(()));
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn operations_match_sequential_execution(operations in prop::collection::vec(operation(), 0..20)) {
+        timeout(move || run_operations(operations), 2000);
+    }
+}
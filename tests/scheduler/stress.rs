@@ -0,0 +1,32 @@
+use desync::Desync;
+use desync::scheduler::QueueState;
+
+use std::thread;
+use std::sync::*;
+
+///
+/// An operation that a stress test performs on a `Desync` object
+///
+pub type StressOp<T> = Arc<dyn Fn(&Arc<Desync<T>>)+Send+Sync>;
+
+///
+/// Runs an operation on a `Desync` object from several threads at once, and checks that the object's queue
+/// has not panicked once all of the threads have finished
+///
+pub fn run_stress_test<T: 'static+Send+Unpin>(desync: Arc<Desync<T>>, num_threads: usize, ops_per_thread: usize, op: StressOp<T>) {
+    let threads = (0..num_threads).map(|_| {
+        let desync  = Arc::clone(&desync);
+        let op      = Arc::clone(&op);
+
+        thread::spawn(move || {
+            for _ in 0..ops_per_thread {
+                op(&desync);
+            }
+        })
+    }).collect::<Vec<_>>();
+
+    threads.into_iter().for_each(|thread| thread.join().expect("Stress test thread"));
+
+    let state = desync.health_check().state;
+    assert!(state != QueueState::Panicked, "Queue state after stress test: {:?}", state);
+}
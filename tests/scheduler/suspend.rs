@@ -112,3 +112,52 @@ fn safe_to_drop_suspended_queue() {
         executor::block_on(suspended).unwrap();
     }, 500);
 }
+
+#[test]
+fn suspension_guard_resumes_queue_when_dropped() {
+    timeout(|| {
+        use futures::executor;
+
+        let queue           = queue();
+        let scheduler       = scheduler();
+        let (tx, rx)        = channel();
+
+        let guard           = executor::block_on(scheduler.suspend_guard(&queue)).unwrap();
+        assert!(Arc::ptr_eq(guard.queue(), &queue));
+
+        desync(&queue, move || { tx.send(42).unwrap(); });
+
+        // The job can't run until the guard is dropped
+        assert!(rx.recv_timeout(Duration::from_millis(20)).is_err());
+
+        drop(guard);
+        assert!(rx.recv().unwrap() == 42);
+    }, 500);
+}
+
+#[test]
+fn resumer_only_resumes_its_own_queue() {
+    timeout(|| {
+        use futures::executor;
+
+        let queue1          = queue();
+        let queue2          = queue();
+        let scheduler       = scheduler();
+        let (tx1, rx1)      = channel();
+        let (tx2, rx2)      = channel();
+
+        let resumer1        = executor::block_on(scheduler.suspend(&queue1)).unwrap();
+        let resumer2        = executor::block_on(scheduler.suspend(&queue2)).unwrap();
+
+        desync(&queue1, move || { tx1.send(1).unwrap(); });
+        desync(&queue2, move || { tx2.send(2).unwrap(); });
+
+        // Resuming the first queue leaves the second one suspended
+        resumer1.resume();
+        assert!(rx1.recv().unwrap() == 1);
+        assert!(rx2.recv_timeout(Duration::from_millis(20)).is_err());
+
+        resumer2.resume();
+        assert!(rx2.recv().unwrap() == 2);
+    }, 500);
+}
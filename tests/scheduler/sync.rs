@@ -102,3 +102,114 @@ fn sync_drains_with_no_threads() {
         assert!(new_val == 42);
     }, 500);
 }
+
+#[test]
+fn sync_drain_does_not_miss_wake_before_park() {
+    timeout(|| {
+        use futures::channel::oneshot;
+
+        let scheduler   = Scheduler::new();
+
+        // With 0 threads, sync will drain the queue on the current thread, parking when the future blocks
+        scheduler.set_max_threads(0);
+        scheduler.despawn_threads_if_overloaded();
+
+        for _ in 0..100 {
+            let queue       = queue();
+            let (tx, rx)    = oneshot::channel::<i32>();
+
+            let future      = scheduler.future(&queue, move || async {
+                rx.await.expect("Receive result")
+            });
+
+            // Send the value at around the point where the draining thread is about to park
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(1));
+                tx.send(42).expect("Send")
+            });
+
+            assert!(scheduler.sync(&queue, || 1) == 1);
+            assert!(futures::executor::block_on(future).expect("result") == 42);
+        }
+    }, 2000);
+}
+
+#[test]
+fn drain_waits_for_all_queues() {
+    timeout(|| {
+        use futures::executor;
+
+        let scheduler   = Scheduler::new();
+        let queues      = (0..4).map(|_| scheduler.create_job_queue()).collect::<Vec<_>>();
+        let finished    = Arc::new(Mutex::new(0));
+
+        for queue in queues.iter() {
+            let finished = Arc::clone(&finished);
+            scheduler.desync(queue, move || { thread::sleep(Duration::from_millis(20)); *finished.lock().unwrap() += 1; });
+        }
+
+        executor::block_on(scheduler.drain());
+        assert!(*finished.lock().unwrap() == 4);
+    }, 500);
+}
+
+#[test]
+fn join_queues_waits_for_every_queue() {
+    timeout(|| {
+        use futures::executor;
+
+        let queues      = (0..3).map(|_| queue()).collect::<Vec<_>>();
+        let finished    = Arc::new(Mutex::new(vec![]));
+
+        for (index, queue) in queues.iter().enumerate() {
+            let finished = Arc::clone(&finished);
+            desync(queue, move || { thread::sleep(Duration::from_millis(10 * (3 - index as u64))); finished.lock().unwrap().push(index); });
+        }
+
+        let joined      = Arc::clone(&finished);
+        let count       = executor::block_on(join_queues(&queues, move || joined.lock().unwrap().len()));
+
+        assert!(count == 3);
+    }, 500);
+}
+
+#[test]
+fn join_no_queues() {
+    timeout(|| {
+        use futures::executor;
+
+        assert!(executor::block_on(join_queues(&[], || 42)) == 42);
+    }, 500);
+}
+
+#[test]
+fn select_runs_on_first_queue_to_catch_up() {
+    timeout(|| {
+        let queues      = (0..3).map(|_| queue()).collect::<Vec<_>>();
+
+        // The last queue finishes its backlog first
+        for (index, queue) in queues.iter().enumerate() {
+            desync(queue, move || thread::sleep(Duration::from_millis(100 - 40 * index as u64)));
+        }
+
+        let selected    = scheduler().select(&queues, Arc::clone);
+        assert!(Arc::ptr_eq(&selected, &queues[2]));
+    }, 500);
+}
+
+#[test]
+fn select_skips_panicked_queues() {
+    timeout(|| {
+        let queues      = (0..2).map(|_| queue()).collect::<Vec<_>>();
+        let (tx, rx)    = channel();
+
+        desync(&queues[0], move || { rx.recv().ok(); panic!("Queue panicked"); });
+        desync(&queues[1], move || thread::sleep(Duration::from_millis(50)));
+
+        // The first queue panics while select() is waiting, so the second queue is chosen once it catches up
+        thread::spawn(move || { thread::sleep(Duration::from_millis(10)); tx.send(()).unwrap(); });
+        let selected    = scheduler().select(&queues, Arc::clone);
+
+        assert!(Arc::ptr_eq(&selected, &queues[1]));
+    }, 500);
+}
@@ -1,5 +1,12 @@
 use desync::scheduler::*;
 
+use super::timeout::*;
+
+use std::thread;
+use std::time::*;
+use std::sync::*;
+use std::sync::mpsc::*;
+
 #[test]
 fn will_despawn_extra_threads() {
     // As we join with the threads, we'll timeout if any of the spawned threads fail to end
@@ -13,3 +20,225 @@ fn will_despawn_extra_threads() {
 
     scheduler.despawn_threads_if_overloaded();
 }
+
+#[test]
+fn run_jobs_with_spawner() {
+    timeout(|| {
+        // Spawner that counts how often it's used and runs jobs on a new thread
+        let spawn_count     = Arc::new(Mutex::new(0));
+        let spawner_count   = Arc::clone(&spawn_count);
+        let scheduler       = Scheduler::new_with_spawner(Arc::new(move |job: Box<dyn FnOnce()+Send>| {
+            *spawner_count.lock().unwrap() += 1;
+            thread::spawn(job);
+        }));
+
+        let queue           = scheduler.create_job_queue();
+        let (tx, rx)        = channel();
+
+        scheduler.desync(&queue, move || { tx.send(42).unwrap(); });
+
+        assert!(rx.recv().unwrap() == 42);
+        assert!(*spawn_count.lock().unwrap() > 0);
+    }, 500);
+}
+
+#[test]
+fn work_limited_scheduler_limits_jobs_in_flight() {
+    timeout(|| {
+        let scheduler   = Scheduler::new_work_limited(2);
+        let running     = Arc::new(Mutex::new((0, 0)));
+        let (tx, rx)    = channel();
+
+        // Run 8 jobs on separate queues, tracking the most that are running at once
+        for _ in 0..8 {
+            let queue   = scheduler.create_job_queue();
+            let running = Arc::clone(&running);
+            let tx      = tx.clone();
+
+            scheduler.desync(&queue, move || {
+                { let mut running = running.lock().unwrap(); running.0 += 1; running.1 = running.1.max(running.0); }
+                thread::sleep(Duration::from_millis(20));
+                { running.lock().unwrap().0 -= 1; }
+
+                tx.send(()).unwrap();
+            });
+        }
+
+        rx.iter().take(8).for_each(|_| { });
+
+        assert!(running.lock().unwrap().1 == 2);
+    }, 1000);
+}
+
+#[test]
+#[should_panic]
+fn work_limited_scheduler_needs_at_least_one_job_in_flight() {
+    Scheduler::new_work_limited(0);
+}
+
+#[test]
+fn threads_have_default_names() {
+    timeout(|| {
+        let scheduler   = Scheduler::new();
+        let queue       = scheduler.create_job_queue();
+        let (tx, rx)    = channel();
+
+        scheduler.desync(&queue, move || { tx.send(thread::current().name().map(|name| name.to_string())).unwrap(); });
+
+        let name = rx.recv().unwrap().unwrap();
+        assert!(name.starts_with("desync-worker-"), "{}", name);
+    }, 500);
+}
+
+#[test]
+fn threads_use_configured_names() {
+    timeout(|| {
+        let scheduler   = Scheduler::new_with_config(SchedulerConfig { thread_name_fn: Some(Box::new(|index| format!("test-pool-{}", index))) });
+        let queue       = scheduler.create_job_queue();
+        let (tx, rx)    = channel();
+
+        scheduler.desync(&queue, move || { tx.send(thread::current().name().map(|name| name.to_string())).unwrap(); });
+
+        assert!(rx.recv().unwrap() == Some("test-pool-0".to_string()));
+    }, 500);
+}
+
+#[test]
+fn thread_recovers_after_job_panics() {
+    timeout(|| {
+        // With only one thread, a thread that was lost to a panic would stop all further work
+        let scheduler       = Scheduler::new().with_panic_on_thread_death(false);
+        scheduler.set_max_threads(1);
+
+        let panicking_queue = scheduler.create_job_queue();
+        let queue           = scheduler.create_job_queue();
+        let (tx, rx)        = channel();
+
+        scheduler.desync(&panicking_queue, || panic!("Test panic"));
+        thread::sleep(Duration::from_millis(20));
+
+        scheduler.desync(&queue, move || { tx.send(42).unwrap(); });
+
+        assert!(rx.recv().unwrap() == 42);
+        assert!(panicking_queue.is_panicked());
+    }, 500);
+}
+
+#[test]
+fn job_panic_does_not_abort_when_panic_on_thread_death_is_set() {
+    timeout(|| {
+        // Only panics outside of a job should abort the process
+        let scheduler       = Scheduler::new().with_panic_on_thread_death(true);
+        scheduler.set_max_threads(1);
+
+        let panicking_queue = scheduler.create_job_queue();
+        let queue           = scheduler.create_job_queue();
+        let (tx, rx)        = channel();
+
+        scheduler.desync(&panicking_queue, || panic!("Test panic"));
+        thread::sleep(Duration::from_millis(20));
+
+        scheduler.desync(&queue, move || { tx.send(42).unwrap(); });
+
+        assert!(rx.recv().unwrap() == 42);
+        assert!(panicking_queue.is_panicked());
+    }, 500);
+}
+
+#[test]
+fn metrics_report_busy_threads_and_waiting_queues() {
+    timeout(|| {
+        let scheduler       = Scheduler::new();
+        scheduler.set_max_threads(1);
+
+        let blocked_queue   = scheduler.create_job_queue();
+        let waiting_queue   = scheduler.create_job_queue();
+        let (started, wait_started) = channel();
+        let (release, wait_release) = channel::<()>();
+
+        // Occupy the only thread, so the second queue has to wait for it
+        scheduler.desync(&blocked_queue, move || { started.send(()).unwrap(); wait_release.recv().ok(); });
+        wait_started.recv().unwrap();
+        scheduler.desync(&waiting_queue, || { });
+
+        assert!(scheduler.metrics() == SchedulerMetrics { active_thread_count: 1, idle_thread_count: 0, pending_queue_count: 1, max_threads: 1 });
+
+        // Once everything has finished, the thread is idle
+        release.send(()).unwrap();
+        scheduler.sync(&waiting_queue, || { });
+        while scheduler.metrics().active_thread_count > 0 {
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        assert!(scheduler.metrics() == SchedulerMetrics { active_thread_count: 0, idle_thread_count: 1, pending_queue_count: 0, max_threads: 1 });
+    }, 500);
+}
+
+#[test]
+fn autoscale_adds_threads_for_waiting_queues_and_removes_them_when_idle() {
+    timeout(|| {
+        let scheduler = Scheduler::new();
+        scheduler.enable_autoscale_with_idle_time(1, 4, 0, Duration::from_millis(50));
+
+        // Four queues that all block until they're released
+        let (release, wait_release) = channel::<()>();
+        let wait_release            = Arc::new(Mutex::new(wait_release));
+        let (started, wait_started) = channel();
+        let queues                  = (0..4).map(|_| scheduler.create_job_queue()).collect::<Vec<_>>();
+
+        for queue in queues.iter() {
+            let started         = started.clone();
+            let wait_release    = Arc::clone(&wait_release);
+            scheduler.desync(queue, move || { started.send(()).unwrap(); wait_release.lock().unwrap().recv().ok(); });
+        }
+
+        // With only one thread to start with, the queues can only all run once the autoscaler has added more
+        for _ in 0..4 {
+            wait_started.recv().unwrap();
+        }
+        assert!(scheduler.metrics().max_threads == 4);
+
+        // Once the queues have finished, the autoscaler removes the extra threads
+        for _ in 0..4 {
+            release.send(()).unwrap();
+        }
+        while scheduler.metrics().max_threads > 1 {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let metrics = scheduler.metrics();
+        assert!(metrics.active_thread_count + metrics.idle_thread_count == 1);
+    }, 2000);
+}
+
+#[test]
+fn dedicated_thread_runs_queue() {
+    timeout(|| {
+        let scheduler   = Scheduler::new();
+        let queue       = JobQueue::with_name("latency");
+        let dedicated   = scheduler.spawn_dedicated_thread(&queue);
+        let (tx, rx)    = channel();
+
+        // Jobs (including ones that are woken after waiting for a future) run on the dedicated thread
+        let thread_name = || thread::current().name().map(|name| name.to_string());
+        let send_name   = tx.clone();
+        scheduler.desync(&queue, move || { send_name.send(thread_name()).unwrap(); });
+        let send_name       = tx.clone();
+        let (wake, wait)    = futures::channel::oneshot::channel::<()>();
+        let woken           = scheduler.future_desync(&queue, move || async move {
+            wait.await.ok();
+            send_name.send(thread_name()).unwrap();
+        });
+        thread::spawn(move || { thread::sleep(Duration::from_millis(10)); wake.send(()).ok(); });
+
+        assert!(rx.recv().unwrap() == Some("desync-dedicated-latency".to_string()));
+        assert!(rx.recv().unwrap() == Some("desync-dedicated-latency".to_string()));
+        assert!(futures::executor::block_on(woken) == Ok(()));
+
+        // Once the thread is despawned, the queue goes back to the shared threads
+        dedicated.despawn().join().unwrap();
+        scheduler.desync(&queue, move || { tx.send(thread_name()).unwrap(); });
+
+        assert!(rx.recv().unwrap() == Some("desync-worker-0".to_string()));
+    }, 500);
+}
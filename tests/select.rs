@@ -0,0 +1,33 @@
+extern crate desync;
+extern crate futures;
+
+use desync::*;
+use futures::executor;
+
+use std::sync::*;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn select_picks_queue_without_backlog() {
+    let busy    = Arc::new(Desync::new(1));
+    let idle    = Arc::new(Desync::new(2));
+
+    busy.desync(|_| thread::sleep(Duration::from_millis(100)));
+
+    let result  = executor::block_on(Arc::clone(&busy).select_with(Arc::clone(&idle), |val| *val, |val| *val));
+
+    assert!(result == Ok(SelectResult::Second(2)));
+}
+
+#[test]
+fn select_picks_first_queue_when_second_is_busy() {
+    let first   = Arc::new(Desync::new("first".to_string()));
+    let second  = Arc::new(Desync::new(42));
+
+    second.desync(|_| thread::sleep(Duration::from_millis(100)));
+
+    let result  = executor::block_on(first.select_with(second, |val| val.clone(), |val| val.to_string()));
+
+    assert!(result.map(|result| result.into_inner()) == Ok("first".to_string()));
+}
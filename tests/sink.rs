@@ -0,0 +1,82 @@
+extern crate desync;
+extern crate futures;
+
+use desync::*;
+
+use futures::executor;
+use futures::stream;
+use futures::sink::{SinkExt};
+
+use std::mem;
+use std::thread;
+use std::sync::*;
+use std::time::{Duration, Instant};
+
+#[test]
+fn send_items_to_sink() {
+    let collected   = Arc::new(Desync::new(vec![]));
+    let mut sink    = collected.as_sink(|collected, item: i32| collected.push(item));
+
+    executor::block_on(async {
+        sink.send_all(&mut stream::iter(vec![Ok(1), Ok(2), Ok(3)])).await.unwrap();
+    });
+
+    assert!(collected.sync(|collected| collected.clone()) == vec![1, 2, 3]);
+}
+
+#[test]
+fn flush_waits_for_items_to_be_processed() {
+    let collected   = Arc::new(Desync::new(vec![]));
+    let mut sink    = collected.as_sink(|collected, item: i32| {
+        thread::sleep(Duration::from_millis(10));
+        collected.push(item);
+    });
+
+    executor::block_on(async {
+        sink.feed(1).await.unwrap();
+        sink.feed(2).await.unwrap();
+        sink.flush().await.unwrap();
+    });
+
+    // Check without waiting for the queue
+    assert!(collected.try_sync(|collected| collected.clone()) == Ok(vec![1, 2]));
+}
+
+#[test]
+fn sink_waits_for_space_in_bounded_queue() {
+    let collected   = Arc::new(Desync::new_bounded(vec![], 1));
+    let mut sink    = collected.as_sink(|collected, item: i32| collected.push(item));
+
+    // Block the queue and fill it
+    collected.desync(|_| thread::sleep(Duration::from_millis(100)));
+    thread::sleep(Duration::from_millis(10));
+    collected.desync(|_| { });
+
+    // Sending to the sink waits for the blocking job to finish
+    let start = Instant::now();
+    executor::block_on(async {
+        sink.send(1).await.unwrap();
+    });
+
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    assert!(collected.sync(|collected| collected.clone()) == vec![1]);
+}
+
+#[test]
+fn sink_reports_panicked_queue() {
+    let collected   = Arc::new(Desync::new(vec![]));
+    let mut sink    = collected.as_sink(|collected: &mut Vec<i32>, item: i32| {
+        if item == 2 { panic!("Panic on the queue"); }
+        collected.push(item);
+    });
+
+    executor::block_on(async {
+        assert!(sink.send(1).await.is_ok());
+        assert!(sink.send(2).await.is_err());
+        assert!(sink.send(3).await.is_err());
+    });
+
+    // Dropping an object with a panicked queue panics, so leak it instead
+    mem::forget(sink);
+    mem::forget(collected);
+}
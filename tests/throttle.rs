@@ -0,0 +1,84 @@
+extern crate desync;
+extern crate futures;
+
+use desync::*;
+
+use futures::executor;
+use futures::future;
+use futures::future::{FutureExt};
+
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn first_trigger_runs_immediately() {
+    let desynced    = Desync::new(0);
+    let throttle    = desynced.throttle(Duration::from_millis(1000), |val| *val += 1);
+
+    throttle.trigger();
+    thread::sleep(Duration::from_millis(20));
+
+    assert!(desynced.sync(|val| *val) == 1);
+}
+
+#[test]
+fn triggers_in_window_are_combined() {
+    let desynced    = Desync::new(0);
+    let throttle    = desynced.throttle(Duration::from_millis(50), |val| *val += 1);
+
+    for _ in 0..10 {
+        throttle.trigger();
+    }
+
+    // One update runs immediately and one at the end of the window
+    thread::sleep(Duration::from_millis(150));
+    assert!(desynced.sync(|val| *val) == 2);
+}
+
+#[test]
+fn dropping_handle_discards_pending_update() {
+    let desynced    = Desync::new(0);
+    let throttle    = desynced.throttle(Duration::from_millis(50), |val| *val += 1);
+
+    throttle.trigger();
+    throttle.trigger();
+    drop(throttle);
+
+    thread::sleep(Duration::from_millis(100));
+    assert!(desynced.sync(|val| *val) == 1);
+}
+
+#[test]
+fn throttled_future_discards_jobs_with_same_key() {
+    let desynced    = Desync::new(0);
+    let interval    = Duration::from_millis(1000);
+
+    let first       = desynced.throttled_future(1, interval, |val| { *val += 1; future::ready(*val).boxed() });
+    let second      = desynced.throttled_future(1, interval, |val| { *val += 1; future::ready(*val).boxed() });
+
+    assert!(executor::block_on(first) == Some(1));
+    assert!(executor::block_on(second).is_none());
+    assert!(desynced.sync(|val| *val) == 1);
+}
+
+#[test]
+fn throttled_future_keys_are_independent() {
+    let desynced    = Desync::new(0);
+    let interval    = Duration::from_millis(1000);
+
+    let first       = desynced.throttled_future(1, interval, |val| { *val += 1; future::ready(*val).boxed() });
+    let second      = desynced.throttled_future(2, interval, |val| { *val += 1; future::ready(*val).boxed() });
+
+    assert!(executor::block_on(first) == Some(1));
+    assert!(executor::block_on(second) == Some(2));
+}
+
+#[test]
+fn throttled_future_runs_again_after_interval() {
+    let desynced    = Desync::new(0);
+    let interval    = Duration::from_millis(20);
+
+    assert!(executor::block_on(desynced.throttled_future(1, interval, |val| { *val += 1; future::ready(*val).boxed() })) == Some(1));
+    thread::sleep(Duration::from_millis(40));
+    assert!(executor::block_on(desynced.throttled_future(1, interval, |val| { *val += 1; future::ready(*val).boxed() })) == Some(2));
+}
@@ -0,0 +1,86 @@
+//!
+//! Tests that `Desync` works when its futures are driven by the Tokio runtime
+//!
+//! These only run with the `tokio-tests` feature: `cargo test --features tokio-tests`
+//!
+
+extern crate desync;
+extern crate futures;
+extern crate tokio;
+
+use desync::*;
+
+use futures::future;
+use futures::stream;
+use futures::future::{FutureExt};
+
+use std::sync::*;
+use std::time::Duration;
+
+/// The longest any of these tests should take
+const TEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[tokio::test(flavor = "multi_thread")]
+async fn await_future_in_tokio_task() {
+    tokio::time::timeout(TEST_TIMEOUT, async {
+        let desynced    = Arc::new(Desync::new(0));
+        let task_desync = Arc::clone(&desynced);
+
+        let result      = tokio::spawn(async move {
+            task_desync.desync(|val| *val = 41);
+            task_desync.future(|val| { *val += 1; future::ready(*val).boxed() }).await
+        }).await.unwrap();
+
+        assert!(result == Ok(42));
+    }).await.expect("Test timed out");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pipe_in_from_tokio_channel() {
+    tokio::time::timeout(TEST_TIMEOUT, async {
+        let desynced            = Arc::new(Desync::new(vec![]));
+        let (sender, receiver)  = tokio::sync::mpsc::channel(4);
+
+        // Tokio's receiver isn't a stream by itself
+        let receiver            = Box::pin(stream::unfold(receiver, |mut receiver| async move { receiver.recv().await.map(|item| (item, receiver)) }));
+
+        pipe_in(Arc::clone(&desynced), receiver, |data, item| { data.push(item); Box::pin(future::ready(())) });
+
+        for item in 1..=3 {
+            sender.send(item).await.unwrap();
+        }
+
+        // Wait for the items to be processed
+        loop {
+            if desynced.future(|data| future::ready(data.len()).boxed()).await == Ok(3) { break; }
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+
+        assert!(desynced.future(|data| future::ready(data.clone()).boxed()).await == Ok(vec![1, 2, 3]));
+    }).await.expect("Test timed out");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn after_tokio_sleep() {
+    tokio::time::timeout(TEST_TIMEOUT, async {
+        let desynced    = Desync::new(0);
+        let result      = desynced.after(tokio::time::sleep(Duration::from_millis(10)), |val, _| { *val = 42; *val }).await;
+
+        assert!(result == Ok(42));
+    }).await.expect("Test timed out");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn sync_from_spawn_blocking() {
+    tokio::time::timeout(TEST_TIMEOUT, async {
+        let desynced    = Arc::new(Desync::new(0));
+        let task_desync = Arc::clone(&desynced);
+
+        let result      = tokio::task::spawn_blocking(move || {
+            task_desync.desync(|val| *val = 41);
+            task_desync.sync(|val| { *val += 1; *val })
+        }).await.unwrap();
+
+        assert!(result == 42);
+    }).await.expect("Test timed out");
+}
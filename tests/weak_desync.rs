@@ -0,0 +1,41 @@
+extern crate desync;
+
+use desync::*;
+
+use std::sync::*;
+
+#[test]
+fn upgrade_while_alive() {
+    let desynced    = Arc::new(Desync::new(1));
+    let weak        = desynced.downgrade();
+
+    assert!(weak.upgrade().unwrap().sync(|val| *val) == 1);
+}
+
+#[test]
+fn upgrade_after_drop() {
+    let desynced    = Arc::new(Desync::new(1));
+    let weak        = desynced.downgrade();
+
+    drop(desynced);
+
+    assert!(weak.upgrade().is_none());
+}
+
+#[test]
+fn weak_desync_does_not_keep_object_alive() {
+    let dropped     = Arc::new(Mutex::new(false));
+    let handler     = Arc::clone(&dropped);
+    let desynced    = Arc::new(Desync::with_drop_handler(0, move |_| *handler.lock().unwrap() = true));
+    let weak        = desynced.downgrade();
+    let weak2       = weak.clone();
+
+    weak.desync(|val| *val += 1).unwrap();
+    weak2.desync(|val| *val += 1).unwrap();
+    assert!(desynced.sync(|val| *val) == 2);
+
+    drop(desynced);
+
+    assert!(*dropped.lock().unwrap());
+    assert!(weak.desync(|val| *val += 1).err() == Some(Dropped));
+}
@@ -0,0 +1,87 @@
+extern crate desync;
+extern crate futures;
+
+use desync::*;
+
+use futures::executor;
+use futures::prelude::*;
+
+#[test]
+fn first_pair_is_initial_values() {
+    let first       = Desync::new(1);
+    let second      = Desync::new("a".to_string());
+    let mut zipped  = first.zip_latest(&second);
+
+    executor::block_on(async {
+        assert!(zipped.next().await == Some((1, "a".to_string())));
+    });
+}
+
+#[test]
+fn updates_to_either_object_produce_pairs() {
+    let first       = Desync::new(1);
+    let second      = Desync::new(10);
+    let mut zipped  = first.zip_latest(&second);
+
+    executor::block_on(async {
+        assert!(zipped.next().await == Some((1, 10)));
+
+        first.sync(|val| *val = 2);
+        assert!(zipped.next().await == Some((2, 10)));
+
+        second.sync(|val| *val = 20);
+        assert!(zipped.next().await == Some((2, 20)));
+    });
+}
+
+#[test]
+fn zip_latest_reports_every_job() {
+    let first       = Desync::new(1);
+    let second      = Desync::new(10);
+    let mut zipped  = first.zip_latest(&second);
+
+    executor::block_on(async {
+        assert!(zipped.next().await == Some((1, 10)));
+
+        // Jobs that don't change the value still generate a pair
+        first.sync(|_| { });
+        assert!(zipped.next().await == Some((1, 10)));
+    });
+}
+
+#[test]
+fn zip_latest_distinct_skips_duplicates() {
+    let first       = Desync::new(1);
+    let second      = Desync::new(10);
+    let mut zipped  = first.zip_latest_distinct(&second);
+
+    executor::block_on(async {
+        assert!(zipped.next().await == Some((1, 10)));
+
+        first.sync(|_| { });
+        second.sync(|val| *val = 10);
+        first.sync(|val| *val = 3);
+
+        assert!(zipped.next().await == Some((3, 10)));
+    });
+}
+
+#[test]
+fn zip_latest_only_keeps_the_most_recent_pair() {
+    let first       = Desync::new(1);
+    let second      = Desync::new(10);
+    let mut zipped  = first.zip_latest(&second);
+
+    executor::block_on(async {
+        assert!(zipped.next().await == Some((1, 10)));
+
+        // Pairs that are generated before the stream is read replace each other
+        first.sync(|val| *val = 2);
+        second.sync(|val| *val = 20);
+        first.sync(|val| *val = 3);
+        assert!(zipped.next().await == Some((3, 20)));
+
+        second.sync(|val| *val = 30);
+        assert!(zipped.next().await == Some((3, 30)));
+    });
+}